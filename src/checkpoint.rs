@@ -0,0 +1,178 @@
+//! Resume point of an interrupted account export.
+//!
+//! Stored per account in `checkpoints/<account>.json` under the app config
+//! directory. Folders are exported in UID order, so every message of the
+//! current folder up to `last_uid` is done. The file is removed once the
+//! whole account has been exported.
+
+use crate::config;
+use crate::utils::sanitize_filename;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where an account export stopped.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportCheckpoint {
+    /// Folders fully exported by the interrupted run.
+    #[serde(default)]
+    pub completed_folders: Vec<String>,
+    /// Folder being exported when the checkpoint was written.
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// UIDVALIDITY of `folder`; UIDs mean nothing once it changes.
+    #[serde(default)]
+    pub uid_validity: Option<u32>,
+    /// Last UID of `folder` that was processed.
+    #[serde(default)]
+    pub last_uid: u32,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl ExportCheckpoint {
+    /// Checkpoint file of `account_name` in the app config directory.
+    pub fn account_path(account_name: &str) -> PathBuf {
+        config::checkpoints_dir().join(format!("{}.json", sanitize_filename(account_name)))
+    }
+
+    /// Load the checkpoint stored at `path` (empty if absent).
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut checkpoint: ExportCheckpoint = if path.exists() {
+            let content = fs::read_to_string(path).context("Failed to read export checkpoint")?;
+            serde_json::from_str(&content).context("Failed to parse export checkpoint")?
+        } else {
+            ExportCheckpoint::default()
+        };
+        checkpoint.path = path.to_path_buf();
+        Ok(checkpoint)
+    }
+
+    /// Write the checkpoint to disk.
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(&self.path, content).context("Failed to write export checkpoint")?;
+        Ok(())
+    }
+
+    /// Remove the checkpoint file: the export finished.
+    pub fn clear(&self) -> Result<()> {
+        if self.path.exists() {
+            fs::remove_file(&self.path).context("Failed to remove export checkpoint")?;
+        }
+        Ok(())
+    }
+
+    /// Whether `folder` was fully exported before the interruption.
+    pub fn is_folder_done(&self, folder: &str) -> bool {
+        self.completed_folders.iter().any(|f| f == folder)
+    }
+
+    /// UID after which to resume `folder`, if it was interrupted with the same UIDVALIDITY.
+    pub fn resume_after(&self, folder: &str, uid_validity: Option<u32>) -> Option<u32> {
+        (self.folder.as_deref() == Some(folder) && self.uid_validity == uid_validity)
+            .then_some(self.last_uid)
+    }
+
+    /// Note that `uid` of `folder` was processed (not saved: see [`save`](Self::save)).
+    pub fn record(&mut self, folder: &str, uid_validity: Option<u32>, uid: u32) {
+        if self.folder.as_deref() != Some(folder) {
+            self.folder = Some(folder.to_string());
+        }
+        self.uid_validity = uid_validity;
+        self.last_uid = uid;
+    }
+
+    /// Mark `folder` as fully exported and save.
+    pub fn complete_folder(&mut self, folder: &str) -> Result<()> {
+        if !self.is_folder_done(folder) {
+            self.completed_folders.push(folder.to_string());
+        }
+        self.folder = None;
+        self.uid_validity = None;
+        self.last_uid = 0;
+        self.save()
+    }
+}
+
+/// UIDs still to export in `folder`, in ascending order.
+pub fn pending_uids(
+    uids: impl IntoIterator<Item = u32>,
+    checkpoint: Option<&ExportCheckpoint>,
+    folder: &str,
+    uid_validity: Option<u32>,
+) -> Vec<u32> {
+    let resume_after = checkpoint.and_then(|c| c.resume_after(folder, uid_validity));
+    let mut uids: Vec<u32> = uids
+        .into_iter()
+        .filter(|&uid| resume_after.is_none_or(|last| uid > last))
+        .collect();
+    uids.sort_unstable();
+    uids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_checkpoint_save_and_load() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("checkpoints/Gmail.json");
+
+        let mut checkpoint = ExportCheckpoint::load(&path).unwrap();
+        assert_eq!(checkpoint, ExportCheckpoint::load(&path).unwrap());
+        assert!(!path.exists());
+
+        checkpoint.complete_folder("INBOX").unwrap();
+        checkpoint.record("Archive", Some(7), 42);
+        checkpoint.save().unwrap();
+
+        let reloaded = ExportCheckpoint::load(&path).unwrap();
+        assert_eq!(reloaded.completed_folders, vec!["INBOX"]);
+        assert_eq!(reloaded.folder.as_deref(), Some("Archive"));
+        assert_eq!(reloaded.resume_after("Archive", Some(7)), Some(42));
+
+        reloaded.clear().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_simulated_resume() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("Gmail.json");
+        let folders = [("INBOX", vec![3, 1, 2]), ("Archive", vec![10, 11, 12, 13])];
+
+        // First run: INBOX done, crash after UID 11 of Archive
+        let mut checkpoint = ExportCheckpoint::load(&path).unwrap();
+        for uid in pending_uids(folders[0].1.clone(), Some(&checkpoint), "INBOX", Some(1)) {
+            checkpoint.record("INBOX", Some(1), uid);
+        }
+        checkpoint.complete_folder("INBOX").unwrap();
+        for uid in pending_uids(folders[1].1.clone(), Some(&checkpoint), "Archive", Some(5)) {
+            checkpoint.record("Archive", Some(5), uid);
+            checkpoint.save().unwrap();
+            if uid == 11 {
+                break;
+            }
+        }
+
+        // Second run picks up after UID 11
+        let checkpoint = ExportCheckpoint::load(&path).unwrap();
+        let remaining: Vec<(&str, Vec<u32>)> = folders
+            .iter()
+            .filter(|(folder, _)| !checkpoint.is_folder_done(folder))
+            .map(|(folder, uids)| (*folder, pending_uids(uids.clone(), Some(&checkpoint), folder, Some(5))))
+            .collect();
+        assert_eq!(remaining, vec![("Archive", vec![12, 13])]);
+
+        // A new UIDVALIDITY invalidates the saved UIDs
+        assert_eq!(pending_uids(vec![12, 10, 11], Some(&checkpoint), "Archive", Some(6)), vec![10, 11, 12]);
+    }
+}
@@ -1,4 +1,7 @@
-use serde::{Deserialize, Serialize};
+use crate::keychain::CredentialStore;
+use crate::utils::{keyword_regex, matches_wildcard, validate_filename_template};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::ser::SerializeMap;
 use std::collections::HashMap;
 use std::env;
 use std::fs;
@@ -39,8 +42,141 @@ pub fn sort_config_path() -> PathBuf {
     app_config_dir().join("sort_config.json")
 }
 
+/// Path to `last_export.json` (statistics of the most recent export).
+pub fn last_export_path() -> PathBuf {
+    app_config_dir().join("last_export.json")
+}
+
+/// Directory holding the per-account checkpoints of interrupted exports.
+pub fn checkpoints_dir() -> PathBuf {
+    app_config_dir().join("checkpoints")
+}
+
+/// Directory holding the per-account state kept between exports (last run).
+pub fn state_dir() -> PathBuf {
+    app_config_dir().join("state")
+}
+
+/// Directory holding the per-run export/sort log files.
+pub fn logs_dir() -> PathBuf {
+    app_config_dir().join("logs")
+}
+
 // ── Settings (settings.yaml) ─────────────────────────────────────────────────
 
+/// How many levels of quoted replies (`>`) to keep in exported bodies.
+///
+/// Written as a number in settings.yaml, or `all`/`unlimited` to keep every level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteDepth {
+    Limit(usize),
+    Unlimited,
+}
+
+impl Default for QuoteDepth {
+    fn default() -> Self {
+        QuoteDepth::Limit(1)
+    }
+}
+
+impl Serialize for QuoteDepth {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            QuoteDepth::Limit(depth) => serializer.serialize_u64(*depth as u64),
+            QuoteDepth::Unlimited => serializer.serialize_str("unlimited"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for QuoteDepth {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawQuoteDepth {
+            Number(usize),
+            Text(String),
+        }
+
+        match RawQuoteDepth::deserialize(deserializer)? {
+            RawQuoteDepth::Number(depth) => Ok(QuoteDepth::Limit(depth)),
+            RawQuoteDepth::Text(text) if ["all", "unlimited"].contains(&text.to_lowercase().as_str()) => {
+                Ok(QuoteDepth::Unlimited)
+            }
+            RawQuoteDepth::Text(text) => Err(serde::de::Error::custom(format!(
+                "invalid quote_depth '{}': expected a number, 'all' or 'unlimited'",
+                text
+            ))),
+        }
+    }
+}
+
+/// Date used for emails without a (parseable) Date header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingDatePolicy {
+    /// Keep the `unknown-date` placeholder in filenames.
+    Unknown,
+    /// The date of the export.
+    #[default]
+    UseToday,
+    /// The timestamp of the last `Received:` header (the first relay), if any.
+    UseReceivedHeader,
+    /// 1970-01-01, so undated emails sort first.
+    UseEpoch,
+}
+
+/// Recipient whose short name fills the `to_` part of export filenames.
+///
+/// Written as `first_to`, `first_other` or `label: <text>` in settings.yaml.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum FilenameRecipient {
+    /// The first `To` address.
+    #[default]
+    FirstTo,
+    /// The first `To`/`Cc` address that is not the account's own (`username`,
+    /// `aliases`), so Sent emails show who they went to; else the first `To`.
+    FirstOther,
+    /// The same text for every email.
+    Label(String),
+}
+
+impl Serialize for FilenameRecipient {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            FilenameRecipient::FirstTo => serializer.serialize_str("first_to"),
+            FilenameRecipient::FirstOther => serializer.serialize_str("first_other"),
+            FilenameRecipient::Label(label) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("label", label)?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FilenameRecipient {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawFilenameRecipient {
+            Text(String),
+            Label { label: String },
+        }
+
+        match RawFilenameRecipient::deserialize(deserializer)? {
+            RawFilenameRecipient::Text(text) => match text.as_str() {
+                "first_to" => Ok(FilenameRecipient::FirstTo),
+                "first_other" => Ok(FilenameRecipient::FirstOther),
+                _ => Err(serde::de::Error::custom(format!(
+                    "invalid filename_recipient '{}': expected 'first_to', 'first_other' or 'label: <text>'",
+                    text
+                ))),
+            },
+            RawFilenameRecipient::Label { label } => Ok(FilenameRecipient::Label(label)),
+        }
+    }
+}
+
 /// Per-account behaviour overrides stored in settings.yaml.
 /// All fields are optional so unset values fall back to `Settings::defaults`.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -50,7 +186,7 @@ pub struct AccountBehavior {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub folder_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub quote_depth: Option<usize>,
+    pub quote_depth: Option<QuoteDepth>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub skip_existing: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -59,6 +195,49 @@ pub struct AccountBehavior {
     pub skip_signature_images: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub delete_after_export: Option<bool>,
+    /// Write calendar invites (`text/calendar` parts) as `.ics` sidecars.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_ics: Option<bool>,
+    /// Write the original message as `.eml` next to the markdown.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keep_raw_eml: Option<bool>,
+    /// Mirror the IMAP folder hierarchy as sub-directories of the export directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preserve_folder_structure: Option<bool>,
+    /// Append a slug of the subject to export filenames.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject_in_filename: Option<bool>,
+    /// Export filename layout, e.g. `{date}_{sender}_{subject}` (`.md` is appended).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_template: Option<String>,
+    /// Date to use when an email has no Date header.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub missing_date_policy: Option<MissingDatePolicy>,
+    /// Recipient named in export filenames (`first_to`, `first_other`, `label: ME`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename_recipient: Option<FilenameRecipient>,
+    /// Mask email addresses in exported frontmatter and bodies (`j***@e***.com`);
+    /// unsubscribe links are kept as they are.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redact: Option<bool>,
+    /// Store each distinct attachment once, in `attachments/_shared/`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dedup_attachments: Option<bool>,
+    /// Minimum delay between two message fetches, in milliseconds (strict servers).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetch_delay_ms: Option<u64>,
+    /// Stop after exporting this many messages (trial runs).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_messages: Option<usize>,
+    /// Prefix of the generated tags, e.g. `email/` for nested Obsidian tags.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_prefix: Option<String>,
+    /// Truncate exported bodies longer than this many characters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_body_chars: Option<usize>,
+    /// Other addresses of the account owner, left out of the contacts CSV.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -74,6 +253,17 @@ pub struct Settings {
     /// Per-account overrides keyed by account name.
     #[serde(default)]
     pub accounts: HashMap<String, AccountBehavior>,
+
+    /// Extra password variable tried first, e.g. `EMAIL_PASSWORD_{name}`.
+    /// `{name}` is the account name as in [`env_prefix`]: uppercased, every
+    /// non-alphanumeric character replaced by `_`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_env_template: Option<String>,
+
+    /// IMAP fetches in flight at once across the accounts of one `export`
+    /// (default 1); above 1 the accounts are exported in parallel.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<usize>,
 }
 
 impl Settings {
@@ -98,7 +288,7 @@ impl Settings {
 
 /// A single account entry as stored in accounts.yaml.
 /// Contains only connection details; behaviour comes from settings.yaml.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RawAccount {
     pub name: String,
     pub server: String,
@@ -134,16 +324,66 @@ fn merge_account(raw: &RawAccount, settings: &Settings) -> Account {
         port: raw.port,
         username: raw.username.clone(),
         password: None,
+        auth: AccountAuth::default(),
         ignored_folders: raw.ignored_folders.clone(),
         export_directory,
-        quote_depth: per.and_then(|a| a.quote_depth).or(def.quote_depth).unwrap_or(1),
+        quote_depth: per.and_then(|a| a.quote_depth).or(def.quote_depth).unwrap_or_default(),
         skip_existing: per.and_then(|a| a.skip_existing).or(def.skip_existing).unwrap_or(true),
         collect_contacts: per.and_then(|a| a.collect_contacts).or(def.collect_contacts).unwrap_or(false),
         skip_signature_images: per.and_then(|a| a.skip_signature_images).or(def.skip_signature_images).unwrap_or(false),
         delete_after_export: per.and_then(|a| a.delete_after_export).or(def.delete_after_export).unwrap_or(false),
+        keep_ics: per.and_then(|a| a.keep_ics).or(def.keep_ics).unwrap_or(false),
+        keep_raw_eml: per.and_then(|a| a.keep_raw_eml).or(def.keep_raw_eml).unwrap_or(false),
+        preserve_folder_structure: per
+            .and_then(|a| a.preserve_folder_structure)
+            .or(def.preserve_folder_structure)
+            .unwrap_or(true),
+        subject_in_filename: per
+            .and_then(|a| a.subject_in_filename)
+            .or(def.subject_in_filename)
+            .unwrap_or(false),
+        filename_template: per
+            .and_then(|a| a.filename_template.clone())
+            .or_else(|| def.filename_template.clone()),
+        missing_date_policy: per
+            .and_then(|a| a.missing_date_policy)
+            .or(def.missing_date_policy)
+            .unwrap_or_default(),
+        filename_recipient: per
+            .and_then(|a| a.filename_recipient.clone())
+            .or_else(|| def.filename_recipient.clone())
+            .unwrap_or_default(),
+        redact: per.and_then(|a| a.redact).or(def.redact).unwrap_or(false),
+        dedup_attachments: per
+            .and_then(|a| a.dedup_attachments)
+            .or(def.dedup_attachments)
+            .unwrap_or(false),
+        fetch_delay_ms: per.and_then(|a| a.fetch_delay_ms).or(def.fetch_delay_ms),
+        max_messages: per.and_then(|a| a.max_messages).or(def.max_messages),
+        tag_prefix: per
+            .and_then(|a| a.tag_prefix.clone())
+            .or_else(|| def.tag_prefix.clone()),
+        max_body_chars: per.and_then(|a| a.max_body_chars).or(def.max_body_chars),
+        aliases: per
+            .and_then(|a| a.aliases.clone())
+            .or_else(|| def.aliases.clone())
+            .unwrap_or_default(),
     }
 }
 
+/// How the IMAP client logs in to an account.
+///
+/// Not stored in YAML: an `<ACCOUNT>_OAUTH2_TOKEN` environment variable
+/// selects OAuth2, otherwise the password is used.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AccountAuth {
+    /// `LOGIN` with [`Account::password`].
+    #[default]
+    Password,
+    /// `AUTHENTICATE XOAUTH2` with a bearer token (Gmail, Outlook).
+    OAuth2 { access_token: String },
+}
+
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("Failed to read config file: {0}")]
@@ -168,15 +408,94 @@ pub struct Account {
     pub username: String,
     #[serde(skip)]
     pub password: Option<String>,
+    #[serde(skip)]
+    pub auth: AccountAuth,
     /// Computed: `export_base_dir / folder_name`
     pub export_directory: String,
     #[serde(default)]
     pub ignored_folders: Vec<String>,
-    pub quote_depth: usize,
+    pub quote_depth: QuoteDepth,
     pub skip_existing: bool,
     pub collect_contacts: bool,
     pub skip_signature_images: bool,
     pub delete_after_export: bool,
+    #[serde(default)]
+    pub keep_ics: bool,
+    #[serde(default)]
+    pub keep_raw_eml: bool,
+    #[serde(default = "default_true")]
+    pub preserve_folder_structure: bool,
+    #[serde(default)]
+    pub subject_in_filename: bool,
+    /// `None` keeps the built-in `email_{date}_{sender}_to_{recipient}` layout.
+    #[serde(default)]
+    pub filename_template: Option<String>,
+    #[serde(default)]
+    pub missing_date_policy: MissingDatePolicy,
+    #[serde(default)]
+    pub filename_recipient: FilenameRecipient,
+    #[serde(default)]
+    pub redact: bool,
+    #[serde(default)]
+    pub dedup_attachments: bool,
+    /// `None`: fetch messages back to back.
+    #[serde(default)]
+    pub fetch_delay_ms: Option<u64>,
+    /// Number of messages exported before stopping; skipped ones don't count.
+    #[serde(default)]
+    pub max_messages: Option<usize>,
+    /// Prepended to the account, folder and type tags of exported emails.
+    #[serde(default)]
+    pub tag_prefix: Option<String>,
+    /// `None`: bodies are written in full.
+    #[serde(default)]
+    pub max_body_chars: Option<usize>,
+    /// Addresses of the owner besides `username`; never collected as contacts.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+impl Account {
+    /// Whether a password or an OAuth2 token is available to log in.
+    pub fn has_credentials(&self) -> bool {
+        match self.auth {
+            AccountAuth::Password => self.password.is_some(),
+            AccountAuth::OAuth2 { .. } => true,
+        }
+    }
+
+    /// Check whether a decoded folder name matches one of `ignored_folders`.
+    /// Entries may use `*` and `?` wildcards (e.g. `[Gmail]/*`).
+    pub fn is_folder_ignored(&self, folder: &str) -> bool {
+        self.ignored_folders
+            .iter()
+            .any(|pattern| matches_wildcard(pattern, folder))
+    }
+}
+
+/// Prefix of an account's environment variables (`john.doe@my-mail.com` ->
+/// `JOHN_DOE_MY_MAIL_COM`): every character that is not a letter or digit
+/// becomes `_`, so the result is a valid shell variable name.
+pub fn env_prefix(account_name: &str) -> String {
+    account_name
+        .to_uppercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Environment variables holding an account's password, in lookup order:
+/// `template` (if any), then `<NAME>_APPLICATION_PASSWORD` and `<NAME>_PASSWORD`.
+pub fn password_env_vars(account_name: &str, template: Option<&str>) -> Vec<String> {
+    let sanitized = env_prefix(account_name);
+    template
+        .map(|t| t.replace("{name}", &sanitized))
+        .into_iter()
+        .chain([
+            format!("{}_APPLICATION_PASSWORD", sanitized),
+            format!("{}_PASSWORD", sanitized),
+        ])
+        .collect()
 }
 
 fn default_true() -> bool {
@@ -198,6 +517,22 @@ impl Config {
     pub fn load_with_settings(
         accounts_path: &Path,
         settings_file: &Path,
+    ) -> Result<Self, ConfigError> {
+        #[cfg(feature = "keyring")]
+        let store: Option<&dyn CredentialStore> = Some(&crate::keychain::KeyringStore);
+        #[cfg(not(feature = "keyring"))]
+        let store = None;
+        Self::load_with_credentials(accounts_path, settings_file, &|var| env::var(var).ok(), store)
+    }
+
+    /// Like [`Config::load_with_settings`], with passwords and tokens read
+    /// through `env_var` (a variable lookup) and then `store`, instead of the
+    /// process environment and the OS keychain.
+    pub fn load_with_credentials(
+        accounts_path: &Path,
+        settings_file: &Path,
+        env_var: &dyn Fn(&str) -> Option<String>,
+        store: Option<&dyn CredentialStore>,
     ) -> Result<Self, ConfigError> {
         if !accounts_path.exists() {
             return Ok(Config { accounts: vec![] });
@@ -214,12 +549,20 @@ impl Config {
             .map(|raw| merge_account(raw, &settings))
             .collect();
 
-        // Inject passwords from environment
+        // Inject passwords and OAuth2 tokens from environment
         for account in &mut accounts {
-            let sanitized = account.name.to_uppercase().replace(['@', '.', '-'], "_");
-            account.password = env::var(format!("{}_APPLICATION_PASSWORD", sanitized))
-                .ok()
-                .or_else(|| env::var(format!("{}_PASSWORD", sanitized)).ok());
+            let sanitized = env_prefix(&account.name);
+            account.password = password_env_vars(&account.name, settings.password_env_template.as_deref())
+                .iter()
+                .find_map(|var| env_var(var));
+            if let Some(access_token) = env_var(&format!("{}_OAUTH2_TOKEN", sanitized)) {
+                account.auth = AccountAuth::OAuth2 { access_token };
+            }
+        }
+
+        // Then the keychain for accounts still without password
+        if let Some(store) = store {
+            crate::keychain::fill_missing_passwords(&mut accounts, store);
         }
 
         let config = Config { accounts };
@@ -263,6 +606,15 @@ impl Config {
                     account.name
                 )));
             }
+
+            if let Some(template) = &account.filename_template {
+                validate_filename_template(template).map_err(|e| {
+                    ConfigError::ValidationError(format!(
+                        "Invalid filename_template for account '{}': {}",
+                        account.name, e
+                    ))
+                })?;
+            }
         }
 
         Ok(())
@@ -279,6 +631,89 @@ impl Config {
     pub fn list_accounts(&self) -> Vec<&str> {
         self.accounts.iter().map(|a| a.name.as_str()).collect()
     }
+
+    /// Describe the resolved account `name` (case-insensitive) for diagnostics.
+    ///
+    /// Lists every setting with the layer that supplied it: accounts.yaml,
+    /// the per-account section of settings.yaml, its `defaults`, or the
+    /// built-in default. Validation problems are reported instead of failing,
+    /// and secrets are never shown.
+    pub fn describe(accounts_path: &Path, settings_file: &Path, name: &str) -> Result<String, ConfigError> {
+        let content = fs::read_to_string(accounts_path)?;
+        let raw_file: RawAccountsFile = serde_yaml::from_str(&content)?;
+        let raw = raw_file
+            .accounts
+            .iter()
+            .find(|a| a.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| ConfigError::AccountNotFound(name.to_string()))?;
+        let settings = Settings::load(settings_file)?;
+        let account = merge_account(raw, &settings);
+
+        let keys = |behavior: Option<&AccountBehavior>| -> Vec<String> {
+            match behavior.map(serde_yaml::to_value) {
+                Some(Ok(serde_yaml::Value::Mapping(map))) => {
+                    map.keys().filter_map(|k| k.as_str().map(String::from)).collect()
+                }
+                _ => Vec::new(),
+            }
+        };
+        let per_keys = keys(settings.accounts.get(&raw.name));
+        let default_keys = keys(Some(&settings.defaults));
+        let source = |key: &str| {
+            if per_keys.iter().any(|k| k == key) {
+                format!("settings.yaml accounts.{}", raw.name)
+            } else if default_keys.iter().any(|k| k == key) {
+                "settings.yaml defaults".to_string()
+            } else {
+                "built-in default".to_string()
+            }
+        };
+
+        let mut lines = vec![format!("Account: {}", account.name)];
+        let mut push = |key: &str, value: String, origin: String| {
+            lines.push(format!("  {}: {}  ({})", key, value, origin));
+        };
+
+        let export_origin = match &settings.export_base_dir {
+            Some(base) => format!("export_base_dir '{}' + folder_name from {}", base, source("folder_name")),
+            None => "not configured: set export_base_dir in settings.yaml".to_string(),
+        };
+
+        if let Ok(serde_yaml::Value::Mapping(resolved)) = serde_yaml::to_value(&account) {
+            for (key, value) in &resolved {
+                let Some(key) = key.as_str() else { continue };
+                let value = match value {
+                    serde_yaml::Value::String(s) => s.clone(),
+                    other => serde_json::to_string(other).unwrap_or_default(),
+                };
+                let origin = match key {
+                    "name" | "server" | "port" | "username" | "ignored_folders" => "accounts.yaml".to_string(),
+                    "export_directory" => export_origin.clone(),
+                    _ => source(key),
+                };
+                push(key, value, origin);
+            }
+        }
+
+        let sanitized = env_prefix(&account.name);
+        let credentials = if env::var(format!("{}_OAUTH2_TOKEN", sanitized)).is_ok() {
+            format!("OAuth2 token ({}_OAUTH2_TOKEN)", sanitized)
+        } else if let Some(var) = password_env_vars(&account.name, settings.password_env_template.as_deref())
+            .into_iter()
+            .find(|var| env::var(var).is_ok())
+        {
+            format!("password ({})", var)
+        } else {
+            format!("none (set {}_PASSWORD or {}_OAUTH2_TOKEN)", sanitized, sanitized)
+        };
+        push("credentials", credentials, "environment".to_string());
+
+        if let Err(e) = (Config { accounts: vec![account] }).validate() {
+            lines.push(format!("Problem: {}", e));
+        }
+
+        Ok(lines.join("\n"))
+    }
 }
 
 /// Configuration for the email sorting tool.
@@ -311,6 +746,12 @@ pub struct SortConfig {
     #[serde(default = "default_old_threshold")]
     pub old_threshold_days: i64,
 
+    /// Files whose body (after the frontmatter, trimmed) has fewer characters
+    /// than this are skipped as empty: read receipts, delivery notifications...
+    /// Empty files are always skipped (default: 10).
+    #[serde(default = "default_skip_below_chars")]
+    pub skip_below_chars: usize,
+
     #[serde(default = "default_small_threshold")]
     pub small_email_threshold: usize,
     #[serde(default = "default_large_threshold")]
@@ -321,6 +762,27 @@ pub struct SortConfig {
 
     #[serde(default = "default_type_weights")]
     pub type_weights: HashMap<String, i32>,
+    /// Added to the score of auto-replies (out-of-office, vacation messages).
+    #[serde(default = "default_auto_reply_weight")]
+    pub auto_reply_weight: i32,
+
+    /// Emails scoring at or below this are deleted.
+    #[serde(default = "default_delete_score_threshold")]
+    pub delete_score_threshold: i32,
+    /// Emails scoring at or above this are kept in full.
+    #[serde(default = "default_keep_score_threshold")]
+    pub keep_score_threshold: i32,
+    /// Emails scoring within this distance of either threshold go to review (0 disables).
+    #[serde(default)]
+    pub review_margin: i32,
+
+    /// Ignore accents when matching keywords and senders (`resume` matches `Résumé`).
+    #[serde(default)]
+    pub accent_insensitive: bool,
+
+    /// UTC offset (minutes) for frontmatter dates without a timezone; local time if unset.
+    #[serde(default)]
+    pub naive_date_utc_offset_minutes: Option<i32>,
 }
 
 fn default_delete_keywords() -> Vec<String> {
@@ -369,6 +831,22 @@ fn default_large_threshold() -> usize {
     10000
 }
 
+fn default_auto_reply_weight() -> i32 {
+    -3
+}
+
+fn default_skip_below_chars() -> usize {
+    10
+}
+
+fn default_delete_score_threshold() -> i32 {
+    -2
+}
+
+fn default_keep_score_threshold() -> i32 {
+    2
+}
+
 fn default_type_weights() -> HashMap<String, i32> {
     let mut weights = HashMap::new();
     weights.insert("newsletter".into(), -2);
@@ -393,10 +871,17 @@ impl Default for SortConfig {
             whitelist: Vec::new(),
             recent_threshold_days: default_recent_threshold(),
             old_threshold_days: default_old_threshold(),
+            skip_below_chars: default_skip_below_chars(),
             small_email_threshold: default_small_threshold(),
             large_email_threshold: default_large_threshold(),
             keep_with_attachments: true,
             type_weights: default_type_weights(),
+            auto_reply_weight: default_auto_reply_weight(),
+            delete_score_threshold: default_delete_score_threshold(),
+            keep_score_threshold: default_keep_score_threshold(),
+            review_margin: 0,
+            accent_insensitive: false,
+            naive_date_utc_offset_minutes: None,
         }
     }
 }
@@ -408,12 +893,47 @@ impl SortConfig {
             let content = fs::read_to_string(config_path)?;
             let config: SortConfig = serde_json::from_str(&content)
                 .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            config.validate()?;
             Ok(config)
         } else {
             Ok(Self::default())
         }
     }
 
+    /// Check that paired thresholds are consistent.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.recent_threshold_days >= self.old_threshold_days {
+            return Err(ConfigError::ValidationError(format!(
+                "recent_threshold_days ({}) must be lower than old_threshold_days ({})",
+                self.recent_threshold_days, self.old_threshold_days
+            )));
+        }
+        if self.small_email_threshold >= self.large_email_threshold {
+            return Err(ConfigError::ValidationError(format!(
+                "small_email_threshold ({}) must be lower than large_email_threshold ({})",
+                self.small_email_threshold, self.large_email_threshold
+            )));
+        }
+        if let Some(minutes) = self.naive_date_utc_offset_minutes {
+            if minutes.abs() >= 24 * 60 {
+                return Err(ConfigError::ValidationError(format!(
+                    "naive_date_utc_offset_minutes ({}) must be within ±1439",
+                    minutes
+                )));
+            }
+        }
+        for entry in self.delete_keywords.iter().chain(&self.keep_keywords) {
+            if let Some(Err(e)) = keyword_regex(entry) {
+                return Err(ConfigError::ValidationError(format!(
+                    "Invalid keyword regex '{}': {}",
+                    entry, e
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Save configuration to JSON file.
     pub fn save(&self, config_path: &Path) -> Result<(), std::io::Error> {
         let content = serde_json::to_string_pretty(self)
@@ -462,6 +982,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_is_whitelisted() {
         let mut config = SortConfig::default();
         config.whitelist = vec![
@@ -475,4 +996,44 @@ mod tests {
         assert!(config.is_whitelisted("boss@anywhere.com"));
         assert!(!config.is_whitelisted("random@other.com"));
     }
+
+    #[test]
+    fn test_is_folder_ignored_wildcards() {
+        let raw = RawAccount {
+            name: "Gmail".into(),
+            server: "imap.gmail.com".into(),
+            port: 993,
+            username: "me@gmail.com".into(),
+            ignored_folders: vec!["[Gmail]/*".into(), "Archive/*".into(), "Junk".into()],
+        };
+        let account = merge_account(&raw, &Settings::default());
+
+        assert!(account.is_folder_ignored("[Gmail]/Spam"));
+        assert!(account.is_folder_ignored("Archive/2023"));
+        assert!(account.is_folder_ignored("Junk"));
+        assert!(!account.is_folder_ignored("Archived"));
+        assert!(!account.is_folder_ignored("INBOX"));
+    }
+
+    #[test]
+    fn test_validate_rejects_filename_template_without_unique_part() {
+        let raw = RawAccount {
+            name: "Work".into(),
+            server: "imap.example.com".into(),
+            port: 993,
+            username: "me@example.com".into(),
+            ignored_folders: Vec::new(),
+        };
+        let settings = Settings {
+            export_base_dir: Some("/tmp/mail".into()),
+            ..Default::default()
+        };
+        let mut account = merge_account(&raw, &settings);
+        account.filename_template = Some("{date}_{sender}".into());
+
+        let config = Config { accounts: vec![account] };
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("filename_template"));
+        assert!(err.contains("'Work'"));
+    }
 }
@@ -1,28 +1,101 @@
-use crate::config::Account;
-use crate::network::{NetworkConfig, ProgressIndicator, with_retry};  // [3][4]
+use crate::checkpoint::{pending_uids, ExportCheckpoint};
+use crate::config::{Account, AccountAuth, FilenameRecipient, MissingDatePolicy, QuoteDepth};
+use crate::export_index::ExportIndex;
+use crate::fix_yaml::extract_frontmatter;
+use crate::ledger::MessageIdLedger;
+use crate::network::{ConcurrencyLimit, NetworkConfig, Pacer, ProgressIndicator, with_retry};  // [3][4]
+use crate::run_log::{LogStatus, RunLog};
+use crate::run_state::AccountState;
+use crate::threads::{group_threads, ThreadLinks};
 use crate::utils::{
-    decode_imap_utf7, decode_mime_filename, extract_emails, get_short_name, hash_md5_prefix,
-    is_signature_image, limit_quote_depth, normalize_line_breaks, sanitize_filename,
+    body_hash, cleanup_empty_dirs, decode_imap_utf7, decode_mime_filename, decode_mime_header, encode_imap_utf7, extract_emails,
+    get_short_name, hash_md5_prefix, is_signature_image, limit_quote_depth, normalize_line_breaks,
+    normalize_trailing_whitespace, redact_emails, render_filename_template, sanitize_filename,
+    markdown_files, slugify, strip_html_tags,
 };
 use anyhow::{Context, Result};
 use chrono::{DateTime, FixedOffset, Utc};
 use imap::{ImapConnection, Session};
-use mailparse::{self, MailHeaderMap, ParsedMail};
+use mailparse::{self, MailAddr, MailHeaderMap, ParsedMail};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
-use std::fs::{self, File};
-use std::io::Write;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Version of the frontmatter layout written by `export_to_markdown`.
+///
+/// Bump it when fields are added, renamed or change meaning. Files written
+/// before versioning was introduced have no `schema_version` (read as 0).
+pub const FRONTMATTER_SCHEMA_VERSION: u32 = 2;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailFrontmatter {
+    #[serde(default)]
+    pub schema_version: u32,
     pub from: String,
-    pub to: String,
+    /// Recipients as `Name <address>` (or the bare address); a single string before version 2.
+    #[serde(deserialize_with = "string_or_list")]
+    pub to: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cc: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bcc: Vec<String>,
     pub date: String,
     pub subject: String,
     pub subject_hash: String,
+    /// Message-Id header without angle brackets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// Source IMAP folder (decoded).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    /// Message-Id of the parent message (In-Reply-To header).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<String>,
+    /// Message-Ids of the thread ancestors (References header), oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+    /// `Auto-Submitted` value (`auto-replied` also stands for `X-Autoreply`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_submitted: Option<String>,
+    /// `mailto:` and `http(s):` links of the `List-Unsubscribe` header, never redacted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unsubscribe: Vec<String>,
+    /// MD5 of the body as written (after `redact`), with whitespace collapsed
+    /// and quoted lines removed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_hash: Option<String>,
     pub tags: Vec<String>,
     pub attachments: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ics: Option<String>,
+    /// The original message, written next to the markdown (`keep_raw_eml`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_source: Option<String>,
+    /// The email carries a calendar invite (`text/calendar` part).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub calendar_event: bool,
+}
+
+/// Accept either a list of strings or a single (possibly empty) string.
+fn string_or_list<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match StringOrList::deserialize(deserializer)? {
+        StringOrList::One(value) if value.is_empty() => Vec::new(),
+        StringOrList::One(value) => vec![value],
+        StringOrList::Many(values) => values,
+    })
 }
 
 #[derive(Debug, Clone)]
@@ -56,42 +129,68 @@ impl std::fmt::Display for EmailType {
     }
 }
 
+/// Contacts met during an export, by email type, with the number of emails
+/// each appeared in.
 pub struct ContactsCollector {
-    pub direct: HashSet<String>,
-    pub group: HashSet<String>,
-    pub newsletter: HashSet<String>,
-    pub mailing_list: HashSet<String>,
-    pub unknown: HashSet<String>,
+    pub direct: HashMap<String, usize>,
+    pub group: HashMap<String, usize>,
+    pub newsletter: HashMap<String, usize>,
+    pub mailing_list: HashMap<String, usize>,
+    pub unknown: HashMap<String, usize>,
+    /// The owner's own addresses (lowercase), never added.
+    self_addresses: HashSet<String>,
 }
 
 impl ContactsCollector {
     pub fn new() -> Self {
         ContactsCollector {
-            direct: HashSet::new(),
-            group: HashSet::new(),
-            newsletter: HashSet::new(),
-            mailing_list: HashSet::new(),
-            unknown: HashSet::new(),
+            direct: HashMap::new(),
+            group: HashMap::new(),
+            newsletter: HashMap::new(),
+            mailing_list: HashMap::new(),
+            unknown: HashMap::new(),
+            self_addresses: HashSet::new(),
         }
     }
 
+    /// Collector for `account`, skipping its username and `aliases`.
+    pub fn for_account(account: &Account) -> Self {
+        Self::new().with_self_addresses(std::iter::once(&account.username).chain(&account.aliases))
+    }
+
+    /// Addresses of the account owner, which are not contacts.
+    pub fn with_self_addresses<I, S>(mut self, addresses: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.self_addresses
+            .extend(addresses.into_iter().map(|a| a.as_ref().trim().to_lowercase()));
+        self
+    }
+
     pub fn add(&mut self, email_type: &EmailType, contact: String) {
-        match email_type {
-            EmailType::Direct => self.direct.insert(contact),
-            EmailType::Group => self.group.insert(contact),
-            EmailType::Newsletter => self.newsletter.insert(contact),
-            EmailType::MailingList => self.mailing_list.insert(contact),
-            EmailType::Unknown => self.unknown.insert(contact),
+        if self.self_addresses.contains(&contact.to_lowercase()) {
+            return;
+        }
+        let bucket = match email_type {
+            EmailType::Direct => &mut self.direct,
+            EmailType::Group => &mut self.group,
+            EmailType::Newsletter => &mut self.newsletter,
+            EmailType::MailingList => &mut self.mailing_list,
+            EmailType::Unknown => &mut self.unknown,
         };
+        *bucket.entry(contact).or_insert(0) += 1;
     }
 
+    /// Write the contacts CSV, most frequent contacts first.
     pub fn generate_csv(&self, base_dir: &Path, account_name: &str) -> Result<PathBuf> {
         let date_str = Utc::now().format("%Y-%m-%d").to_string();
         let filename = format!("contacts_{}_{}.csv", account_name, date_str);
         let filepath = base_dir.join(&filename);
 
         let mut writer = csv::Writer::from_path(&filepath)?;
-        writer.write_record(["Name", "Email", "Type", "Source", "Notes"])?;
+        writer.write_record(["Name", "Email", "Type", "Count", "Source", "Notes"])?;
 
         let categories = [
             (&self.direct, "Direct"),
@@ -101,32 +200,40 @@ impl ContactsCollector {
             (&self.unknown, "Unknown"),
         ];
 
-        for (contacts, contact_type) in categories {
-            for contact in contacts {
-                let name = contact
-                    .split('@')
-                    .next()
-                    .unwrap_or("")
-                    .replace('.', " ")
-                    .split_whitespace()
-                    .map(|w| {
-                        let mut c = w.chars();
-                        match c.next() {
-                            None => String::new(),
-                            Some(f) => f.to_uppercase().chain(c).collect(),
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .join(" ");
-
-                writer.write_record([
-                    &name,
-                    contact,
-                    contact_type,
-                    account_name,
-                    &format!("Collected from {} emails", account_name),
-                ])?;
-            }
+        let mut rows: Vec<(&String, usize, &str)> = categories
+            .into_iter()
+            .flat_map(|(contacts, contact_type)| {
+                contacts.iter().map(move |(contact, &count)| (contact, count, contact_type))
+            })
+            .collect();
+        // Most frequent first; ties by address, then in type order (stable sort)
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        for (contact, count, contact_type) in rows {
+            let name = contact
+                .split('@')
+                .next()
+                .unwrap_or("")
+                .replace('.', " ")
+                .split_whitespace()
+                .map(|w| {
+                    let mut c = w.chars();
+                    match c.next() {
+                        None => String::new(),
+                        Some(f) => f.to_uppercase().chain(c).collect(),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            writer.write_record([
+                &name,
+                contact,
+                contact_type,
+                &count.to_string(),
+                account_name,
+                &format!("Collected from {} emails", account_name),
+            ])?;
         }
 
         writer.flush()?;
@@ -145,7 +252,7 @@ pub fn analyze_email_type(mail: &ParsedMail) -> EmailAnalysis {
     let from_field = mail.headers.get_first_value("From").unwrap_or_default();
     let to_field = mail.headers.get_first_value("To").unwrap_or_default();
     let cc_field = mail.headers.get_first_value("Cc").unwrap_or_default();
-    let subject = mail.headers.get_first_value("Subject").unwrap_or_default();
+    let subject = subject_of(mail);
 
     let from_emails = extract_emails(Some(&from_field));
     let to_emails = extract_emails(Some(&to_field));
@@ -187,6 +294,58 @@ pub fn analyze_email_type(mail: &ParsedMail) -> EmailAnalysis {
     }
 }
 
+/// Addresses of every `header` field (To, Cc, Bcc), keeping display names as `Name <address>`.
+///
+/// Falls back to the bare addresses found by `extract_emails` when the
+/// header is not a valid address list.
+fn address_list(mail: &ParsedMail, header: &str) -> Vec<String> {
+    mail.headers
+        .get_all_headers(header)
+        .into_iter()
+        .flat_map(|field| match mailparse::addrparse_header(field) {
+            Ok(addresses) => addresses
+                .iter()
+                .flat_map(|address| match address {
+                    MailAddr::Single(info) => vec![info.clone()],
+                    MailAddr::Group(group) => group.addrs.clone(),
+                })
+                .map(|info| match info.display_name {
+                    Some(name) if !name.trim().is_empty() => format!("{} <{}>", name.trim(), info.addr),
+                    _ => info.addr,
+                })
+                .collect(),
+            Err(_) => extract_emails(Some(&field.get_value())),
+        })
+        .collect()
+}
+
+/// Short name of the recipient in export filenames, per the account's `filename_recipient`.
+fn filename_recipient(account: &Account, mail: &ParsedMail, to_field: &str) -> String {
+    match &account.filename_recipient {
+        FilenameRecipient::FirstTo => get_short_name(Some(to_field)),
+        FilenameRecipient::FirstOther => {
+            let own: HashSet<String> = std::iter::once(&account.username)
+                .chain(&account.aliases)
+                .map(|a| a.trim().to_lowercase())
+                .collect();
+            let mut recipients = address_list(mail, "To").into_iter().chain(address_list(mail, "Cc"));
+            match recipients.find(|r| {
+                extract_emails(Some(r)).first().is_some_and(|addr| !own.contains(&addr.to_lowercase()))
+            }) {
+                Some(other) => get_short_name(Some(&other)),
+                None => get_short_name(Some(to_field)),
+            }
+        }
+        FilenameRecipient::Label(label) => sanitize_filename(label),
+    }
+}
+
+/// Maximum length of the subject slug appended to filenames.
+const SUBJECT_SLUG_MAX_BYTES: usize = 40;
+
+/// Maximum length of a generated tag, before the prefix.
+const TAG_MAX_BYTES: usize = 64;
+
 /// Check if an email has already been exported.
 pub fn email_already_exported(
     date_str: &str,
@@ -195,16 +354,53 @@ pub fn email_already_exported(
     subject_hash: &str,
     export_directory: &Path,
 ) -> bool {
+    let search_pattern = format!("email_{}_{}*to_{}*.md", date_str, sender_short, recipient_short);
+    file_with_hash_exists(&search_pattern, subject_hash, export_directory)
+}
+
+/// Check if an email has already been exported, preferring the Message-Id ledger.
+///
+/// A Message-Id recorded in the ledger answers without touching the filesystem
+/// and survives renamed files or a changed filename template. Otherwise this
+/// falls back to a filename scan, which also finds files exported before the
+/// ledger existed: files starting with `templated_filename` when the account
+/// has a filename template, else the default layout of [`email_already_exported`].
+#[allow(clippy::too_many_arguments)]
+pub fn email_already_exported_with_ledger(
+    ledger: Option<&MessageIdLedger>,
+    message_id: Option<&str>,
+    templated_filename: Option<&str>,
+    date_str: &str,
+    sender_short: &str,
+    recipient_short: &str,
+    subject_hash: &str,
+    export_directory: &Path,
+) -> bool {
+    if let (Some(ledger), Some(id)) = (ledger, message_id) {
+        if ledger.contains(id) {
+            return true;
+        }
+    }
+
+    match templated_filename {
+        Some(name) => {
+            let search_pattern = format!("{}*.md", glob::Pattern::escape(name));
+            file_with_hash_exists(&search_pattern, subject_hash, export_directory)
+        }
+        None => email_already_exported(date_str, sender_short, recipient_short, subject_hash, export_directory),
+    }
+}
+
+/// Check whether a file matching `search_pattern` in `export_directory` contains `subject_hash`.
+fn file_with_hash_exists(search_pattern: &str, subject_hash: &str, export_directory: &Path) -> bool {
     if !export_directory.exists() {
         return false;
     }
 
-    let search_pattern = format!("email_{}_{}*to_{}*.md", date_str, sender_short, recipient_short);
-
     if let Ok(entries) = fs::read_dir(export_directory) {
         for entry in entries.flatten() {
             let filename = entry.file_name().to_string_lossy().to_string();
-            if glob::Pattern::new(&search_pattern)
+            if glob::Pattern::new(search_pattern)
                 .map(|p| p.matches(&filename))
                 .unwrap_or(false)
             {
@@ -221,41 +417,195 @@ pub fn email_already_exported(
     false
 }
 
+/// Split a header holding one or more `<id>` values (References, In-Reply-To).
+pub fn parse_message_id_list(header: &str) -> Vec<String> {
+    header
+        .split(|c: char| c.is_whitespace() || c == '>' || c == ',')
+        .filter_map(MessageIdLedger::normalize)
+        .collect()
+}
+
+/// Whether `raw` starts with a header field (`Name: value`) or an empty line, not body text.
+fn starts_with_header(raw: &[u8]) -> bool {
+    let first_line = raw.split(|&b| b == b'\n').next().unwrap_or_default();
+    let first_line = first_line.strip_suffix(b"\r").unwrap_or(first_line);
+    if first_line.is_empty() {
+        return true;
+    }
+    match first_line.iter().position(|&b| b == b':') {
+        Some(colon) => colon > 0 && first_line[..colon].iter().all(|b| b.is_ascii_graphic()),
+        None => false,
+    }
+}
+
+/// Date for an email without a usable Date header, according to `policy`.
+fn fallback_date(mail: &ParsedMail, policy: MissingDatePolicy) -> Option<DateTime<FixedOffset>> {
+    match policy {
+        MissingDatePolicy::Unknown => None,
+        MissingDatePolicy::UseToday => Some(Utc::now().fixed_offset()),
+        MissingDatePolicy::UseEpoch => DateTime::from_timestamp(0, 0).map(|dt| dt.fixed_offset()),
+        // `Received: from ... by ...; <date>`; the last header is the earliest hop
+        MissingDatePolicy::UseReceivedHeader => mail
+            .headers
+            .get_all_values("Received")
+            .last()
+            .and_then(|received| received.rsplit_once(';'))
+            .and_then(|(_, date)| parse_email_date(date.trim())),
+    }
+}
+
 /// Parse email date string to DateTime.
+///
+/// A missing (empty) Date header gives `None`; mailparse would read it as the epoch.
 fn parse_email_date(date_str: &str) -> Option<DateTime<FixedOffset>> {
+    if date_str.trim().is_empty() {
+        return None;
+    }
     mailparse::dateparse(date_str)
         .ok()
-        .map(|ts| DateTime::from_timestamp(ts, 0))
-        .flatten()
+        .and_then(|ts| DateTime::from_timestamp(ts, 0))
         .map(|dt| dt.with_timezone(&FixedOffset::east_opt(0).unwrap()))
 }
 
+/// Decoded subject; encoded words mailparse leaves behind (glued to text,
+/// malformed folding) go through [`decode_mime_header`].
+fn subject_of(mail: &ParsedMail) -> String {
+    decode_mime_header(&mail.headers.get_first_value("Subject").unwrap_or_default())
+}
+
+/// Whether and how the email was sent automatically, from `Auto-Submitted`
+/// (`no` means a human sent it) or the older `X-Autoreply` header.
+fn auto_submitted(mail: &ParsedMail) -> Option<String> {
+    let value = mail
+        .headers
+        .get_first_value("Auto-Submitted")
+        .map(|v| v.split(';').next().unwrap_or_default().trim().to_lowercase())
+        .filter(|v| !v.is_empty() && v != "no");
+    value.or_else(|| {
+        mail.headers
+            .get_first_value("X-Autoreply")
+            .map(|_| "auto-replied".to_string())
+    })
+}
+
+/// Unsubscribe links of the `List-Unsubscribe` header (`<mailto:...>, <https://...>`).
+fn unsubscribe_links(mail: &ParsedMail) -> Vec<String> {
+    let Some(header) = mail.headers.get_first_value("List-Unsubscribe") else {
+        return Vec::new();
+    };
+    header
+        .split(',')
+        .map(|link| link.trim().trim_start_matches('<').trim_end_matches('>').trim())
+        .filter(|link| {
+            let lower = link.to_lowercase();
+            ["mailto:", "https://", "http://"].iter().any(|scheme| lower.starts_with(scheme))
+        })
+        .map(String::from)
+        .collect()
+}
+
+/// Cut `body` after `max_chars` characters, noting how many were left out.
+fn truncate_body(body: &str, max_chars: usize) -> String {
+    match body.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!(
+            "{}\n\n[truncated: {} chars omitted]",
+            &body[..cut],
+            body[cut..].chars().count()
+        ),
+        None => body.to_string(),
+    }
+}
+
+/// Tags of an exported email: account name, folder and email type, slugified
+/// and prefixed with the account's `tag_prefix`.
+pub fn derived_tags(account: &Account, folder: Option<&str>, email_type: &EmailType) -> Vec<String> {
+    let prefix = account.tag_prefix.as_deref().unwrap_or_default();
+    [
+        Some(slugify(&account.name, TAG_MAX_BYTES)),
+        // Keep hierarchy separators apart: `Archive/2023` -> `archive-2023`
+        folder.map(|f| slugify(&f.replace(['/', '.'], " "), TAG_MAX_BYTES)),
+        Some(email_type.to_string()),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|tag| !tag.is_empty())
+    .map(|tag| format!("{}{}", prefix, tag))
+    .collect()
+}
+
 /// Export a single email to Markdown with frontmatter.
+///
+/// `tags` come first in the frontmatter, followed by the tags derived from
+/// the account, the folder and the email type (see [`derived_tags`]).
+///
+/// When `export_index` is given, emails whose source hash is already indexed are
+/// skipped (if `skip_existing`), and newly written emails are recorded in it.
+/// With `skip_existing`, emails whose Message-Id is in `ledger`, or that the
+/// filename scan finds, are skipped too (see [`email_already_exported_with_ledger`]);
+/// newly written emails are recorded in `ledger`.
+#[allow(clippy::too_many_arguments)]
 pub fn export_to_markdown(
     raw_email: &[u8],
     export_directory: &Path,
     base_export_directory: &Path,
     tags: Vec<String>,
+    folder: Option<&str>,
     account: &Account,
     contacts_collector: Option<&mut ContactsCollector>,
+    export_index: Option<&mut ExportIndex>,
+    ledger: Option<&mut MessageIdLedger>,
     debug_mode: bool,
 ) -> Result<Option<PathBuf>> {
+    let source_hash = ExportIndex::source_hash(raw_email);
+
+    // Check the global index first: it still knows about files moved elsewhere
+    if account.skip_existing
+        && export_index
+            .as_ref()
+            .is_some_and(|index| index.contains(&source_hash))
+    {
+        return Ok(None);
+    }
+
+    // A message without any header would otherwise lose its first line as a header name
+    let headerless;
+    let raw_email = if starts_with_header(raw_email) {
+        raw_email
+    } else {
+        headerless = [&b"\r\n"[..], raw_email].concat();
+        &headerless[..]
+    };
+
     let mail = mailparse::parse_mail(raw_email)
         .context("Failed to parse email")?;
 
     let from_field = mail.headers.get_first_value("From").unwrap_or_default();
     let to_field = mail.headers.get_first_value("To").unwrap_or_default();
     let date_field = mail.headers.get_first_value("Date").unwrap_or_default();
-    let subject = mail.headers.get_first_value("Subject").unwrap_or_default();
-
-    // Parse date
-    let date_obj = parse_email_date(&date_field);
+    let subject = subject_of(&mail);
+    let message_id = mail
+        .headers
+        .get_first_value("Message-ID")
+        .and_then(|id| MessageIdLedger::normalize(&id));
+    let in_reply_to = mail
+        .headers
+        .get_first_value("In-Reply-To")
+        .and_then(|v| parse_message_id_list(&v).into_iter().next());
+    let references = mail
+        .headers
+        .get_first_value("References")
+        .map(|v| parse_message_id_list(&v))
+        .unwrap_or_default();
+
+    // Parse date, falling back to the account's policy when there is none
+    let date_obj = parse_email_date(&date_field)
+        .or_else(|| fallback_date(&mail, account.missing_date_policy));
     let date_str = date_obj
         .map(|d| d.format("%Y-%m-%d").to_string())
         .unwrap_or_else(|| "unknown-date".to_string());
 
     let sender_short = get_short_name(Some(&from_field));
-    let recipient_short = get_short_name(Some(&to_field));
+    let recipient_short = filename_recipient(account, &mail, &to_field);
 
     // Generate subject hash for uniqueness
     let subject_hash = if !subject.is_empty() {
@@ -264,16 +614,47 @@ pub fn export_to_markdown(
         "no-subject".to_string()
     };
 
-    // Check if email already exported
-    if account.skip_existing
-        && email_already_exported(&date_str, &sender_short, &recipient_short, &subject_hash, export_directory)
-    {
+    // Render the configured filename layout, if any
+    let templated_filename = account.filename_template.as_ref().map(|template| {
+        let subject_slug = slugify(&subject, SUBJECT_SLUG_MAX_BYTES);
+        sanitize_filename(&render_filename_template(
+            template,
+            &[
+                ("date", &date_str),
+                ("sender", &sender_short),
+                ("recipient", &recipient_short),
+                ("hash", &subject_hash),
+                ("subject", &subject_slug),
+            ],
+        ))
+    });
+
+    let already_exported = |ledger: Option<&MessageIdLedger>| {
+        email_already_exported_with_ledger(
+            ledger,
+            message_id.as_deref(),
+            templated_filename.as_deref(),
+            &date_str,
+            &sender_short,
+            &recipient_short,
+            &subject_hash,
+            export_directory,
+        )
+    };
+
+    if account.skip_existing && already_exported(ledger.as_deref()) {
         return Ok(None);
     }
 
     // Analyze email and collect contacts if enabled
+    let analysis = analyze_email_type(&mail);
+    let mut tags = tags;
+    for tag in derived_tags(account, folder, &analysis.email_type) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
     if let Some(collector) = contacts_collector {
-        let analysis = analyze_email_type(&mail);
         for contact in analysis.contacts {
             collector.add(&analysis.email_type, contact);
         }
@@ -283,24 +664,28 @@ pub fn export_to_markdown(
     fs::create_dir_all(export_directory)?;
 
     // Generate unique filename
-    let base_filename = format!("email_{}_{}*to_{}", date_str, sender_short, recipient_short);
+    let mut base_filename = templated_filename.clone().unwrap_or_else(|| {
+        format!("email_{}_{}*to_{}", date_str, sender_short, recipient_short)
+    });
+    if account.subject_in_filename && account.filename_template.is_none() {
+        let slug = slugify(&subject, SUBJECT_SLUG_MAX_BYTES);
+        if !slug.is_empty() {
+            base_filename = format!("{}_{}", base_filename, slug);
+        }
+    }
     let mut counter = 1;
     let mut filename = format!("{}.md", base_filename.replace('*', "_"));
     while export_directory.join(&filename).exists() {
+        // Possibly this very message, written meanwhile by a concurrent export
+        if account.skip_existing
+            && fs::read_to_string(export_directory.join(&filename)).is_ok_and(|c| c.contains(&subject_hash))
+        {
+            return Ok(None);
+        }
         counter += 1;
         filename = format!("{}_{}.md", base_filename.replace('*', "_"), counter);
     }
 
-    // Extract body
-    let body = extract_body(&mail);
-
-    // Apply quote depth limiting
-    let body = if account.quote_depth > 0 {
-        limit_quote_depth(&body, account.quote_depth)
-    } else {
-        body
-    };
-
     // Handle attachments
     let relative_path = export_directory
         .strip_prefix(base_export_directory)
@@ -308,67 +693,147 @@ pub fn export_to_markdown(
     let attachments_dir = base_export_directory.join("attachments").join(relative_path);
     fs::create_dir_all(&attachments_dir)?;
 
-    let mut attachments = Vec::new();
     let base_filename_for_attachments = base_filename.replace('*', "_");
 
-    extract_attachments(
+    // Raw calendar invite kept next to the markdown file, instead of as an attachment
+    let ics_payload = if account.keep_ics { calendar_payload(&mail) } else { None };
+
+    let (body, attachments) = render_body(
         &mail,
         &attachments_dir,
         &base_filename_for_attachments,
         base_export_directory,
-        account.skip_signature_images,
+        account,
         debug_mode,
-        &mut attachments,
+        ics_payload.is_some(),
     )?;
 
-    // Create frontmatter
-    let frontmatter = EmailFrontmatter {
-        from: from_field,
-        to: to_field,
+    // Mask addresses last: filenames and `subject_hash` above use the real ones,
+    // while `body_hash` covers the body as written (redacted), so `verify` can recompute it
+    let redact = |text: String| if account.redact { redact_emails(&text) } else { text };
+
+    // Meeting details, shown above the body
+    let calendar_event = find_calendar_part(&mail)
+        .and_then(|part| part.get_body().ok())
+        .and_then(|ics| parse_calendar_event(&ics));
+
+    // Create frontmatter (sidecar names are set once the filename is final)
+    let mut frontmatter = EmailFrontmatter {
+        schema_version: FRONTMATTER_SCHEMA_VERSION,
+        from: redact(from_field),
+        to: address_list(&mail, "To").into_iter().map(redact).collect(),
+        cc: address_list(&mail, "Cc").into_iter().map(redact).collect(),
+        bcc: address_list(&mail, "Bcc").into_iter().map(redact).collect(),
         date: date_obj
             .map(|d| d.to_rfc3339())
             .unwrap_or_else(|| date_field.clone()),
-        subject: subject.clone(),
-        subject_hash,
+        subject: redact(subject.clone()),
+        subject_hash: subject_hash.clone(),
+        message_id: message_id.clone(),
+        folder: folder.map(String::from),
+        in_reply_to,
+        references,
+        auto_submitted: auto_submitted(&mail),
+        // Not redacted: a masked `mailto:` could no longer be used
+        unsubscribe: unsubscribe_links(&mail),
+        body_hash: body_hash(&body),
         tags,
         attachments: attachments.clone(),
+        ics: None,
+        raw_source: None,
+        calendar_event: calendar_event.is_some(),
     };
 
-    // Normalize body and add attachments list
-    let mut normalized_body = normalize_line_breaks(&body);
+    // Normalize body and add attachments list (the body hash above uses the full body)
+    let mut normalized_body = finish_body(&body, account);
+    if let Some(event) = &calendar_event {
+        normalized_body = format!("{}\n{}", redact(event.to_markdown()), normalized_body);
+    }
+    push_attachment_list(&mut normalized_body, &attachments);
 
-    if !attachments.is_empty() {
-        normalized_body.push_str("\n\n### Pieces jointes :\n");
-        for attachment in &attachments {
-            let filename_only = Path::new(attachment)
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy();
-            normalized_body.push_str(&format!("- [{}]({})\n", filename_only, attachment));
+    // Write file, never overwriting one created meanwhile by a concurrent export
+    fs::create_dir_all(export_directory)?;
+    loop {
+        frontmatter.ics = ics_payload.as_ref().map(|_| sidecar_filename(&filename, "ics"));
+        frontmatter.raw_source = account.keep_raw_eml.then(|| sidecar_filename(&filename, "eml"));
+        let yaml = serde_yaml::to_string(&frontmatter)?;
+        let content = format!("---\n{}---\n\n{}", yaml, normalized_body);
+        if write_new_file(export_directory, &filename, content.as_bytes())? {
+            break;
         }
-    }
 
-    // Write file
+        // Same message exported concurrently, or another one that took the name
+        if account.skip_existing && already_exported(ledger.as_deref()) {
+            return Ok(None);
+        }
+        counter += 1;
+        filename = format!("{}_{}.md", base_filename.replace('*', "_"), counter);
+    }
     let filepath = export_directory.join(&filename);
-    let mut file = File::create(&filepath)?;
 
-    writeln!(file, "---")?;
-    let yaml = serde_yaml::to_string(&frontmatter)?;
-    write!(file, "{}", yaml)?;
-    writeln!(file, "---\n")?;
-    write!(file, "{}", normalized_body)?;
+    // Sidecars share the name the markdown file claimed
+    if let Some(payload) = &ics_payload {
+        fs::write(export_directory.join(sidecar_filename(&filename, "ics")), payload)?;
+    }
+    // Keep the message as received, for what the markdown leaves out
+    if account.keep_raw_eml {
+        fs::write(export_directory.join(sidecar_filename(&filename, "eml")), raw_email)?;
+    }
+
+    if let Some(index) = export_index {
+        index.insert(&source_hash)?;
+    }
+    if let (Some(ledger), Some(id)) = (ledger, &message_id) {
+        ledger.insert(id)?;
+    }
 
     Ok(Some(filepath))
 }
 
+/// Create `directory/filename` with `content`, unless that file already exists.
+///
+/// The content is written to a temporary file first, then hard-linked into
+/// place: the link fails rather than replacing an existing file, and readers
+/// never see a partially written one. Returns `false` if the name is taken.
+fn write_new_file(directory: &Path, filename: &str, content: &[u8]) -> Result<bool> {
+    static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let final_path = directory.join(filename);
+    let temp_path = directory.join(format!(
+        ".{}.{}-{}.tmp",
+        filename,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::write(&temp_path, content)?;
+
+    let linked = match fs::hard_link(&temp_path, &final_path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(false),
+        // No hard links on this filesystem (e.g. FAT): exclusive create instead
+        Err(_) => match OpenOptions::new().write(true).create_new(true).open(&final_path) {
+            Ok(mut file) => file.write_all(content).map(|_| true),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e),
+        },
+    };
+
+    let _ = fs::remove_file(&temp_path);
+    Ok(linked?)
+}
+
 /// Extract the body from a parsed email.
-fn extract_body(mail: &ParsedMail) -> String {
+///
+/// The flag tells whether the body is HTML (no text/plain alternative).
+fn extract_body(mail: &ParsedMail) -> (String, bool) {
     if mail.subparts.is_empty() {
         // Not multipart
-        mail.get_body().unwrap_or_default()
+        let is_html = mail.ctype.mimetype.eq_ignore_ascii_case("text/html");
+        (mail.get_body().unwrap_or_default(), is_html)
     } else {
         // Multipart - look for text/plain or text/html
         let mut body = String::new();
+        let mut is_html = false;
 
         for part in &mail.subparts {
             let content_type = part
@@ -379,39 +844,307 @@ fn extract_body(mail: &ParsedMail) -> String {
 
             if content_type.starts_with("text/plain") {
                 body = part.get_body().unwrap_or_default();
+                is_html = false;
                 break;
             } else if content_type.starts_with("text/html") && body.is_empty() {
                 body = part.get_body().unwrap_or_default();
+                is_html = true;
             } else if content_type.starts_with("multipart/") {
                 // Recurse into nested multipart
-                let nested_body = extract_body(part);
+                let (nested_body, nested_html) = extract_body(part);
                 if !nested_body.is_empty() && body.is_empty() {
                     body = nested_body;
+                    is_html = nested_html;
                 }
             }
         }
 
+        (body, is_html)
+    }
+}
+
+/// Find the first `text/calendar` part of an email, if any.
+fn find_calendar_part<'a>(mail: &'a ParsedMail<'a>) -> Option<&'a ParsedMail<'a>> {
+    if mail.ctype.mimetype.eq_ignore_ascii_case("text/calendar") {
+        return Some(mail);
+    }
+    mail.subparts.iter().find_map(find_calendar_part)
+}
+
+/// Key fields of the first event of a calendar invite.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CalendarEvent {
+    pub summary: Option<String>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub location: Option<String>,
+    pub organizer: Option<String>,
+}
+
+impl CalendarEvent {
+    /// Render the event as a markdown block placed above the email body.
+    fn to_markdown(&self) -> String {
+        let mut block = format!(
+            "### Invitation : {}\n",
+            self.summary.as_deref().unwrap_or("(sans titre)")
+        );
+        for (label, value) in [
+            ("Début", &self.start),
+            ("Fin", &self.end),
+            ("Lieu", &self.location),
+            ("Organisateur", &self.organizer),
+        ] {
+            if let Some(value) = value {
+                block.push_str(&format!("- **{}** : {}\n", label, value));
+            }
+        }
+        block
+    }
+}
+
+/// Parse SUMMARY, DTSTART, DTEND, LOCATION and ORGANIZER from the first
+/// VEVENT of an iCalendar document. Returns `None` without a VEVENT.
+pub fn parse_calendar_event(ics: &str) -> Option<CalendarEvent> {
+    // Unfold continuation lines (RFC 5545: CRLF followed by a space or tab)
+    let unfolded = ics.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+
+    let mut event: Option<CalendarEvent> = None;
+    for line in unfolded.lines() {
+        if line.eq_ignore_ascii_case("BEGIN:VEVENT") {
+            event = Some(CalendarEvent::default());
+            continue;
+        }
+        let Some(current) = event.as_mut() else {
+            continue;
+        };
+        if line.eq_ignore_ascii_case("END:VEVENT") {
+            break;
+        }
+
+        let Some((name_and_params, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name_and_params.split(';').next().unwrap_or_default().to_uppercase();
+        let param = |key: &str| {
+            name_and_params
+                .split(';')
+                .skip(1)
+                .find_map(|p| p.split_once('=').filter(|(k, _)| k.eq_ignore_ascii_case(key)))
+                .map(|(_, v)| v.trim_matches('"').to_string())
+        };
+        let text = unescape_ics_text(value);
+
+        match name.as_str() {
+            "SUMMARY" => current.summary = Some(text),
+            "LOCATION" if !text.is_empty() => current.location = Some(text),
+            "DTSTART" => current.start = Some(format_ics_date(value, param("TZID"))),
+            "DTEND" => current.end = Some(format_ics_date(value, param("TZID"))),
+            "ORGANIZER" => {
+                let address = value.trim_start_matches("mailto:").trim_start_matches("MAILTO:");
+                current.organizer = Some(match param("CN") {
+                    Some(name) => format!("{} <{}>", name, address),
+                    None => address.to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+    event
+}
+
+/// Undo iCalendar text escaping (`\,`, `\;`, `\\`; `\n` becomes a space).
+fn unescape_ics_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => text.push(' '),
+                Some(escaped) => text.push(escaped),
+                None => {}
+            }
+        } else {
+            text.push(c);
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Make an iCalendar date readable: `20240115T100000Z` -> `2024-01-15 10:00 UTC`.
+fn format_ics_date(value: &str, tzid: Option<String>) -> String {
+    let formatted = if let Ok(utc) = chrono::NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ") {
+        utc.format("%Y-%m-%d %H:%M UTC").to_string()
+    } else if let Ok(local) = chrono::NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        local.format("%Y-%m-%d %H:%M").to_string()
+    } else if let Ok(day) = chrono::NaiveDate::parse_from_str(value, "%Y%m%d") {
+        day.format("%Y-%m-%d").to_string()
+    } else {
+        value.to_string()
+    };
+
+    match tzid {
+        Some(tz) => format!("{} ({})", formatted, tz),
+        None => formatted,
+    }
+}
+
+/// The calendar invite of an email, or `None` if it carries no (non-empty) one.
+fn calendar_payload(mail: &ParsedMail) -> Option<Vec<u8>> {
+    let payload = find_calendar_part(mail)?.get_body_raw().unwrap_or_default();
+    (!payload.is_empty()).then_some(payload)
+}
+
+/// Name of the `<stem>.<extension>` sidecar kept next to a markdown file.
+fn sidecar_filename(markdown_filename: &str, extension: &str) -> String {
+    format!("{}.{}", markdown_filename.trim_end_matches(".md"), extension)
+}
+
+/// Directory (relative to the export base) of attachments shared between emails.
+const SHARED_ATTACHMENTS_DIR: &str = "attachments/_shared";
+
+/// Store an attachment once per content in `attachments/_shared/<md5>/<filename>`.
+///
+/// If a file with the same content is already stored (under any name), it is
+/// reused and nothing is written. Returns the link relative to
+/// `base_export_directory`.
+pub fn store_shared_attachment(
+    base_export_directory: &Path,
+    filename: &str,
+    payload: &[u8],
+) -> Result<String> {
+    let content_hash = format!("{:x}", md5::compute(payload));
+    let hash_dir = base_export_directory.join(SHARED_ATTACHMENTS_DIR).join(&content_hash);
+
+    let existing = fs::read_dir(&hash_dir).ok().and_then(|entries| {
+        entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| !name.starts_with('.'))
+            .min()
+    });
+
+    let stored_name = match existing {
+        Some(name) => name,
+        None => {
+            fs::create_dir_all(&hash_dir)?;
+            // Losing a race to a concurrent export is fine: same content
+            write_new_file(&hash_dir, filename, payload)?;
+            filename.to_string()
+        }
+    };
+
+    Ok(format!("{}/{}/{}", SHARED_ATTACHMENTS_DIR, content_hash, stored_name))
+}
+
+/// An attachment written by `extract_attachments`.
+struct SavedAttachment {
+    /// Path relative to the export base directory.
+    path: String,
+    /// Content-ID (without angle brackets) of an inline part, referenced as `cid:` in the body.
+    content_id: Option<String>,
+}
+
+/// Body of `mail` as exported: quotes limited to `quote_depth`, attachments
+/// saved (see [`extract_attachments`]) and linked from `cid:` references,
+/// HTML stripped and addresses redacted. Returns it with the paths of the
+/// saved attachments, relative to `base_export_directory`.
+fn render_body(
+    mail: &ParsedMail,
+    attachments_dir: &Path,
+    base_filename: &str,
+    base_export_directory: &Path,
+    account: &Account,
+    debug_mode: bool,
+    skip_calendar: bool,
+) -> Result<(String, Vec<String>)> {
+    let (body, body_is_html) = extract_body(mail);
+
+    // A depth of 0 also keeps every quote
+    let body = if account.quote_depth != QuoteDepth::Limit(0) {
+        limit_quote_depth(&body, account.quote_depth)
+    } else {
         body
+    };
+
+    let mut saved_attachments = Vec::new();
+    extract_attachments(
+        mail,
+        attachments_dir,
+        base_filename,
+        base_export_directory,
+        account,
+        debug_mode,
+        skip_calendar,
+        &mut saved_attachments,
+    )?;
+
+    // Point `cid:` references of inline images at the saved files
+    let body = rewrite_cid_references(&body, &saved_attachments);
+    // No HTML converter: strip the markup rather than embed it
+    let body = if body_is_html { strip_html_tags(&body) } else { body };
+    let body = if account.redact { redact_emails(&body) } else { body };
+
+    Ok((body, saved_attachments.into_iter().map(|a| a.path).collect()))
+}
+
+/// Normalize the line breaks of a rendered body and cut it to `max_body_chars`.
+fn finish_body(body: &str, account: &Account) -> String {
+    let body = normalize_trailing_whitespace(&normalize_line_breaks(body));
+    match account.max_body_chars {
+        Some(max_chars) => truncate_body(&body, max_chars),
+        None => body,
+    }
+}
+
+/// Append the [`ATTACHMENTS_HEADING`] list linking `attachments` to `body`.
+fn push_attachment_list(body: &mut String, attachments: &[String]) {
+    if attachments.is_empty() {
+        return;
+    }
+
+    body.push_str(&format!("\n\n{}\n", ATTACHMENTS_HEADING));
+    for attachment in attachments {
+        let filename_only = Path::new(attachment)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        body.push_str(&format!("- [{}]({})\n", filename_only, attachment));
     }
 }
 
 /// Extract attachments from a parsed email.
+///
+/// Parts with a Content-ID (inline images) are saved even without a
+/// filename, so `cid:` references can be rewritten to them. `text/calendar`
+/// parts are skipped with `skip_calendar`, when the invite has an `.ics` sidecar.
+#[allow(clippy::too_many_arguments)]
 fn extract_attachments(
     mail: &ParsedMail,
     attachments_dir: &Path,
     base_filename: &str,
     base_export_directory: &Path,
-    skip_signature_images: bool,
+    account: &Account,
     debug_mode: bool,
-    attachments: &mut Vec<String>,
+    skip_calendar: bool,
+    attachments: &mut Vec<SavedAttachment>,
 ) -> Result<()> {
     for part in &mail.subparts {
         let content_disposition = part
             .headers
             .get_first_value("Content-Disposition")
             .unwrap_or_default();
+        let content_id = part
+            .headers
+            .get_first_value("Content-ID")
+            .map(|id| id.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+            .filter(|id| !id.is_empty() && part.subparts.is_empty() && !part.ctype.mimetype.starts_with("text/"));
+
+        let is_calendar = part.ctype.mimetype.eq_ignore_ascii_case("text/calendar");
+        if is_calendar && skip_calendar {
+            continue;
+        }
 
-        if content_disposition.is_empty() && part.subparts.is_empty() {
+        if content_disposition.is_empty() && part.subparts.is_empty() && content_id.is_none() && !is_calendar {
             continue;
         }
 
@@ -419,7 +1152,10 @@ fn extract_attachments(
         let has_attachment_disposition = content_disposition.to_lowercase().contains("attachment")
             || content_disposition.to_lowercase().contains("inline");
 
-        if let Some(filename) = extract_attachment_filename(part) {
+        let filename = extract_attachment_filename(part)
+            .or_else(|| content_id.as_deref().map(|id| cid_filename(id, &part.ctype.mimetype)))
+            .or_else(|| is_calendar.then(|| "invite.ics".to_string()));
+        if let Some(filename) = filename {
             let decoded_filename = decode_mime_filename(&filename);
 
             if has_attachment_disposition || !filename.is_empty() {
@@ -431,7 +1167,7 @@ fn extract_attachments(
                 let payload = part.get_body_raw().unwrap_or_default();
 
                 // Check if this is a signature image that should be skipped
-                if skip_signature_images
+                if account.skip_signature_images
                     && is_signature_image(
                         Some(&decoded_filename),
                         &content_type,
@@ -451,21 +1187,29 @@ fn extract_attachments(
 
                 if !payload.is_empty() {
                     let safe_filename = sanitize_filename(&decoded_filename);
-                    let filename_hash = hash_md5_prefix(&decoded_filename, 8);
-                    let full_filename =
-                        format!("{}_{}_{}", base_filename, filename_hash, safe_filename);
-                    let filepath = attachments_dir.join(&full_filename);
-
-                    fs::write(&filepath, &payload)?;
 
-                    // Calculate relative path from base export directory
-                    let relative_path = filepath
-                        .strip_prefix(base_export_directory)
-                        .unwrap_or(&filepath)
-                        .to_string_lossy()
-                        .replace('\\', "/");
-
-                    attachments.push(relative_path);
+                    let relative_path = if account.dedup_attachments {
+                        store_shared_attachment(base_export_directory, &safe_filename, &payload)?
+                    } else {
+                        let filename_hash = hash_md5_prefix(&decoded_filename, 8);
+                        let full_filename =
+                            format!("{}_{}_{}", base_filename, filename_hash, safe_filename);
+                        let filepath = attachments_dir.join(&full_filename);
+
+                        fs::write(&filepath, &payload)?;
+
+                        // Calculate relative path from base export directory
+                        filepath
+                            .strip_prefix(base_export_directory)
+                            .unwrap_or(&filepath)
+                            .to_string_lossy()
+                            .replace('\\', "/")
+                    };
+
+                    attachments.push(SavedAttachment {
+                        path: relative_path,
+                        content_id: content_id.clone(),
+                    });
                 } else if debug_mode {
                     println!(
                         "    Skipping attachment '{}' with empty payload",
@@ -482,8 +1226,9 @@ fn extract_attachments(
                 attachments_dir,
                 base_filename,
                 base_export_directory,
-                skip_signature_images,
+                account,
                 debug_mode,
+                skip_calendar,
                 attachments,
             )?;
         }
@@ -492,6 +1237,44 @@ fn extract_attachments(
     Ok(())
 }
 
+/// Filename for an inline part that has a Content-ID but no name.
+///
+/// `image001.png@01D9` → `image001.png`; `logo@example.com` (image/gif) → `logo.gif`.
+fn cid_filename(content_id: &str, mimetype: &str) -> String {
+    let stem = content_id.split('@').next().unwrap_or(content_id);
+    let name = match mimetype.split_once('/') {
+        Some((_, subtype)) if !stem.contains('.') && !subtype.is_empty() => format!("{}.{}", stem, subtype),
+        _ => stem.to_string(),
+    };
+    sanitize_filename(&name)
+}
+
+/// Rewrite `cid:` references to saved inline parts as markdown image links.
+///
+/// Handles HTML `<img src="cid:...">` tags and the `[cid:...]` markers left in
+/// plain-text bodies by some clients. Unknown Content-IDs are left untouched.
+fn rewrite_cid_references(body: &str, attachments: &[SavedAttachment]) -> String {
+    if attachments.iter().all(|a| a.content_id.is_none()) {
+        return body.to_string();
+    }
+
+    let image_link = |caps: &regex::Captures| {
+        attachments
+            .iter()
+            .find(|a| a.content_id.as_deref() == Some(&caps[1]))
+            .map(|a| {
+                let name = a.path.rsplit('/').next().unwrap_or(&a.path);
+                format!("![{}]({})", name, a.path)
+            })
+            .unwrap_or_else(|| caps[0].to_string())
+    };
+
+    let img_tag = regex::Regex::new(r#"(?i)<img\b[^>]*?\bsrc\s*=\s*["']cid:([^"']+)["'][^>]*>"#).unwrap();
+    let body = img_tag.replace_all(body, image_link);
+    let marker = regex::Regex::new(r"\[cid:([^\]\s]+)\]").unwrap();
+    marker.replace_all(&body, image_link).to_string()
+}
+
 /// Extract filename from an attachment part.
 fn extract_attachment_filename(part: &ParsedMail) -> Option<String> {
     // Try Content-Disposition header first
@@ -534,12 +1317,75 @@ fn extract_filename_from_header(header: &str) -> Option<String> {
     None
 }
 
+/// Callback receiving human-readable progress messages during an export.
+pub type ProgressCallback = Box<dyn Fn(&str) + Send>;
+
+/// SASL XOAUTH2 initial response: `user=<user>^Aauth=Bearer <token>^A^A`.
+///
+/// The IMAP client base64-encodes it before sending.
+pub fn xoauth2_string(user: &str, access_token: &str) -> String {
+    format!("user={}\x01auth=Bearer {}\x01\x01", user, access_token)
+}
+
+/// Authenticator for `AUTHENTICATE XOAUTH2`.
+struct XOAuth2 {
+    user: String,
+    access_token: String,
+}
+
+impl imap::Authenticator for XOAuth2 {
+    type Response = String;
+
+    fn process(&self, challenge: &[u8]) -> Self::Response {
+        // A non-empty challenge carries the error details: an empty reply ends the exchange
+        if challenge.is_empty() {
+            xoauth2_string(&self.user, &self.access_token)
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// Check that `account` can connect and log in, and list its (decoded) folders.
+///
+/// Nothing is downloaded. Connecting is retried according to `net`.
+pub fn test_connection(account: &Account, net: &NetworkConfig) -> Result<Vec<String>> {
+    let mut exporter = ImapExporter::new(account.clone(), false).with_network_config(net.clone());
+    with_retry(net, "connect", || exporter.connect())?;
+
+    let folders = exporter.list_folders();
+    exporter.disconnect().ok();
+    folders
+}
+
+/// Report progress to the callback every this many messages.
+const PROGRESS_CALLBACK_STEP: usize = 25;
+
+/// Save the export checkpoint every this many messages.
+const CHECKPOINT_STEP: usize = 20;
+
 /// IMAP client for exporting emails.
 pub struct ImapExporter {
     session: Option<Session<Box<dyn ImapConnection>>>,
     account: Account,
     debug_mode: bool,
     network_config: NetworkConfig,  // [4][5]
+    /// Bounds the fetches in flight, possibly shared with other exporters.
+    fetch_limit: Arc<ConcurrencyLimit>,
+    progress_callback: Option<ProgressCallback>,
+    run_log: Option<RunLog>,
+    /// Resume point, loaded by `export_account`.
+    checkpoint: Option<ExportCheckpoint>,
+    /// Messages exported so far, checked against `max_messages`.
+    exported_count: usize,
+    /// Load and save the account's resume checkpoint (off for previews).
+    use_checkpoint: bool,
+    /// Set (e.g. by Ctrl-C) to stop between two messages.
+    stop: Option<Arc<AtomicBool>>,
+    /// Only fetch the mail received since the account's last complete run.
+    since_last_run: bool,
+    /// IMAP `SEARCH` criteria of each folder, set by `export_account`.
+    search_criteria: String,
 }
 
 impl ImapExporter {
@@ -549,22 +1395,96 @@ impl ImapExporter {
             account,
             debug_mode,
             network_config: NetworkConfig::default(),  // [4][5]
+            fetch_limit: Arc::new(ConcurrencyLimit::new(NetworkConfig::default().max_concurrency)),
+            progress_callback: None,
+            run_log: None,
+            checkpoint: None,
+            exported_count: 0,
+            use_checkpoint: true,
+            stop: None,
+            since_last_run: false,
+            search_criteria: "ALL".to_string(),
         }
     }
 
+    /// Neither resume from nor write the account's checkpoint, e.g. for a
+    /// preview run that must not affect the next real export. Messages are
+    /// then fetched with `BODY.PEEK[]`, which leaves them unread.
+    pub fn without_checkpoint(mut self) -> Self {
+        self.use_checkpoint = false;
+        self
+    }
+
+    /// Ask the server only for the mail received since the last complete
+    /// export of the account (`SEARCH SINCE`, see [`AccountState`]).
+    pub fn since_last_run(mut self) -> Self {
+        self.since_last_run = true;
+        self
+    }
+
     /// [5] Set custom network configuration
     pub fn with_network_config(mut self, config: NetworkConfig) -> Self {
+        self.fetch_limit = Arc::new(ConcurrencyLimit::new(config.max_concurrency));
         self.network_config = config;
         self
     }
 
+    /// Share a fetch limit with the exporters running alongside, so
+    /// `max_concurrency` holds across all of them rather than per exporter.
+    pub fn with_fetch_limit(mut self, limit: Arc<ConcurrencyLimit>) -> Self {
+        self.fetch_limit = limit;
+        self
+    }
+
+    /// Stop between two messages once `stop` is set, saving the checkpoint;
+    /// `export_account` then returns the stats of what was done.
+    pub fn with_stop_flag(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// Receive progress messages (folder started, messages done) while exporting.
+    pub fn with_progress_callback<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str) + Send + 'static,
+    {
+        self.progress_callback = Some(Box::new(callback));
+        self
+    }
+
+    /// Record every exported, skipped or failed message in a run log.
+    pub fn with_run_log(mut self, run_log: RunLog) -> Self {
+        self.run_log = Some(run_log);
+        self
+    }
+
+    fn log(&self, status: LogStatus, message: &str) {
+        if let Some(run_log) = &self.run_log {
+            run_log.write(status, message);
+        }
+    }
+
+    /// Whether the account's `max_messages` cap has been reached.
+    fn limit_reached(&self) -> bool {
+        self.account.max_messages.is_some_and(|max| self.exported_count >= max)
+    }
+
+    /// Whether the stop flag has been set.
+    fn stop_requested(&self) -> bool {
+        self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::SeqCst))
+    }
+
+    fn report_progress(&self, message: &str) {
+        if let Some(callback) = &self.progress_callback {
+            callback(message);
+        }
+    }
+
     /// Connect to the IMAP server.
     pub fn connect(&mut self) -> Result<()> {
-        let password = self
-            .account
-            .password
-            .as_ref()
-            .context("No password found")?;
+        if !self.account.has_credentials() {
+            anyhow::bail!("No password found");
+        }
 
         if self.debug_mode {
             println!(
@@ -580,7 +1500,19 @@ impl ImapExporter {
             println!("Authenticating as {}...", self.account.username);
         }
 
-        let session = client.login(&self.account.username, password).map_err(|e| e.0)?;
+        let session = match &self.account.auth {
+            AccountAuth::OAuth2 { access_token } => {
+                let authenticator = XOAuth2 {
+                    user: self.account.username.clone(),
+                    access_token: access_token.clone(),
+                };
+                client.authenticate("XOAUTH2", &authenticator).map_err(|e| e.0)?
+            }
+            AccountAuth::Password => {
+                let password = self.account.password.as_deref().unwrap_or_default();
+                client.login(&self.account.username, password).map_err(|e| e.0)?
+            }
+        };
 
         if self.debug_mode {
             println!("Connected successfully!");
@@ -617,33 +1549,64 @@ impl ImapExporter {
         folder_name: &str,
         mut contacts_collector: Option<&mut ContactsCollector>,
     ) -> Result<ExportStats> {
-        let base_export_directory = PathBuf::from(&self.account.export_directory);
-        let export_directory = base_export_directory.join(folder_name.replace('.', "/"));
+        let (base_export_directory, export_directory) = export_directories(&self.account, folder_name);
+
+        let mut export_index = ExportIndex::load(&base_export_directory)?;
+        let mut ledger = MessageIdLedger::load(&base_export_directory)?;
 
         let session = self.session.as_mut().context("Not connected")?;
 
         // Select folder
-        let mailbox = session.select(folder_name)?;
+        let mailbox = session.select(encode_imap_utf7(folder_name))?;
         let message_count = mailbox.exists as usize;
 
         if self.debug_mode {
             println!("  {} messages in folder", message_count);
         }
 
-        // Search for all messages
-        let uids = session.search("ALL")?;
-        let uids_vec: Vec<_> = uids.into_iter().collect();
+        // Search for all messages, skipping those done before an interruption
+        let uid_validity = mailbox.uid_validity;
+        let uids = session.uid_search(&self.search_criteria)?;
+        let found = uids.len();
+        let uids_vec = pending_uids(uids, self.checkpoint.as_ref(), folder_name, uid_validity);
         let total_messages = uids_vec.len();
+        if self.debug_mode && total_messages < found {
+            println!("  Resuming: {} messages already exported", found - total_messages);
+        }
 
         // [3] Progress indicator
         let mut progress = ProgressIndicator::new(folder_name, total_messages);
         let mut stats = ExportStats::default();
 
-        for (_idx, uid) in uids_vec.into_iter().enumerate() {
-            // [4] Retry logic for fetch
-            let fetch_result = with_retry(&self.network_config, "fetch", || {
-                session.fetch(uid.to_string(), "RFC822")
-            });
+        // The network setting wins over the account's `fetch_delay_ms`
+        let fetch_delay = self
+            .network_config
+            .fetch_delay
+            .or(self.account.fetch_delay_ms.map(Duration::from_millis));
+        let mut pacer = Pacer::new(fetch_delay);
+        let max_messages = self.account.max_messages;
+        let stop = self.stop.clone();
+        // `RFC822` marks the message \Seen; a preview must leave the server untouched
+        let fetch_query = if self.use_checkpoint { "RFC822" } else { "BODY.PEEK[]" };
+
+        for uid in uids_vec {
+            if max_messages.is_some_and(|max| self.exported_count >= max)
+                || stop.as_ref().is_some_and(|stop| stop.load(Ordering::SeqCst))
+            {
+                break;
+            }
+            pacer.wait();
+
+            // Borrowed per message, so `self` is free again for the progress report
+            let session = self.session.as_mut().context("Not connected")?;
+
+            // [4] Retry logic for fetch; writing the files is not limited
+            let fetch_result = {
+                let _permit = self.fetch_limit.acquire();
+                with_retry(&self.network_config, "fetch", || {
+                    session.uid_fetch(uid.to_string(), fetch_query)
+                })
+            };
 
             let messages = match fetch_result {
                 Ok(m) => m,
@@ -651,6 +1614,12 @@ impl ImapExporter {
                     if self.debug_mode {
                         println!("  Failed to fetch message {}: {}", uid, e);
                     }
+                    if let Some(run_log) = &self.run_log {
+                        run_log.write(
+                            LogStatus::Error,
+                            &format!("{} uid {}: fetch failed: {}", folder_name, uid, e),
+                        );
+                    }
                     stats.errors += 1;
                     progress.inc();
                     continue;
@@ -663,32 +1632,59 @@ impl ImapExporter {
                         body,
                         &export_directory,
                         &base_export_directory,
-                        vec![folder_name.to_string()],
+                        Vec::new(),
+                        Some(folder_name),
                         &self.account,
                         contacts_collector.as_deref_mut(),
+                        Some(&mut export_index),
+                        Some(&mut ledger),
                         self.debug_mode,
                     );
 
-                    match result {
-                        Ok(Some(_)) => stats.exported += 1,
-                        Ok(None) => stats.skipped += 1,
+                    let (status, detail) = match result {
+                        Ok(Some(path)) => {
+                            stats.exported += 1;
+                            self.exported_count += 1;
+                            (LogStatus::Exported, path.display().to_string())
+                        }
+                        Ok(None) => {
+                            stats.skipped += 1;
+                            (LogStatus::Skipped, "already exported".to_string())
+                        }
                         Err(e) => {
                             if self.debug_mode {
                                 println!("  Error exporting message {}: {}", uid, e);
                             }
                             stats.errors += 1;
+                            (LogStatus::Error, format!("{:#}", e))
                         }
+                    };
+                    if let Some(run_log) = &self.run_log {
+                        run_log.write(status, &format!("{} uid {}: {}", folder_name, uid, detail));
                     }
                 }
             }
 
             // Delete after export if requested
             if self.account.delete_after_export {
-                session.store(uid.to_string(), "+FLAGS (\\Deleted)")?;
+                session.uid_store(uid.to_string(), "+FLAGS (\\Deleted)")?;
             }
 
             // [3] Update progress
             progress.inc();
+
+            let done = stats.exported + stats.skipped + stats.errors;
+            if let Some(checkpoint) = self.checkpoint.as_mut() {
+                checkpoint.record(folder_name, uid_validity, uid);
+                if done % CHECKPOINT_STEP == 0 {
+                    if let Err(e) = checkpoint.save() {
+                        eprintln!("Could not save export checkpoint: {}", e);
+                    }
+                }
+            }
+            if done % PROGRESS_CALLBACK_STEP == 0 {
+                self.report_progress(&format!("{}: {}/{}", folder_name, done, total_messages));
+            }
         }
 
         // [3] Finish progress indicator
@@ -699,7 +1695,7 @@ impl ImapExporter {
 
         // Expunge deleted messages
         if self.account.delete_after_export {
-            session.expunge()?;
+            self.session.as_mut().context("Not connected")?.expunge()?;
         }
 
         Ok(stats)
@@ -709,31 +1705,102 @@ impl ImapExporter {
     pub fn export_account(&mut self) -> Result<HashMap<String, ExportStats>> {
         let mut results = HashMap::new();
         let mut contacts_collector = if self.account.collect_contacts {
-            Some(ContactsCollector::new())
+            Some(ContactsCollector::for_account(&self.account))
         } else {
             None
         };
 
         let folders = self.list_folders()?;
+        let folder_count = folders.len();
+
+        // Previews neither resume nor move the account's last run
+        let started = Utc::now();
+        let mut state = None;
+        if self.use_checkpoint {
+            let checkpoint = ExportCheckpoint::load(&ExportCheckpoint::account_path(&self.account.name))?;
+            self.checkpoint = Some(checkpoint);
+            state = Some(AccountState::load(&AccountState::account_path(&self.account.name))?);
+        }
+        if let Some(state) = state.as_ref().filter(|_| self.since_last_run) {
+            self.search_criteria = state.search_criteria();
+        }
 
-        for folder in folders {
+        for (index, folder) in folders.into_iter().enumerate() {
             // Skip ignored folders
-            if self.account.ignored_folders.contains(&folder) {
+            if self.account.is_folder_ignored(&folder) {
                 println!("Ignored folder: {}", folder);
+                self.log(LogStatus::Info, &format!("Ignored folder: {}", folder));
+                continue;
+            }
+
+            // Skip folders finished by an interrupted run
+            if self.checkpoint.as_ref().is_some_and(|c| c.is_folder_done(&folder)) {
+                println!("Already exported (resuming): {}", folder);
+                self.log(LogStatus::Info, &format!("Already exported (resuming): {}", folder));
                 continue;
             }
 
             println!("Exporting {} ...", folder);
+            self.log(LogStatus::Info, &format!("Exporting {}", folder));
+            self.report_progress(&format!("{} ({}/{})", folder, index + 1, folder_count));
 
-            let stats = self.export_folder(&folder, contacts_collector.as_mut())?;
+            let stats = match self.export_folder(&folder, contacts_collector.as_mut()) {
+                Ok(stats) => stats,
+                Err(e) => {
+                    // Keep the exact resume point, not just the last periodic save
+                    if let Some(checkpoint) = &self.checkpoint {
+                        checkpoint.save().ok();
+                    }
+                    return Err(e);
+                }
+            };
             println!(
                 "  {} exported, {} skipped, {} errors",
                 stats.exported, stats.skipped, stats.errors
             );
 
+            // Stopped by `max_messages`: the next run resumes from here
+            if self.limit_reached() {
+                println!("Reached the limit of {} exported messages", self.exported_count);
+                self.log(LogStatus::Info, "Reached max_messages, stopping");
+                if let Some(checkpoint) = self.checkpoint.take() {
+                    checkpoint.save()?;
+                }
+                results.insert(folder, stats);
+                break;
+            }
+
+            // Interrupted: keep the resume point and report what was done
+            if self.stop_requested() {
+                println!("Interrupted, stopping after {} exported messages", self.exported_count);
+                self.log(LogStatus::Info, "Interrupted, stopping");
+                if let Some(checkpoint) = self.checkpoint.take() {
+                    checkpoint.save()?;
+                }
+                results.insert(folder, stats);
+                break;
+            }
+
+            if let Some(checkpoint) = self.checkpoint.as_mut() {
+                checkpoint.complete_folder(&folder)?;
+            }
             results.insert(folder, stats);
         }
 
+        // Finished: the next export starts from scratch
+        if let Some(checkpoint) = self.checkpoint.take() {
+            checkpoint.clear()?;
+            if let Some(state) = state.as_mut() {
+                if !record_clean_run(state, started, &results)? {
+                    self.log(LogStatus::Info, "Some messages failed, last run not recorded");
+                }
+            }
+        }
+
+        // Attachment directories of emails without attachments are left empty
+        let attachments_dir = PathBuf::from(&self.account.export_directory).join("attachments");
+        cleanup_empty_dirs(&attachments_dir).context("Failed to remove empty attachment directories")?;
+
         // Generate contacts file if enabled
         if let Some(collector) = contacts_collector {
             let base_dir = PathBuf::from(&self.account.export_directory);
@@ -753,6 +1820,22 @@ impl ImapExporter {
     }
 }
 
+/// Record the run started at `started` in `state` unless a message of
+/// `results` failed, so `--since-last-run` still covers the failed ones.
+///
+/// Returns whether the run was recorded.
+fn record_clean_run(
+    state: &mut AccountState,
+    started: DateTime<Utc>,
+    results: &HashMap<String, ExportStats>,
+) -> Result<bool> {
+    if results.values().any(|stats| stats.errors > 0) {
+        return Ok(false);
+    }
+    state.record_run(started)?;
+    Ok(true)
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ExportStats {
     pub exported: usize,
@@ -760,9 +1843,422 @@ pub struct ExportStats {
     pub errors: usize,
 }
 
+impl ExportStats {
+    /// Add up the statistics of two exports.
+    pub fn merge(self, other: ExportStats) -> ExportStats {
+        ExportStats {
+            exported: self.exported + other.exported,
+            skipped: self.skipped + other.skipped,
+            errors: self.errors + other.errors,
+        }
+    }
+}
+
+/// Base export directory of the account and the directory for `folder_name`.
+fn export_directories(account: &Account, folder_name: &str) -> (PathBuf, PathBuf) {
+    let base_export_directory = PathBuf::from(&account.export_directory);
+    let export_directory = if account.preserve_folder_structure {
+        base_export_directory.join(folder_name.replace('.', "/"))
+    } else {
+        base_export_directory.clone()
+    };
+    (base_export_directory, export_directory)
+}
+
+/// Export already-fetched raw messages of one folder, without an IMAP session.
+///
+/// `stop` is checked before each message; once it returns true the stats of
+/// the messages done so far are returned.
+///
+/// Uses the same directory layout, export index and Message-Id ledger as
+/// [`ImapExporter::export_folder`]. A message that fails to export is counted
+/// as an error and does not stop the batch. With `max_exported`, the batch
+/// stops once that many messages have been exported.
+pub fn export_batch(
+    account: &Account,
+    folder_name: &str,
+    messages: &[Vec<u8>],
+    max_exported: Option<usize>,
+    mut contacts_collector: Option<&mut ContactsCollector>,
+    debug_mode: bool,
+    stop: &dyn Fn() -> bool,
+) -> Result<ExportStats> {
+    let (base_export_directory, export_directory) = export_directories(account, folder_name);
+
+    let mut export_index = ExportIndex::load(&base_export_directory)?;
+    let mut ledger = MessageIdLedger::load(&base_export_directory)?;
+    let mut stats = ExportStats::default();
+
+    for (index, raw_email) in messages.iter().enumerate() {
+        if max_exported.is_some_and(|max| stats.exported >= max) || stop() {
+            break;
+        }
+
+        let result = export_to_markdown(
+            raw_email,
+            &export_directory,
+            &base_export_directory,
+            Vec::new(),
+            Some(folder_name),
+            account,
+            contacts_collector.as_deref_mut(),
+            Some(&mut export_index),
+            Some(&mut ledger),
+            debug_mode,
+        );
+
+        match result {
+            Ok(Some(_)) => stats.exported += 1,
+            Ok(None) => stats.skipped += 1,
+            Err(e) => {
+                if debug_mode {
+                    println!("  Error exporting message {}: {}", index + 1, e);
+                }
+                stats.errors += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Heading of the attachment list appended to exported bodies.
+const ATTACHMENTS_HEADING: &str = "### Pieces jointes :";
+
+/// Recompute the `body_hash` of the exported markdown files under `directory`
+/// and list those that no longer match their frontmatter, as
+/// `(path, expected, actual)` in path order (`actual` is empty when the body is).
+///
+/// The invitation block and attachment list added by the export are left out,
+/// as when the hash was computed. Files without a `body_hash` (empty body,
+/// older export), cut by `max_body_chars` or with unreadable frontmatter
+/// (see `fix_yaml::validate_directory`) are skipped.
+pub fn verify_export(directory: &Path) -> Result<Vec<(PathBuf, String, String)>> {
+    // Ending of bodies cut by `truncate_body`
+    let truncated = regex::Regex::new(r"\n\[truncated: \d+ chars omitted\]").unwrap();
+    let mut mismatches = Vec::new();
+
+    for path in markdown_files(directory) {
+        let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let Some((frontmatter, body)) = extract_frontmatter(&content) else {
+            continue;
+        };
+        let Ok(frontmatter) = serde_yaml::from_str::<serde_yaml::Value>(&frontmatter) else {
+            continue;
+        };
+        let Some(expected) = frontmatter.get("body_hash").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        if truncated.is_match(&body) {
+            continue;
+        }
+
+        let has_attachments = frontmatter
+            .get("attachments")
+            .and_then(|v| v.as_sequence())
+            .is_some_and(|a| !a.is_empty());
+        let has_invitation = frontmatter.get("calendar_event").and_then(|v| v.as_bool()) == Some(true);
+
+        let actual = body_hash(&hashed_body(&body, has_invitation, has_attachments)).unwrap_or_default();
+        if actual != expected {
+            mismatches.push((path, expected.to_string(), actual));
+        }
+    }
+
+    mismatches.sort();
+    Ok(mismatches)
+}
+
+/// The part of an exported body covered by `body_hash`: without the leading
+/// invitation block and the trailing attachment list.
+fn hashed_body(body: &str, has_invitation: bool, has_attachments: bool) -> String {
+    let body = match body.rfind(ATTACHMENTS_HEADING) {
+        Some(start) if has_attachments => &body[..start],
+        _ => body,
+    };
+
+    let mut lines = body.lines().skip_while(|line| line.trim().is_empty()).peekable();
+    if has_invitation && lines.peek().is_some_and(|line| line.starts_with("### Invitation : ")) {
+        lines.next();
+        while lines.peek().is_some_and(|line| line.starts_with("- **")) {
+            lines.next();
+        }
+    }
+    lines.collect::<Vec<_>>().join("\n")
+}
+
+/// Directory, under the account's export directory, of the files written by [`export_threads`].
+pub const THREADS_DIR: &str = "threads";
+
+/// Frontmatter of a conversation file written by [`export_threads`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThreadFrontmatter {
+    pub subject: String,
+    /// Date of the first message.
+    pub date: String,
+    /// Senders, in order of first appearance.
+    pub participants: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub message_ids: Vec<String>,
+    pub attachments: Vec<String>,
+}
+
+/// Export raw messages as one markdown file per conversation.
+///
+/// Messages are grouped by `References`/`In-Reply-To` (see
+/// [`group_threads`](crate::threads::group_threads)) and written in date
+/// order, each under a `## From — Date` heading, to `<export_directory>/threads/`.
+/// Quote depth, signature images and redaction apply to each message as for
+/// [`export_to_markdown`].
+///
+/// The Message-Ids written are recorded in the ledger of `threads/`; with
+/// `skip_existing`, a thread whose messages are all there is left alone.
+/// Otherwise the thread is written again and the files of its earlier
+/// exports are removed, even when an earlier message changed its name.
+/// Returns the files written and the counts of messages exported, skipped
+/// and failed to parse.
+pub fn export_threads(messages: &[Vec<u8>], account: &Account) -> Result<(Vec<PathBuf>, ExportStats)> {
+    let base_export_directory = PathBuf::from(&account.export_directory);
+    let export_directory = base_export_directory.join(THREADS_DIR);
+    let attachments_dir = base_export_directory.join("attachments").join(THREADS_DIR);
+    let mut ledger = MessageIdLedger::load(&export_directory)?;
+    let previous_files = thread_files_by_message_id(&export_directory)?;
+    let mut stats = ExportStats::default();
+
+    // A message without any header keeps its first line, as in `export_to_markdown`
+    let raws: Vec<Cow<[u8]>> = messages
+        .iter()
+        .map(|raw| {
+            if starts_with_header(raw) {
+                Cow::Borrowed(&raw[..])
+            } else {
+                Cow::Owned([&b"\r\n"[..], raw].concat())
+            }
+        })
+        .collect();
+    let mut mails: Vec<ParsedMail> = Vec::new();
+    for (index, raw) in raws.iter().enumerate() {
+        match mailparse::parse_mail(raw) {
+            Ok(mail) => mails.push(mail),
+            Err(e) => {
+                println!("  Error parsing message {}: {}", index + 1, e);
+                stats.errors += 1;
+            }
+        }
+    }
+
+    struct Entry<'a> {
+        mail: &'a ParsedMail<'a>,
+        date: Option<DateTime<FixedOffset>>,
+        message_id: Option<String>,
+        in_reply_to: Option<String>,
+        references: Vec<String>,
+        subject: String,
+    }
+
+    let mut entries: Vec<Entry> = mails
+        .iter()
+        .map(|mail| {
+            let header = |name: &str| mail.headers.get_first_value(name).unwrap_or_default();
+            Entry {
+                mail,
+                date: parse_email_date(&header("Date"))
+                    .or_else(|| fallback_date(mail, account.missing_date_policy)),
+                message_id: MessageIdLedger::normalize(&header("Message-ID")),
+                in_reply_to: parse_message_id_list(&header("In-Reply-To")).into_iter().next(),
+                references: parse_message_id_list(&header("References")),
+                subject: subject_of(mail),
+            }
+        })
+        .collect();
+    // Undated messages last
+    entries.sort_by_key(|e| (e.date.is_none(), e.date));
+
+    let links: Vec<ThreadLinks> = entries
+        .iter()
+        .map(|e| ThreadLinks {
+            message_id: e.message_id.as_deref(),
+            in_reply_to: e.in_reply_to.as_deref(),
+            references: &e.references,
+            subject: &e.subject,
+        })
+        .collect();
+
+    let redact = |text: String| if account.redact { redact_emails(&text) } else { text };
+    let mut written = Vec::new();
+
+    for members in group_threads(&links) {
+        // Chronological, whatever the reply structure
+        let mut thread: Vec<&Entry> = members.into_iter().map(|(i, _)| &entries[i]).collect();
+        thread.sort_by_key(|e| (e.date.is_none(), e.date));
+
+        let message_ids: Vec<String> = thread.iter().filter_map(|e| e.message_id.clone()).collect();
+        if account.skip_existing
+            && message_ids.len() == thread.len()
+            && message_ids.iter().all(|id| ledger.contains(id))
+        {
+            stats.skipped += thread.len();
+            continue;
+        }
+
+        let root = thread[0];
+        let subject = root.subject.clone();
+        let date_str = root
+            .date
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "unknown-date".to_string());
+        let key = root.message_id.clone().unwrap_or_else(|| subject.clone());
+        let stem = sanitize_filename(&format!(
+            "thread_{}_{}_{}",
+            date_str,
+            slugify(&subject, SUBJECT_SLUG_MAX_BYTES),
+            hash_md5_prefix(&key, 6)
+        ));
+
+        fs::create_dir_all(&export_directory)?;
+        fs::create_dir_all(&attachments_dir)?;
+
+        let mut participants: Vec<String> = Vec::new();
+        let mut all_attachments = Vec::new();
+        let mut sections = Vec::new();
+
+        for (n, entry) in thread.iter().enumerate() {
+            let from = entry.mail.headers.get_first_value("From").unwrap_or_default();
+            if !participants.contains(&from) {
+                participants.push(from.clone());
+            }
+
+            let (body, attachments) = render_body(
+                entry.mail,
+                &attachments_dir,
+                &format!("{}_{}", stem, n + 1),
+                &base_export_directory,
+                account,
+                false,
+                false,
+            )?;
+            let mut body = finish_body(&body, account);
+            push_attachment_list(&mut body, &attachments);
+            all_attachments.extend(attachments);
+
+            let date = entry
+                .date
+                .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "unknown-date".to_string());
+            sections.push(format!("## {} — {}\n\n{}", redact(from), date, body.trim_end()));
+        }
+
+        let frontmatter = ThreadFrontmatter {
+            subject: redact(subject),
+            date: root.date.map(|d| d.to_rfc3339()).unwrap_or_default(),
+            participants: participants.into_iter().map(redact).collect(),
+            message_ids: message_ids.clone(),
+            attachments: all_attachments,
+        };
+
+        let content = format!(
+            "---\n{}---\n\n{}\n",
+            serde_yaml::to_string(&frontmatter)?,
+            sections.join("\n\n")
+        );
+        let filepath = export_directory.join(format!("{}.md", stem));
+        fs::write(&filepath, content)?;
+
+        // Named after another first message by an earlier export
+        for previous in message_ids.iter().filter_map(|id| previous_files.get(id)) {
+            if *previous != filepath && previous.exists() {
+                fs::remove_file(previous)
+                    .with_context(|| format!("Failed to remove {}", previous.display()))?;
+            }
+        }
+        for id in &message_ids {
+            ledger.insert(id)?;
+        }
+        stats.exported += thread.len();
+        written.push(filepath);
+    }
+
+    Ok((written, stats))
+}
+
+/// Conversation file of each Message-Id listed in the files of `directory`.
+///
+/// Files whose frontmatter is not a [`ThreadFrontmatter`] are not ours and
+/// are left out.
+fn thread_files_by_message_id(directory: &Path) -> Result<HashMap<String, PathBuf>> {
+    let mut files = HashMap::new();
+    if !directory.is_dir() {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("md") {
+            continue;
+        }
+        let content = fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let Some(frontmatter) = extract_frontmatter(&content)
+            .and_then(|(yaml, _)| serde_yaml::from_str::<ThreadFrontmatter>(&yaml).ok())
+        else {
+            continue;
+        };
+        for id in frontmatter.message_ids {
+            files.insert(id, path.clone());
+        }
+    }
+    Ok(files)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
+
+    fn test_account(export_directory: &Path) -> Account {
+        Account {
+            name: "Test".to_string(),
+            server: "imap.example.com".to_string(),
+            port: 993,
+            username: "user@example.com".to_string(),
+            password: None,
+            auth: AccountAuth::Password,
+            export_directory: export_directory.to_string_lossy().to_string(),
+            ignored_folders: Vec::new(),
+            quote_depth: QuoteDepth::Limit(1),
+            skip_existing: true,
+            collect_contacts: false,
+            skip_signature_images: false,
+            delete_after_export: false,
+            keep_ics: false,
+            keep_raw_eml: false,
+            preserve_folder_structure: true,
+            subject_in_filename: false,
+            filename_template: None,
+            missing_date_policy: MissingDatePolicy::default(),
+            filename_recipient: FilenameRecipient::default(),
+            redact: false,
+            dedup_attachments: false,
+            fetch_delay_ms: None,
+            max_messages: None,
+            tag_prefix: None,
+            max_body_chars: None,
+            aliases: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_xoauth2_string() {
+        assert_eq!(
+            xoauth2_string("someuser@example.com", "ya29.vF9dft4qmTc2Nvb3RlckBhdHRhdmlzdGEuY29tCg"),
+            "user=someuser@example.com\x01auth=Bearer ya29.vF9dft4qmTc2Nvb3RlckBhdHRhdmlzdGEuY29tCg\x01\x01"
+        );
+
+        let authenticator = XOAuth2 {
+            user: "u@example.com".to_string(),
+            access_token: "token".to_string(),
+        };
+        assert_eq!(imap::Authenticator::process(&authenticator, b""), "user=u@example.com\x01auth=Bearer token\x01\x01");
+        assert_eq!(imap::Authenticator::process(&authenticator, b"{\"status\":\"401\"}"), "");
+    }
 
     #[test]
     fn test_analyze_email_type() {
@@ -799,7 +2295,1022 @@ mod tests {
         collector.add(&EmailType::Direct, "test@example.com".to_string());
         collector.add(&EmailType::Group, "group@example.com".to_string());
 
-        assert!(collector.direct.contains("test@example.com"));
-        assert!(collector.group.contains("group@example.com"));
+        assert!(collector.direct.contains_key("test@example.com"));
+        assert!(collector.group.contains_key("group@example.com"));
+    }
+
+    #[test]
+    fn test_contacts_collector_counts() {
+        let temp = TempDir::new().unwrap();
+        let mut collector = ContactsCollector::new();
+        for _ in 0..3 {
+            collector.add(&EmailType::Direct, "bob@example.com".to_string());
+        }
+        collector.add(&EmailType::Direct, "alice@example.com".to_string());
+        collector.add(&EmailType::Group, "alice@example.com".to_string());
+        assert_eq!(collector.direct["bob@example.com"], 3);
+        assert_eq!(collector.direct["alice@example.com"], 1);
+
+        let csv = fs::read_to_string(collector.generate_csv(temp.path(), "Test").unwrap()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], "Name,Email,Type,Count,Source,Notes");
+        assert!(rows[1].starts_with("Bob,bob@example.com,Direct,3,"));
+        assert!(rows[2].starts_with("Alice,alice@example.com,Direct,1,"));
+        assert!(rows[3].starts_with("Alice,alice@example.com,Group,1,"));
+    }
+
+    #[test]
+    fn test_contacts_collector_skips_self() {
+        let temp = TempDir::new().unwrap();
+        let mut account = test_account(temp.path());
+        account.username = "me@example.com".to_string();
+        account.aliases = vec!["Me.Alias@example.org".to_string()];
+
+        let mut collector = ContactsCollector::for_account(&account);
+        collector.add(&EmailType::Direct, "me@example.com".to_string());
+        collector.add(&EmailType::Group, "me.alias@example.org".to_string());
+        collector.add(&EmailType::Direct, "alice@example.com".to_string());
+
+        let csv = fs::read_to_string(collector.generate_csv(temp.path(), "Test").unwrap()).unwrap();
+        assert!(csv.contains("alice@example.com"));
+        assert!(!csv.contains("me@example.com"));
+        assert!(!csv.to_lowercase().contains("me.alias@example.org"));
+    }
+
+    #[test]
+    fn test_keep_ics_writes_sidecar() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nSUMMARY:Planning\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let raw_email = format!(
+            "From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Invitation: Planning\r\n\
+             Date: Mon, 15 Jan 2024 10:00:00 +0000\r\nMIME-Version: 1.0\r\n\
+             Content-Type: multipart/alternative; boundary=\"b1\"\r\n\r\n\
+             --b1\r\nContent-Type: text/plain\r\n\r\nYou are invited\r\n\
+             --b1\r\nContent-Type: text/calendar; method=REQUEST\r\n\r\n{}\
+             --b1--\r\n",
+            ics
+        );
+
+        let temp = TempDir::new().unwrap();
+        let mut account = test_account(temp.path());
+        account.keep_ics = true;
+        let export_dir = temp.path().join("INBOX");
+
+        let filepath = export_to_markdown(
+            raw_email.as_bytes(),
+            &export_dir,
+            temp.path(),
+            vec!["INBOX".to_string()],
+            None,
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        let ics_path = filepath.with_extension("ics");
+        let mail = mailparse::parse_mail(raw_email.as_bytes()).unwrap();
+        let original = find_calendar_part(&mail).unwrap().get_body_raw().unwrap();
+        assert_eq!(fs::read(&ics_path).unwrap(), original);
+
+        let content = fs::read_to_string(&filepath).unwrap();
+        let ics_name = ics_path.file_name().unwrap().to_string_lossy().to_string();
+        assert!(content.contains(&format!("ics: {}", ics_name)));
+
+        // The sidecar replaces the `invite.ics` attachment
+        assert!(content.contains("attachments: []\n"));
+        assert!(!content.contains("invite.ics"));
+    }
+
+    #[test]
+    fn test_keep_raw_eml_writes_sidecar() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: =?UTF-8?Q?Archiv=C3=A9?=\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\nX-Custom: kept\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let mut account = test_account(temp.path());
+        account.keep_raw_eml = true;
+        let export_dir = temp.path().join("INBOX");
+
+        let filepath = export_to_markdown(raw_email, &export_dir, temp.path(), vec![], None, &account, None, None, None, false)
+            .unwrap()
+            .unwrap();
+
+        let eml_path = filepath.with_extension("eml");
+        assert_eq!(eml_path.file_stem(), filepath.file_stem());
+        assert_eq!(fs::read(&eml_path).unwrap(), raw_email);
+
+        let content = fs::read_to_string(&filepath).unwrap();
+        let eml_name = eml_path.file_name().unwrap().to_string_lossy().to_string();
+        assert!(content.contains(&format!("raw_source: {}", eml_name)));
+
+        // Off by default
+        account.keep_raw_eml = false;
+        let other = export_to_markdown(b"From: a@x.com\r\nSubject: Other\r\n\r\nBody", &export_dir, temp.path(), vec![], None, &account, None, None, None, false)
+            .unwrap()
+            .unwrap();
+        assert!(!other.with_extension("eml").exists());
+        assert!(!fs::read_to_string(&other).unwrap().contains("raw_source"));
+    }
+
+    #[test]
+    fn test_verify_export_reports_tampered_files() {
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let export_dir = temp.path().join("INBOX");
+        let export = |raw: &str| {
+            export_to_markdown(raw.as_bytes(), &export_dir, temp.path(), vec![], None, &account, None, None, None, false)
+                .unwrap()
+                .unwrap()
+        };
+
+        let plain = export("From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Plain\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nFirst line\r\n> quoted\r\nLast line");
+        let with_attachment = export(
+            "From: billing@example.com\r\nTo: bob@example.com\r\nSubject: Invoice\r\n\
+Date: Mon, 15 Jan 2024 11:00:00 +0000\r\nContent-Type: multipart/mixed; boundary=\"b\"\r\n\r\n\
+--b\r\nContent-Type: text/plain\r\n\r\nSee attached.\r\n\
+--b\r\nContent-Type: application/pdf\r\nContent-Disposition: attachment; filename=\"invoice.pdf\"\r\n\
+Content-Transfer-Encoding: base64\r\n\r\nJVBERi0xLjQgdGVtcGxhdGU=\r\n--b--\r\n",
+        );
+        assert!(fs::read_to_string(&with_attachment).unwrap().contains(ATTACHMENTS_HEADING));
+
+        assert_eq!(verify_export(temp.path()).unwrap(), vec![]);
+
+        // Reflowed whitespace still matches; a changed word does not
+        let content = fs::read_to_string(&plain).unwrap();
+        fs::write(&plain, content.replace("First line", "First   line\n")).unwrap();
+        assert_eq!(verify_export(temp.path()).unwrap(), vec![]);
+
+        let content = fs::read_to_string(&with_attachment).unwrap();
+        let expected = content
+            .lines()
+            .find_map(|line| line.strip_prefix("body_hash: "))
+            .unwrap()
+            .to_string();
+        fs::write(&with_attachment, content.replace("See attached.", "See nothing.")).unwrap();
+
+        let mismatches = verify_export(temp.path()).unwrap();
+        assert_eq!(mismatches.len(), 1);
+        let (path, stored, actual) = &mismatches[0];
+        assert_eq!(path, &with_attachment);
+        assert_eq!(stored, &expected);
+        assert_eq!(actual, &body_hash("See nothing.").unwrap());
+    }
+
+    #[test]
+    fn test_calendar_invite_block() {
+        let ics = "BEGIN:VCALENDAR\r\nMETHOD:REQUEST\r\nBEGIN:VEVENT\r\nSUMMARY:Revue de projet\\, T1\r\n\
+                   DTSTART;TZID=Europe/Paris:20240118T140000\r\nDTEND:20240118T150000Z\r\n\
+                   LOCATION:Salle B\r\nORGANIZER;CN=\"Alice Martin\":mailto:alice@example.com\r\n\
+                   DESCRIPTION:Ordre du jour\r\n  en pièce jointe\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let raw_email = format!(
+            "From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Invitation\r\n\
+             Date: Mon, 15 Jan 2024 10:00:00 +0000\r\nMIME-Version: 1.0\r\n\
+             Content-Type: multipart/alternative; boundary=\"b1\"\r\n\r\n\
+             --b1\r\nContent-Type: text/plain\r\n\r\nYou are invited\r\n\
+             --b1\r\nContent-Type: text/calendar; charset=utf-8; method=REQUEST\r\n\r\n{}\
+             --b1--\r\n",
+            ics
+        );
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let filepath = export_to_markdown(
+            raw_email.as_bytes(),
+            &temp.path().join("INBOX"),
+            temp.path(),
+            Vec::new(),
+            None,
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        let content = fs::read_to_string(&filepath).unwrap();
+        assert!(content.contains("calendar_event: true\n"));
+        assert!(content.contains(
+            "### Invitation : Revue de projet, T1\n\
+             - **Début** : 2024-01-18 14:00 (Europe/Paris)\n\
+             - **Fin** : 2024-01-18 15:00 UTC\n\
+             - **Lieu** : Salle B\n\
+             - **Organisateur** : Alice Martin <alice@example.com>\n\nYou are invited"
+        ));
+
+        // The raw invite is kept as an attachment
+        let frontmatter: EmailFrontmatter = serde_yaml::from_str(content.split("---\n").nth(1).unwrap()).unwrap();
+        assert_eq!(frontmatter.attachments.len(), 1);
+        assert!(frontmatter.attachments[0].ends_with("_invite.ics"));
+
+        // The invitation block and attachment list are not part of the body hash
+        assert_eq!(verify_export(temp.path()).unwrap(), vec![]);
+        assert!(fs::read_to_string(temp.path().join(&frontmatter.attachments[0])).unwrap().contains("SUMMARY:Revue"));
+
+        assert_eq!(parse_calendar_event("BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n"), None);
+    }
+
+    #[test]
+    fn test_export_threads() {
+        let reply = b"From: Bob <bob@example.com>\r\nTo: alice@example.com\r\nSubject: Re: Plan\r\n\
+Message-ID: <b@x>\r\nIn-Reply-To: <a@x>\r\nReferences: <a@x>\r\nDate: Tue, 16 Jan 2024 09:30:00 +0000\r\n\r\n\
+Agreed.\r\n> Shall we meet?\r\n>> older quote\r\n"
+            .to_vec();
+        let first = b"From: Alice <alice@example.com>\r\nTo: bob@example.com\r\nSubject: Plan\r\n\
+Message-ID: <a@x>\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nShall we meet?\r\n"
+            .to_vec();
+        let other = b"From: carol@example.com\r\nTo: bob@example.com\r\nSubject: Other\r\n\
+Message-ID: <c@x>\r\nDate: Wed, 17 Jan 2024 10:00:00 +0000\r\n\r\nUnrelated\r\n"
+            .to_vec();
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let (written, stats) = export_threads(&[reply.clone(), other, first.clone()], &account).unwrap();
+        assert_eq!(written.len(), 2);
+        assert_eq!((stats.exported, stats.skipped, stats.errors), (3, 0, 0));
+        assert!(written.iter().all(|p| p.parent() == Some(&temp.path().join(THREADS_DIR))));
+
+        let content = fs::read_to_string(&written[0]).unwrap();
+        assert_eq!(content.matches("\n## ").count(), 2);
+        let alice = content.find("## Alice <alice@example.com> — 2024-01-15 10:00\n\nShall we meet?").unwrap();
+        let bob = content.find("## Bob <bob@example.com> — 2024-01-16 09:30\n\nAgreed.\n> Shall we meet?\n").unwrap();
+        assert!(alice < bob);
+        assert!(!content.contains("older quote"));
+        assert!(content.contains("message_ids:\n- a@x\n- b@x\n"));
+
+        assert!(fs::read_to_string(&written[1]).unwrap().contains("## carol@example.com — 2024-01-17 10:00\n\nUnrelated"));
+
+        // Already in the ledger: left alone
+        let (again, stats) = export_threads(&[reply, first], &account).unwrap();
+        assert!(again.is_empty());
+        assert_eq!((stats.exported, stats.skipped), (0, 2));
+    }
+
+    #[test]
+    fn test_export_threads_replaces_renamed_thread() {
+        let reply = b"From: bob@example.com\r\nSubject: Re: Plan\r\nMessage-ID: <b@x>\r\n\
+In-Reply-To: <a@x>\r\nDate: Tue, 16 Jan 2024 09:30:00 +0000\r\n\r\nAgreed.\r\n"
+            .to_vec();
+        let first = b"From: alice@example.com\r\nSubject: Plan\r\nMessage-ID: <a@x>\r\n\
+Date: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nShall we meet?\r\n"
+            .to_vec();
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let threads_dir = temp.path().join(THREADS_DIR);
+
+        // The reply arrives alone, then with the message it answers
+        let (early, _) = export_threads(std::slice::from_ref(&reply), &account).unwrap();
+        let (late, stats) = export_threads(&[reply, first], &account).unwrap();
+        assert_eq!(stats.exported, 2);
+        assert_ne!(early, late);
+
+        let files: Vec<PathBuf> = fs::read_dir(&threads_dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().is_some_and(|e| e == "md"))
+            .collect();
+        assert_eq!(files, late);
+        assert_eq!(fs::read_to_string(&late[0]).unwrap().matches("\n## ").count(), 2);
+    }
+
+    #[test]
+    fn test_export_threads_keeps_headerless_message() {
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+
+        let (written, stats) = export_threads(&[b"Just a note\r\nwith no headers\r\n".to_vec()], &account).unwrap();
+        assert_eq!(written.len(), 1);
+        assert_eq!((stats.exported, stats.errors), (1, 0));
+        assert!(fs::read_to_string(&written[0]).unwrap().contains("Just a note\nwith no headers"));
+    }
+
+    #[test]
+    fn test_skip_existing_after_file_moved() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Report\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let export_dir = temp.path().join("INBOX");
+
+        let export = |index: &mut ExportIndex| {
+            export_to_markdown(
+                raw_email,
+                &export_dir,
+                temp.path(),
+                vec!["INBOX".to_string()],
+                None,
+                &account,
+                None,
+                Some(index),
+                None,
+                false,
+            )
+            .unwrap()
+        };
+
+        let mut index = ExportIndex::load(temp.path()).unwrap();
+        let filepath = export(&mut index).unwrap();
+
+        // Simulate the sorter moving the file into a category sub-folder
+        let moved_dir = temp.path().join("keep");
+        fs::create_dir_all(&moved_dir).unwrap();
+        fs::rename(&filepath, moved_dir.join(filepath.file_name().unwrap())).unwrap();
+
+        let mut index = ExportIndex::load(temp.path()).unwrap();
+        assert!(export(&mut index).is_none());
+        assert_eq!(fs::read_dir(&export_dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_ledger_skips_renamed_file() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Report\r\nMessage-ID: <report-1@example.com>\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let export_dir = temp.path().join("INBOX");
+
+        let export = |ledger: &mut MessageIdLedger| {
+            export_to_markdown(
+                raw_email,
+                &export_dir,
+                temp.path(),
+                vec!["INBOX".to_string()],
+                None,
+                &account,
+                None,
+                None,
+                Some(ledger),
+                false,
+            )
+            .unwrap()
+        };
+
+        let mut ledger = MessageIdLedger::load(temp.path()).unwrap();
+        let filepath = export(&mut ledger).unwrap();
+        assert!(ledger.contains("report-1@example.com"));
+
+        // A renamed file defeats the filename scan, but not the ledger
+        fs::rename(&filepath, export_dir.join("renamed.md")).unwrap();
+
+        let mut ledger = MessageIdLedger::load(temp.path()).unwrap();
+        assert!(export(&mut ledger).is_none());
+        assert_eq!(fs::read_dir(&export_dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_export_batch_stops_when_requested() {
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let messages: Vec<Vec<u8>> = (1..=4)
+            .map(|i| {
+                format!(
+                    "From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Message {i}\r\nMessage-ID: <{i}@x>\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody {i}"
+                )
+                .into_bytes()
+            })
+            .collect();
+
+        // The flag is set (as by Ctrl-C) once two messages are done
+        let stop = AtomicBool::new(false);
+        let checks = AtomicUsize::new(0);
+        let should_stop = || {
+            if checks.fetch_add(1, Ordering::SeqCst) == 2 {
+                stop.store(true, Ordering::SeqCst);
+            }
+            stop.load(Ordering::SeqCst)
+        };
+
+        let stats = export_batch(&account, "INBOX", &messages, None, None, false, &should_stop).unwrap();
+        assert_eq!((stats.exported, stats.skipped, stats.errors), (2, 0, 0));
+
+        // The ledger kept the finished messages: a rerun only does the rest
+        let stats = export_batch(&account, "INBOX", &messages, None, None, false, &|| false).unwrap();
+        assert_eq!((stats.exported, stats.skipped), (2, 2));
+    }
+
+    #[test]
+    fn test_concurrent_export_writes_one_file() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Race\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let export_dir = temp.path().join("INBOX");
+
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..2)
+                .map(|_| {
+                    scope.spawn(|| {
+                        export_to_markdown(
+                            raw_email,
+                            &export_dir,
+                            temp.path(),
+                            vec!["INBOX".to_string()],
+                            None,
+                            &account,
+                            None,
+                            None,
+                            None,
+                            false,
+                        )
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert!(results.iter().all(|r| r.is_ok()));
+        let entries: Vec<_> = fs::read_dir(&export_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(entries.len(), 1, "{:?}", entries);
+        assert!(entries[0].ends_with(".md"));
+    }
+
+    #[test]
+    fn test_ledger_miss_falls_back_to_filename_scan() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Report\r\nMessage-ID: <report-2@example.com>\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let export_dir = temp.path().join("INBOX");
+
+        let export = |ledger: &mut MessageIdLedger| {
+            export_to_markdown(
+                raw_email,
+                &export_dir,
+                temp.path(),
+                vec!["INBOX".to_string()],
+                None,
+                &account,
+                None,
+                None,
+                Some(ledger),
+                false,
+            )
+            .unwrap()
+        };
+
+        let mut ledger = MessageIdLedger::load(temp.path()).unwrap();
+        export(&mut ledger).unwrap();
+
+        // Exported before the ledger existed: only the filename scan knows
+        fs::remove_file(temp.path().join(crate::ledger::LEDGER_FILE_NAME)).unwrap();
+        let mut ledger = MessageIdLedger::load(temp.path()).unwrap();
+        assert!(export(&mut ledger).is_none());
+        assert_eq!(fs::read_dir(&export_dir).unwrap().count(), 1);
+
+        assert!(email_already_exported_with_ledger(
+            None,
+            Some("report-2@example.com"),
+            None,
+            "2024-01-15",
+            &get_short_name(Some("alice@example.com")),
+            &get_short_name(Some("bob@example.com")),
+            &hash_md5_prefix("Report", 6),
+            &export_dir,
+        ));
+    }
+
+    #[test]
+    fn test_export_without_headers() {
+        for raw_email in [&b"\r\nJust a body, no headers at all."[..], b"Just a body, no headers at all."] {
+            let temp = TempDir::new().unwrap();
+            let account = test_account(temp.path());
+
+            let filepath = export_to_markdown(
+                raw_email,
+                &temp.path().join("INBOX"),
+                temp.path(),
+                vec!["INBOX".to_string()],
+                None,
+                &account,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+
+            // No Date header: dated the day of the export by default
+            let filename = filepath.file_name().unwrap().to_string_lossy().to_string();
+            let today = Utc::now().format("%Y-%m-%d").to_string();
+            assert_eq!(filename, format!("email_{}_UNK_to_UNK.md", today));
+            let content = fs::read_to_string(&filepath).unwrap();
+            assert!(content.contains("subject_hash: no-subject\n"));
+            assert!(content.contains("Just a body, no headers at all."));
+        }
+    }
+
+    #[test]
+    fn test_filename_recipient() {
+        let sent = b"From: Me <user@example.com>\r\nTo: Me <user@example.com>, Bob Martin <bob@example.com>\r\nCc: carol@example.com\r\nSubject: Sent\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+        let to_self = b"From: alice@example.com\r\nTo: user@example.com\r\nCc: Alias <ME@work.example>\r\nSubject: Received\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+        let filename = |raw: &[u8], mode: FilenameRecipient| {
+            let temp = TempDir::new().unwrap();
+            let mut account = test_account(temp.path());
+            account.aliases = vec!["me@work.example".to_string()];
+            account.filename_recipient = mode;
+            let filepath = export_to_markdown(
+                raw,
+                &temp.path().join("INBOX"),
+                temp.path(),
+                vec![],
+                None,
+                &account,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+            filepath.file_name().unwrap().to_string_lossy().to_string()
+        };
+
+        assert_eq!(filename(sent, FilenameRecipient::FirstTo), "email_2024-01-15_ME_to_ME.md");
+        assert_eq!(filename(sent, FilenameRecipient::FirstOther), "email_2024-01-15_ME_to_BM.md");
+        assert_eq!(filename(sent, FilenameRecipient::Label("team".to_string())), "email_2024-01-15_ME_to_team.md");
+
+        // Only own addresses (username and aliases): the first To is kept
+        assert_eq!(filename(to_self, FilenameRecipient::FirstOther), "email_2024-01-15_ALI_to_USE.md");
+    }
+
+    #[test]
+    fn test_missing_date_policy() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Undated\r\n\
+Received: by mx2.example.com; Tue, 16 Jan 2024 09:00:05 +0000\r\n\
+Received: from client (client.example.com)\r\n\tby mx1.example.com; Mon, 15 Jan 2024 23:59:00 +0000\r\n\r\nBody";
+        let filename = |policy| {
+            let temp = TempDir::new().unwrap();
+            let mut account = test_account(temp.path());
+            account.missing_date_policy = policy;
+            let filepath = export_to_markdown(
+                raw_email,
+                &temp.path().join("INBOX"),
+                temp.path(),
+                vec![],
+                None,
+                &account,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+            filepath.file_name().unwrap().to_string_lossy().to_string()
+        };
+
+        assert_eq!(filename(MissingDatePolicy::Unknown), "email_unknown-date_ALI_to_BOB.md");
+        assert_eq!(filename(MissingDatePolicy::UseReceivedHeader), "email_2024-01-15_ALI_to_BOB.md");
+        assert_eq!(filename(MissingDatePolicy::UseEpoch), "email_1970-01-01_ALI_to_BOB.md");
+        let today = Utc::now().format("%Y-%m-%d").to_string();
+        assert_eq!(filename(MissingDatePolicy::UseToday), format!("email_{}_ALI_to_BOB.md", today));
+    }
+
+    #[test]
+    fn test_recipients_in_frontmatter() {
+        let raw_email = b"From: alice@example.com\r\nTo: Bob Martin <bob@example.com>, carol@example.com\r\n\
+Cc: \"Dave, IT\" <dave@example.com>, Eve <eve@example.com>\r\nSubject: Team\r\n\
+Date: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let filepath = export_to_markdown(
+            raw_email,
+            &temp.path().join("INBOX"),
+            temp.path(),
+            vec![],
+            None,
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        let content = fs::read_to_string(&filepath).unwrap();
+        let yaml = content.trim_start_matches("---\n").split("\n---\n").next().unwrap();
+        let frontmatter: EmailFrontmatter = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(frontmatter.to, vec!["Bob Martin <bob@example.com>", "carol@example.com"]);
+        assert_eq!(frontmatter.cc, vec!["Dave, IT <dave@example.com>", "Eve <eve@example.com>"]);
+        assert!(frontmatter.bcc.is_empty());
+        assert!(!content.contains("bcc:"));
+    }
+
+    #[test]
+    fn test_redacted_export() {
+        let raw_email = b"From: Alice <alice@example.com>\r\nTo: bob@example.com\r\nSubject: Hello\r\n\
+Message-ID: <CAF1@mail.example.com>\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\
+List-Unsubscribe: <mailto:leave@lists.example.com>\r\n\r\nWrite to alice@example.com or carol@test.org.";
+
+        let temp = TempDir::new().unwrap();
+        let mut account = test_account(temp.path());
+        account.redact = true;
+        let filepath = export_to_markdown(
+            raw_email,
+            &temp.path().join("INBOX"),
+            temp.path(),
+            vec![],
+            None,
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        // Short names come from the real addresses
+        assert!(filepath.ends_with("email_2024-01-15_ALI_to_BOB.md"));
+        let content = fs::read_to_string(&filepath).unwrap();
+        assert!(!content.contains("alice@") && !content.contains("bob@") && !content.contains("carol@"));
+        assert!(content.contains("from: Alice <a***@e***.com>\n"));
+        assert!(content.contains("- b***@e***.com\n"));
+        assert!(content.contains("Write to a***@e***.com or c***@t***.org."));
+        assert!(content.contains("message_id: CAF1@mail.example.com\n"));
+        assert!(content.contains("- mailto:leave@lists.example.com\n"));
+    }
+
+    #[test]
+    fn test_dedup_attachments() {
+        let raw_email = |subject: &str, name: &str| {
+            format!(
+                "From: billing@example.com\r\nTo: bob@example.com\r\nSubject: {}\r\n\
+Date: Mon, 15 Jan 2024 10:00:00 +0000\r\nContent-Type: multipart/mixed; boundary=\"b\"\r\n\r\n\
+--b\r\nContent-Type: text/plain\r\n\r\nSee attached.\r\n\
+--b\r\nContent-Type: application/pdf\r\nContent-Disposition: attachment; filename=\"{}\"\r\n\
+Content-Transfer-Encoding: base64\r\n\r\nJVBERi0xLjQgdGVtcGxhdGU=\r\n--b--\r\n",
+                subject, name
+            )
+        };
+
+        let temp = TempDir::new().unwrap();
+        let mut account = test_account(temp.path());
+        account.dedup_attachments = true;
+        let export = |raw: String| {
+            let filepath = export_to_markdown(
+                raw.as_bytes(),
+                &temp.path().join("INBOX"),
+                temp.path(),
+                vec![],
+                None,
+                &account,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+            fs::read_to_string(filepath).unwrap()
+        };
+
+        let first = export(raw_email("Invoice 1", "invoice.pdf"));
+        let second = export(raw_email("Invoice 2", "invoice-feb.pdf"));
+
+        let hash = format!("{:x}", md5::compute(b"%PDF-1.4 template"));
+        let link = format!("attachments/_shared/{}/invoice.pdf", hash);
+        assert!(first.contains(&format!("- {}\n", link)));
+        assert!(second.contains(&format!("- {}\n", link)));
+
+        let stored: Vec<_> = fs::read_dir(temp.path().join("attachments/_shared").join(&hash))
+            .unwrap()
+            .collect();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(fs::read(temp.path().join(&link)).unwrap(), b"%PDF-1.4 template");
+    }
+
+    #[test]
+    fn test_inline_cid_image() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Logo\r\n\
+Date: Mon, 15 Jan 2024 10:00:00 +0000\r\nContent-Type: multipart/related; boundary=\"rel\"\r\n\r\n\
+--rel\r\nContent-Type: text/html; charset=utf-8\r\n\r\n\
+<p>Our logo: <img alt=\"Logo\" src=\"cid:logo123@example.com\"> and <img src=\"cid:missing@x\"></p>\r\n\
+--rel\r\nContent-Type: image/png\r\nContent-ID: <logo123@example.com>\r\n\
+Content-Transfer-Encoding: base64\r\n\r\niVBORw0KGgo=\r\n--rel--\r\n";
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+        let filepath = export_to_markdown(
+            raw_email,
+            &temp.path().join("INBOX"),
+            temp.path(),
+            vec![],
+            None,
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        let name = format!("email_2024-01-15_ALI_to_BOB_{}_logo123.png", hash_md5_prefix("logo123.png", 8));
+        let saved = format!("attachments/INBOX/{}", name);
+        let content = fs::read_to_string(&filepath).unwrap();
+        // The HTML body is stripped once the known image is linked
+        assert!(content.contains(&format!("\nOur logo: ![{}]({}) and\n", name, saved)));
+        assert!(!content.contains("<img") && !content.contains("<p>"));
+        assert_eq!(fs::read(temp.path().join(saved)).unwrap(), b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_auto_submitted() {
+        let parse = |headers: &str| {
+            let raw = format!("{}From: a@example.com\r\n\r\nBody", headers);
+            auto_submitted(&mailparse::parse_mail(raw.as_bytes()).unwrap())
+        };
+        assert_eq!(parse("Auto-Submitted: auto-replied; owner-email=\"a@example.com\"\r\n").as_deref(), Some("auto-replied"));
+        assert_eq!(parse("X-Autoreply: yes\r\n").as_deref(), Some("auto-replied"));
+        assert_eq!(parse("Auto-Submitted: no\r\n"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn test_subject_of() {
+        let parse = |subject: &str| {
+            let raw = format!("Subject: {}\r\nFrom: a@example.com\r\n\r\nBody", subject);
+            subject_of(&mailparse::parse_mail(raw.as_bytes()).unwrap())
+        };
+        assert_eq!(parse("=?UTF-8?Q?Re=3A_caf=C3=A9?="), "Re: café");
+        assert_eq!(parse("Re:=?UTF-8?Q?caf=C3=A9?="), "Re:café");
+        assert_eq!(parse("Plain"), "Plain");
+    }
+
+    #[test]
+    fn test_unsubscribe_links() {
+        let raw = "From: news@shop.example\r\nList-Unsubscribe: <mailto:unsub@shop.example?subject=unsubscribe>,\r\n <https://shop.example/unsub?id=42>, <ftp://shop.example/x>\r\n\r\nBody";
+        let mail = mailparse::parse_mail(raw.as_bytes()).unwrap();
+        assert_eq!(
+            unsubscribe_links(&mail),
+            vec!["mailto:unsub@shop.example?subject=unsubscribe", "https://shop.example/unsub?id=42"]
+        );
+
+        let mail = mailparse::parse_mail(b"From: a@example.com\r\n\r\nBody").unwrap();
+        assert!(unsubscribe_links(&mail).is_empty());
+    }
+
+    #[test]
+    fn test_max_body_chars() {
+        let raw_email = "From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Promo\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nÉté été été été".as_bytes();
+
+        let temp = TempDir::new().unwrap();
+        let mut account = test_account(temp.path());
+        account.max_body_chars = Some(6);
+
+        let filepath = export_to_markdown(
+            raw_email,
+            &temp.path().join("INBOX"),
+            temp.path(),
+            Vec::new(),
+            None,
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        let content = fs::read_to_string(&filepath).unwrap();
+        assert!(content.contains("Été ét\n\n[truncated: 9 chars omitted]"));
+        assert!(!content.contains("été été"));
+
+        assert_eq!(truncate_body("short", 10), "short");
+        assert_eq!(truncate_body("exactly", 7), "exactly");
+    }
+
+    #[test]
+    fn test_derived_tags_in_frontmatter() {
+        let raw_email = b"From: news@shop.example.com\r\nTo: bob@example.com\r\nSubject: Weekly newsletter\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let mut account = test_account(temp.path());
+        account.name = "Work Mail".to_string();
+
+        let filepath = export_to_markdown(
+            raw_email,
+            &temp.path().join("Archive/2023"),
+            temp.path(),
+            vec!["imported".to_string()],
+            Some("Archive/2023"),
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        let content = fs::read_to_string(&filepath).unwrap();
+        assert!(content.contains("tags:\n- imported\n- work-mail\n- archive-2023\n- newsletter\n"));
+
+        account.tag_prefix = Some("email/".to_string());
+        assert_eq!(
+            derived_tags(&account, Some("INBOX"), &EmailType::Direct),
+            vec!["email/work-mail", "email/inbox", "email/direct"]
+        );
+        assert_eq!(derived_tags(&account, None, &EmailType::MailingList), vec!["email/work-mail", "email/mailing_list"]);
+    }
+
+    #[test]
+    fn test_message_id_in_frontmatter() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Hello\r\nMessage-ID: <CAF12345@mail.example.com>\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+
+        let filepath = export_to_markdown(
+            raw_email,
+            &temp.path().join("INBOX"),
+            temp.path(),
+            vec!["INBOX".to_string()],
+            None,
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        let content = fs::read_to_string(&filepath).unwrap();
+        assert!(content.contains("message_id: CAF12345@mail.example.com\n"));
+        assert!(content.starts_with(&format!("---\nschema_version: {}\n", FRONTMATTER_SCHEMA_VERSION)));
+
+        // Files from before versioning still parse
+        let legacy: EmailFrontmatter = serde_yaml::from_str(
+            "from: a\nto: b\ndate: ''\nsubject: s\nsubject_hash: h\ntags: []\nattachments: []\n",
+        )
+        .unwrap();
+        assert_eq!(legacy.schema_version, 0);
+        assert_eq!(legacy.to, vec!["b"]);
+    }
+
+    #[test]
+    fn test_body_hash_in_frontmatter() {
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+
+        let export = |subject: &str, body: &str| {
+            let raw_email = format!(
+                "From: alice@example.com\r\nTo: bob@example.com\r\nSubject: {}\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\n{}",
+                subject, body
+            );
+            let filepath = export_to_markdown(
+                raw_email.as_bytes(),
+                &temp.path().join("INBOX"),
+                temp.path(),
+                vec!["INBOX".to_string()],
+                None,
+                &account,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+            .unwrap();
+            let content = fs::read_to_string(&filepath).unwrap();
+            let (frontmatter, _) = crate::fix_yaml::extract_frontmatter(&content).unwrap();
+            serde_yaml::from_str::<EmailFrontmatter>(&frontmatter).unwrap().body_hash
+        };
+
+        let original = export("One", "See you at noon.\r\nBob");
+        let copy = export("Two", "See you   at noon.\r\n\r\n  Bob\r\n> quoted reply");
+        let other = export("Three", "See you at one.\r\nBob");
+
+        assert!(original.is_some());
+        assert_eq!(original, copy);
+        assert_ne!(original, other);
+    }
+
+    #[test]
+    fn test_parse_message_id_list() {
+        assert_eq!(
+            parse_message_id_list("<a@x> <b@x>\r\n <c@x><d@x>"),
+            vec!["a@x", "b@x", "c@x", "d@x"]
+        );
+        assert!(parse_message_id_list("").is_empty());
+    }
+
+    #[test]
+    fn test_folder_in_frontmatter() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Old\r\nDate: Mon, 15 Jan 2023 10:00:00 +0000\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let account = test_account(temp.path());
+
+        let filepath = export_to_markdown(
+            raw_email,
+            &temp.path().join("Archive/2023"),
+            temp.path(),
+            vec!["Archive/2023".to_string()],
+            Some("Archive/2023"),
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        let content = fs::read_to_string(&filepath).unwrap();
+        assert!(content.contains("folder: Archive/2023\n"));
+    }
+
+    #[test]
+    fn test_subject_slug_in_filename() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Weekly Report: Q3\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let mut account = test_account(temp.path());
+        account.subject_in_filename = true;
+
+        let filepath = export_to_markdown(
+            raw_email,
+            temp.path(),
+            temp.path(),
+            Vec::new(),
+            None,
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        let filename = filepath.file_name().unwrap().to_string_lossy().to_string();
+        assert!(filename.ends_with("_weekly-report-q3.md"), "{}", filename);
+    }
+
+    #[test]
+    fn test_filename_template() {
+        let raw_email = b"From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Weekly Report\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody";
+
+        let temp = TempDir::new().unwrap();
+        let mut account = test_account(temp.path());
+        account.filename_template = Some("{date}_{subject}_{hash}".to_string());
+
+        let export = || {
+            export_to_markdown(
+                raw_email,
+                temp.path(),
+                temp.path(),
+                Vec::new(),
+                None,
+                &account,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+        };
+
+        let filepath = export().unwrap();
+        let expected = format!("2024-01-15_weekly-report_{}.md", hash_md5_prefix("Weekly Report", 6));
+        assert_eq!(filepath.file_name().unwrap().to_string_lossy(), expected);
+
+        // skip_existing still finds the file under the custom layout
+        assert!(export().is_none());
+    }
+
+    #[test]
+    fn test_last_run_not_recorded_after_failed_message() {
+        use chrono::TimeZone;
+
+        let temp = TempDir::new().unwrap();
+        let mut state = AccountState::load(&temp.path().join("state.json")).unwrap();
+        let first = Utc.with_ymd_and_hms(2024, 1, 5, 9, 0, 0).unwrap();
+        let second = Utc.with_ymd_and_hms(2024, 2, 5, 9, 0, 0).unwrap();
+
+        let mut results = HashMap::new();
+        results.insert("INBOX".to_string(), ExportStats { exported: 3, skipped: 0, errors: 0 });
+        assert!(record_clean_run(&mut state, first, &results).unwrap());
+        assert_eq!(state.last_run, Some(first));
+
+        // One folder with a failed message: the timestamp stays put
+        results.insert("Sent".to_string(), ExportStats { exported: 1, skipped: 0, errors: 1 });
+        assert!(!record_clean_run(&mut state, second, &results).unwrap());
+        assert_eq!(state.last_run, Some(first));
+        assert_eq!(AccountState::load(&temp.path().join("state.json")).unwrap().last_run, Some(first));
     }
 }
@@ -0,0 +1,103 @@
+//! Global index of exported emails, keyed by the hash of the raw message.
+//!
+//! The index lives at the root of an account's export directory, so an email
+//! stays "already exported" even after the sorter (or the user) moves its
+//! markdown file into another sub-folder.
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File name of the index inside the base export directory.
+pub const INDEX_FILE_NAME: &str = ".export_index";
+
+/// Set of source hashes for every email exported under a base directory.
+#[derive(Debug)]
+pub struct ExportIndex {
+    path: PathBuf,
+    hashes: HashSet<String>,
+}
+
+impl ExportIndex {
+    /// Load the index stored in `base_export_directory` (empty if absent).
+    pub fn load(base_export_directory: &Path) -> Result<Self> {
+        let path = base_export_directory.join(INDEX_FILE_NAME);
+
+        let hashes = if path.exists() {
+            fs::read_to_string(&path)
+                .context("Failed to read export index")?
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+
+        Ok(ExportIndex { path, hashes })
+    }
+
+    /// Compute the source hash of a raw RFC822 message.
+    pub fn source_hash(raw_email: &[u8]) -> String {
+        format!("{:x}", md5::compute(raw_email))
+    }
+
+    /// Check whether an email with this source hash was already exported.
+    pub fn contains(&self, source_hash: &str) -> bool {
+        self.hashes.contains(source_hash)
+    }
+
+    /// Record a source hash, appending it to the index file on disk.
+    pub fn insert(&mut self, source_hash: &str) -> Result<()> {
+        if !self.hashes.insert(source_hash.to_string()) {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("Failed to open export index")?;
+        writeln!(file, "{}", source_hash)?;
+
+        Ok(())
+    }
+
+    /// Number of indexed emails.
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Whether the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_index_persists_across_loads() {
+        let temp = TempDir::new().unwrap();
+        let hash = ExportIndex::source_hash(b"raw email");
+
+        let mut index = ExportIndex::load(temp.path()).unwrap();
+        assert!(!index.contains(&hash));
+        index.insert(&hash).unwrap();
+        index.insert(&hash).unwrap();
+
+        let reloaded = ExportIndex::load(temp.path()).unwrap();
+        assert!(reloaded.contains(&hash));
+        assert_eq!(reloaded.len(), 1);
+    }
+}
@@ -1,8 +1,12 @@
+use crate::utils::{markdown_files, Verbosity};
 use anyhow::{Context, Result};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use regex::Regex;
 use serde_yaml::Value;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use walkdir::WalkDir;
 
 /// Statistics for the fix operation.
@@ -14,6 +18,18 @@ pub struct FixStats {
     pub errors: usize,
 }
 
+impl FixStats {
+    /// Combine the statistics of two disjoint sets of files.
+    pub fn merge(self, other: FixStats) -> FixStats {
+        FixStats {
+            total_scanned: self.total_scanned + other.total_scanned,
+            files_fixed: self.files_fixed + other.files_fixed,
+            files_rewritten: self.files_rewritten + other.files_rewritten,
+            errors: self.errors + other.errors,
+        }
+    }
+}
+
 /// Fix complex YAML tags in email frontmatter.
 pub fn fix_complex_yaml_tags(content: &str) -> String {
     let mut fixed = content.to_string();
@@ -38,9 +54,12 @@ pub fn fix_complex_yaml_tags(content: &str) -> String {
     if let Some(caps) = re_subject.captures(&fixed) {
         let chunks = caps.get(1).map_or("", |m| m.as_str());
         // Extract the actual subject text from chunks
-        let re_text = Regex::new(r#"-\s*(['"])(.*?)\1"#).unwrap();
+        let re_text = Regex::new(r#"-\s*(?:'([^']*)'|"([^"]*)")"#).unwrap();
         if let Some(text_match) = re_text.captures(chunks) {
-            let subject_text = text_match.get(2).map_or("Unknown", |m| m.as_str());
+            let subject_text = text_match
+                .get(1)
+                .or_else(|| text_match.get(2))
+                .map_or("Unknown", |m| m.as_str());
             fixed = re_subject
                 .replace_all(&fixed, format!("subject: \"{}\"", subject_text))
                 .to_string();
@@ -60,6 +79,129 @@ pub fn fix_complex_yaml_tags(content: &str) -> String {
     fixed
 }
 
+/// Quote frontmatter `subject:` values that contain a colon but were written unquoted.
+///
+/// `subject: Re: Meeting: Q3` is invalid YAML; it becomes
+/// `subject: "Re: Meeting: Q3"`. Only the frontmatter is touched.
+pub fn quote_unquoted_subject(content: &str) -> String {
+    if !content.starts_with("---") {
+        return content.to_string();
+    }
+
+    let mut in_frontmatter = true;
+    content
+        .split_inclusive('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if i > 0 && line.trim() == "---" {
+                in_frontmatter = false;
+            }
+            if i == 0 || !in_frontmatter {
+                return line.to_string();
+            }
+
+            let Some(value) = line.strip_prefix("subject:") else {
+                return line.to_string();
+            };
+            let ending = &value[value.trim_end().len()..];
+            let value = value.trim();
+            if value.is_empty() || value.starts_with(['"', '\'']) || !value.contains(':') {
+                return line.to_string();
+            }
+
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("subject: \"{}\"{}", escaped, ending)
+        })
+        .collect()
+}
+
+/// Replace the tabs indenting frontmatter lines with two spaces each.
+///
+/// YAML forbids tab indentation, but hand-edited files use it. Tabs after the
+/// indentation and anything outside the frontmatter are left alone.
+pub fn expand_frontmatter_tabs(content: &str) -> String {
+    if !content.starts_with("---") {
+        return content.to_string();
+    }
+
+    let mut in_frontmatter = true;
+    content
+        .split_inclusive('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if i > 0 && line.trim() == "---" {
+                in_frontmatter = false;
+            }
+            if i == 0 || !in_frontmatter {
+                return line.to_string();
+            }
+
+            let rest = line.trim_start_matches([' ', '\t']);
+            let indent = &line[..line.len() - rest.len()];
+            if !indent.contains('\t') {
+                return line.to_string();
+            }
+            format!("{}{}", indent.replace('\t', "  "), rest)
+        })
+        .collect()
+}
+
+/// Which occurrence of a repeated frontmatter key survives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeyPolicy {
+    KeepFirst,
+    /// The latest rewrite wins.
+    #[default]
+    KeepLast,
+}
+
+/// Drop repeated top-level frontmatter keys, which YAML rejects.
+///
+/// A key owns its line and the indented or `- ` lines below it; only one
+/// occurrence per key is kept, chosen by `policy`. Nested keys and the body
+/// are left alone.
+pub fn dedupe_frontmatter_keys(content: &str, policy: DuplicateKeyPolicy) -> String {
+    if !content.starts_with("---") {
+        return content.to_string();
+    }
+
+    let key_re = Regex::new(r"^([A-Za-z_][\w-]*)\s*:").unwrap();
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let Some(end) = lines.iter().skip(1).position(|l| l.trim() == "---").map(|p| p + 1) else {
+        return content.to_string();
+    };
+
+    // Top-level entries of the frontmatter: (key, first line, last line exclusive)
+    let mut entries: Vec<(Option<&str>, usize, usize)> = Vec::new();
+    for (i, line) in lines.iter().enumerate().take(end).skip(1) {
+        match key_re.captures(line) {
+            Some(caps) => entries.push((caps.get(1).map(|m| m.as_str()), i, i + 1)),
+            None => match entries.last_mut() {
+                Some(entry) => entry.2 = i + 1,
+                None => entries.push((None, i, i + 1)),
+            },
+        }
+    }
+
+    let keep = |index: usize, key: &str| {
+        let mut same = entries.iter().enumerate().filter(|(_, e)| e.0 == Some(key)).map(|(i, _)| i);
+        let chosen = match policy {
+            DuplicateKeyPolicy::KeepFirst => same.next(),
+            DuplicateKeyPolicy::KeepLast => same.next_back(),
+        };
+        chosen == Some(index)
+    };
+
+    let mut result = String::from(lines[0]);
+    for (index, (key, start, stop)) in entries.iter().enumerate() {
+        if key.is_none_or(|key| keep(index, key)) {
+            result.extend(lines[*start..*stop].iter().copied());
+        }
+    }
+    result.extend(lines[end..].iter().copied());
+    result
+}
+
 /// Extract frontmatter and body from markdown content.
 pub fn extract_frontmatter(content: &str) -> Option<(String, String)> {
     if !content.starts_with("---") {
@@ -93,54 +235,208 @@ pub fn extract_frontmatter(content: &str) -> Option<(String, String)> {
     Some((frontmatter, body))
 }
 
+/// Extension appended to a file's name for its backup (`mail.md` → `mail.md.bak`).
+pub const BACKUP_EXTENSION: &str = "bak";
+
+/// Path of the backup written next to `file_path`.
+pub fn backup_path(file_path: &Path) -> PathBuf {
+    let mut name = file_path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(BACKUP_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Save the original content before the first modification (an existing backup is kept).
+fn write_backup(file_path: &Path, original: &str) -> Result<()> {
+    let backup = backup_path(file_path);
+    if !backup.exists() {
+        fs::write(&backup, original).context("Failed to write backup")?;
+    }
+    Ok(())
+}
+
+/// `content` made ready for fixing (tabs expanded, duplicate keys dropped
+/// according to `policy`), or `None` if the file needs no fix.
+fn prepare_fix(content: &str, policy: DuplicateKeyPolicy) -> Option<String> {
+    let prepared = dedupe_frontmatter_keys(&expand_frontmatter_tabs(content), policy);
+    if !content.contains("!!python/object:") && quote_unquoted_subject(&prepared) == content {
+        None
+    } else {
+        Some(prepared)
+    }
+}
+
+/// Content written by a fix, and whether the frontmatter had to be rebuilt.
+struct FixedContent {
+    content: String,
+    rewritten: bool,
+}
+
+/// Fix the `prepared` content of a file (see [`prepare_fix`]); `None` if it
+/// has no frontmatter.
+///
+/// Python tags are removed and the subject quoted; when the frontmatter
+/// still does not parse, a minimal one is rebuilt from the original `content`.
+/// The body is kept byte for byte: `*word` and `&word` are ordinary text there.
+fn fixed_content(content: &str, prepared: &str) -> Result<Option<FixedContent>> {
+    let Some((frontmatter_block, rest)) = split_at_frontmatter_end(prepared) else {
+        return Ok(None);
+    };
+
+    // Quoting last, so Python tags are gone by then
+    let cleaned = format!("{}{}", quote_unquoted_subject(&fix_complex_yaml_tags(frontmatter_block)), rest);
+    let Some((frontmatter, _)) = extract_frontmatter(&cleaned) else {
+        return Ok(None);
+    };
+    if serde_yaml::from_str::<Value>(&frontmatter).is_ok() {
+        return Ok(Some(FixedContent { content: cleaned, rewritten: false }));
+    }
+
+    // Everything after the closing `---` line
+    let body = rest.split_once('\n').map_or("", |(_, body)| body);
+    let simple_frontmatter = create_simple_frontmatter(content);
+    let rewritten = format!("---\n{}---\n{}", serde_yaml::to_string(&simple_frontmatter)?, body);
+    Ok(Some(FixedContent { content: rewritten, rewritten: true }))
+}
+
+/// Split `content` where the line closing its frontmatter starts:
+/// `("---\n<frontmatter>", "---\n<body>")`. `None` without a closed frontmatter.
+fn split_at_frontmatter_end(content: &str) -> Option<(&str, &str)> {
+    if !content.starts_with("---") {
+        return None;
+    }
+
+    let mut offset = 0;
+    for (i, line) in content.split_inclusive('\n').enumerate() {
+        if i > 0 && line.trim() == "---" {
+            return Some(content.split_at(offset));
+        }
+        offset += line.len();
+    }
+    None
+}
+
 /// Fix a single email markdown file.
-pub fn fix_email_file(file_path: &Path, dry_run: bool) -> Result<bool> {
+///
+/// With `backup`, the original content is saved to `<file>.bak` before rewriting.
+/// `policy` picks which occurrence of a repeated frontmatter key is kept.
+pub fn fix_email_file(
+    file_path: &Path,
+    dry_run: bool,
+    backup: bool,
+    policy: DuplicateKeyPolicy,
+    verbosity: Verbosity,
+) -> Result<bool> {
+    let chatty = verbosity.shows(Verbosity::Normal);
     let content = fs::read_to_string(file_path)
         .context("Failed to read file")?;
 
     // Check if file needs fixing
-    if !content.contains("!!python/object:") {
+    let Some(prepared) = prepare_fix(&content, policy) else {
         return Ok(false);
-    }
-
-    println!("Fixing: {}", file_path.display());
+    };
 
-    // Try the regex approach first
-    let fixed_content = fix_complex_yaml_tags(&content);
+    if chatty {
+        println!("Fixing: {}", file_path.display());
+    }
 
     if dry_run {
         return Ok(true);
     }
 
-    // Try to parse the fixed YAML
-    if let Some((frontmatter, body)) = extract_frontmatter(&fixed_content) {
-        match serde_yaml::from_str::<Value>(&frontmatter) {
-            Ok(_) => {
-                // YAML parses successfully, save the fixed file
-                fs::write(file_path, &fixed_content)?;
-                println!("  Fixed: {}", file_path.display());
-                Ok(true)
-            }
-            Err(_) => {
-                // YAML parsing failed, try to rewrite frontmatter
-                println!("  Complex YAML structure, attempting rewrite...");
-
-                let simple_frontmatter = create_simple_frontmatter(&content);
-                let new_content = format!(
-                    "---\n{}---\n\n{}",
-                    serde_yaml::to_string(&simple_frontmatter)?,
-                    body
-                );
-
-                fs::write(file_path, &new_content)?;
-                println!("  Rewritten: {}", file_path.display());
-                Ok(true)
-            }
+    let Some(fixed) = fixed_content(&content, &prepared)? else {
+        if chatty {
+            println!("  No frontmatter in: {}", file_path.display());
         }
+        return Ok(false);
+    };
+
+    if fixed.rewritten && chatty {
+        println!("  Complex YAML structure, attempting rewrite...");
+    }
+    if backup {
+        write_backup(file_path, &content)?;
+    }
+    fs::write(file_path, &fixed.content)?;
+    if chatty {
+        let action = if fixed.rewritten { "Rewritten" } else { "Fixed" };
+        println!("  {}: {}", action, file_path.display());
+    }
+    Ok(true)
+}
+
+/// Unified diff, for each file under `directory` (or the file itself) that
+/// `fix` would change, of its frontmatter before and after the fix.
+///
+/// `fix` never touches the body, so the frontmatter diff is the whole change.
+/// Nothing is written. Files are returned in path order.
+pub fn preview_fixes(directory: &Path, policy: DuplicateKeyPolicy) -> Result<Vec<(PathBuf, String)>> {
+    let files: Vec<PathBuf> = if directory.is_file() {
+        vec![directory.to_path_buf()]
     } else {
-        println!("  No frontmatter in: {}", file_path.display());
-        Ok(false)
+        markdown_files(directory).collect()
+    };
+
+    let mut diffs = Vec::new();
+    for file_path in files {
+        let content = fs::read_to_string(&file_path)
+            .with_context(|| format!("Failed to read {}", file_path.display()))?;
+        let Some(prepared) = prepare_fix(&content, policy) else {
+            continue;
+        };
+        let Some(fixed) = fixed_content(&content, &prepared)? else {
+            continue;
+        };
+
+        let frontmatter = |text: &str| extract_frontmatter(text).map(|(f, _)| f).unwrap_or_default();
+        let label = file_path.display().to_string();
+        diffs.push((file_path, frontmatter_diff(&label, &frontmatter(&content), &frontmatter(&fixed.content))));
     }
+    diffs.sort();
+    Ok(diffs)
+}
+
+/// Unified diff of two frontmatters, as a single hunk with every line.
+///
+/// Line numbers count from the opening `---` of the file.
+fn frontmatter_diff(label: &str, before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+
+    // Longest common subsequence, filled from the end
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = format!(
+        "--- {}\n+++ {} (fixed)\n@@ -2,{} +2,{} @@\n",
+        label,
+        label,
+        old.len(),
+        new.len()
+    );
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push_str(&format!(" {}\n", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push_str(&format!("-{}\n", old[i]));
+            i += 1;
+        } else {
+            diff.push_str(&format!("+{}\n", new[j]));
+            j += 1;
+        }
+    }
+    diff
 }
 
 /// Create a simple frontmatter structure from complex content.
@@ -170,10 +466,10 @@ fn create_simple_frontmatter(content: &str) -> serde_yaml::Value {
     }
 
     // Try to extract subject
-    let re_subject = Regex::new(r#"subject:.*?(['"])(.*?)\1"#).ok();
+    let re_subject = Regex::new(r#"subject:.*?(?:'([^']*)'|"([^"]*)")"#).ok();
     let subject = if let Some(re) = re_subject {
         re.captures(content)
-            .and_then(|caps| caps.get(2))
+            .and_then(|caps| caps.get(1).or_else(|| caps.get(2)))
             .map_or("Unknown", |m| m.as_str())
     } else {
         "Unknown"
@@ -183,6 +479,19 @@ fn create_simple_frontmatter(content: &str) -> serde_yaml::Value {
         serde_yaml::Value::String(subject.to_string()),
     );
 
+    // Keep the schema version so readers still know which exporter wrote the file
+    if let Some(caps) = Regex::new(r"(?m)^schema_version:\s*(\d+)")
+        .ok()
+        .and_then(|re| re.captures(content))
+    {
+        if let Ok(version) = caps[1].parse::<u64>() {
+            frontmatter.insert(
+                serde_yaml::Value::String("schema_version".to_string()),
+                serde_yaml::Value::Number(version.into()),
+            );
+        }
+    }
+
     // Add empty tags and attachments
     frontmatter.insert(
         serde_yaml::Value::String("tags".to_string()),
@@ -197,37 +506,160 @@ fn create_simple_frontmatter(content: &str) -> serde_yaml::Value {
 }
 
 /// Scan and fix directory for malformed email files.
-pub fn scan_and_fix_directory(directory: &Path, dry_run: bool) -> Result<FixStats> {
-    let mut stats = FixStats::default();
+///
+/// With the `rayon` feature, files are processed in parallel (messages may interleave).
+pub fn scan_and_fix_directory(
+    directory: &Path,
+    dry_run: bool,
+    backup: bool,
+    policy: DuplicateKeyPolicy,
+    verbosity: Verbosity,
+) -> Result<FixStats> {
+    scan_and_fix_directory_with_progress(directory, dry_run, backup, policy, verbosity, |_, _| {})
+}
 
-    let entries: Vec<PathBuf> = if directory.is_file() {
+/// Same as `scan_and_fix_directory`, calling `progress(current, total)` after each file.
+///
+/// Calls are serialized and `current` increases by one each time, even when
+/// files are processed in parallel.
+pub fn scan_and_fix_directory_with_progress<F>(
+    directory: &Path,
+    dry_run: bool,
+    backup: bool,
+    policy: DuplicateKeyPolicy,
+    verbosity: Verbosity,
+    progress: F,
+) -> Result<FixStats>
+where
+    F: FnMut(usize, usize) + Send,
+{
+    let entries: Box<dyn Iterator<Item = PathBuf> + Send> = if directory.is_file() {
+        Box::new(std::iter::once(directory.to_path_buf()))
+    } else {
+        Box::new(markdown_files(directory))
+    };
+    // Counting pass, so the files themselves are still streamed
+    let total = if directory.is_file() {
+        1
+    } else {
+        markdown_files(directory).count()
+    };
+
+    let progress = Mutex::new((0usize, progress));
+    let fix_and_report = |file_path: PathBuf| {
+        let stats = fix_one(&file_path, dry_run, backup, policy, verbosity);
+        if let Ok(mut guard) = progress.lock() {
+            let (current, callback) = &mut *guard;
+            *current += 1;
+            callback(*current, total);
+        }
+        stats
+    };
+
+    #[cfg(feature = "rayon")]
+    let stats = entries
+        .par_bridge()
+        .map(fix_and_report)
+        .reduce(FixStats::default, FixStats::merge);
+
+    #[cfg(not(feature = "rayon"))]
+    let stats = entries
+        .map(fix_and_report)
+        .fold(FixStats::default(), FixStats::merge);
+
+    Ok(stats)
+}
+
+/// List the files under `directory` (or the file itself) whose frontmatter
+/// is not valid YAML, with the reason. Nothing is written.
+///
+/// Files are returned in path order.
+pub fn validate_directory(directory: &Path) -> Vec<(PathBuf, String)> {
+    let files: Vec<PathBuf> = if directory.is_file() {
         vec![directory.to_path_buf()]
     } else {
-        WalkDir::new(directory)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| {
-                e.path().extension().map_or(false, |ext| ext == "md")
-                    && !e.path().to_string_lossy().contains("attachments")
-            })
-            .map(|e| e.path().to_path_buf())
-            .collect()
+        markdown_files(directory).collect()
     };
 
-    for file_path in entries {
-        stats.total_scanned += 1;
+    let mut problems: Vec<(PathBuf, String)> = files
+        .into_iter()
+        .filter_map(|file_path| {
+            let problem = match fs::read_to_string(&file_path) {
+                Err(e) => Some(format!("could not read file: {}", e)),
+                Ok(content) if !content.starts_with("---") => Some("no frontmatter".to_string()),
+                Ok(content) => match extract_frontmatter(&content) {
+                    None => Some("frontmatter is not closed by ---".to_string()),
+                    Some((frontmatter, _)) => serde_yaml::from_str::<Value>(&frontmatter).err().map(|e| e.to_string()),
+                },
+            };
+            problem.map(|problem| (file_path, problem))
+        })
+        .collect();
+    problems.sort();
+    problems
+}
 
-        match fix_email_file(&file_path, dry_run) {
-            Ok(true) => stats.files_fixed += 1,
-            Ok(false) => {} // No fixing needed
-            Err(e) => {
-                println!("  Error processing {}: {}", file_path.display(), e);
-                stats.errors += 1;
-            }
+/// Fix one file and report it as single-file statistics.
+fn fix_one(
+    file_path: &Path,
+    dry_run: bool,
+    backup: bool,
+    policy: DuplicateKeyPolicy,
+    verbosity: Verbosity,
+) -> FixStats {
+    let mut stats = FixStats {
+        total_scanned: 1,
+        ..Default::default()
+    };
+
+    match fix_email_file(file_path, dry_run, backup, policy, verbosity) {
+        Ok(true) => stats.files_fixed += 1,
+        Ok(false) => {} // No fixing needed
+        Err(e) => {
+            eprintln!("  Error processing {}: {}", file_path.display(), e);
+            stats.errors += 1;
         }
     }
 
-    Ok(stats)
+    stats
+}
+
+/// Revert every markdown file under `directory` from the `<name>.md.bak`
+/// backup written by `fix --backup`, removing the backup.
+///
+/// Other `.bak` files (e.g. attachments) are left alone, `attachments/` is
+/// not searched, and a backup whose target exists but is not a regular file
+/// is skipped with a warning. Returns the number of restored files.
+pub fn restore_backups(directory: &Path, verbosity: Verbosity) -> Result<usize> {
+    let mut restored = 0;
+
+    let entries = WalkDir::new(directory)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !(e.file_type().is_dir() && e.file_name() == "attachments"))
+        .filter_map(|e| e.ok());
+    for entry in entries {
+        let backup = entry.path();
+        let is_markdown_backup = entry.file_type().is_file()
+            && backup.extension().is_some_and(|ext| ext == BACKUP_EXTENSION)
+            && backup.file_stem().is_some_and(|stem| Path::new(stem).extension().is_some_and(|ext| ext == "md"));
+        if !is_markdown_backup {
+            continue;
+        }
+
+        let original = backup.with_extension("");
+        if original.exists() && !original.is_file() {
+            eprintln!("  Not restored, {} is not a file", original.display());
+            continue;
+        }
+        fs::rename(backup, &original)
+            .with_context(|| format!("Failed to restore {}", original.display()))?;
+        if verbosity.shows(Verbosity::Normal) {
+            println!("  Restored: {}", original.display());
+        }
+        restored += 1;
+    }
+
+    Ok(restored)
 }
 
 /// Print summary of fix operation.
@@ -258,6 +690,188 @@ mod tests {
         assert!(!fixed.contains("!!python/object:"));
     }
 
+    #[test]
+    fn test_quote_unquoted_subject() {
+        let content = "---\nfrom: a@example.com\nsubject: Re: Fwd: Hi \"there\"\n---\n\nsubject: body line: untouched\n";
+        let fixed = quote_unquoted_subject(content);
+        assert!(fixed.contains("subject: \"Re: Fwd: Hi \\\"there\\\"\"\n"));
+        assert!(fixed.ends_with("subject: body line: untouched\n"));
+
+        let (frontmatter, _) = extract_frontmatter(&fixed).unwrap();
+        let value: Value = serde_yaml::from_str(&frontmatter).unwrap();
+        assert_eq!(value["subject"].as_str(), Some("Re: Fwd: Hi \"there\""));
+
+        // Already quoted or colon-free subjects are left alone
+        let quoted = "---\nsubject: 'Re: Hi'\n---\n";
+        assert_eq!(quote_unquoted_subject(quoted), quoted);
+        let plain = "---\nsubject: Hello\n---\n";
+        assert_eq!(quote_unquoted_subject(plain), plain);
+    }
+
+    #[test]
+    fn test_expand_frontmatter_tabs() {
+        let content = "---\nfrom: a@example.com\nto:\n\t- b@example.com\n\t- c@example.com\nsubject: \"a\tb\"\n---\n\n\tindented body\n";
+        let fixed = expand_frontmatter_tabs(content);
+        assert!(fixed.contains("\n  - b@example.com\n  - c@example.com\n"));
+        assert!(fixed.contains("subject: \"a\tb\""));
+        assert!(fixed.ends_with("\n\tindented body\n"));
+
+        let (frontmatter, _) = extract_frontmatter(&fixed).unwrap();
+        let value: Value = serde_yaml::from_str(&frontmatter).unwrap();
+        assert_eq!(value["to"][1].as_str(), Some("c@example.com"));
+        assert!(serde_yaml::from_str::<Value>(&extract_frontmatter(content).unwrap().0).is_err());
+    }
+
+    #[test]
+    fn test_dedupe_frontmatter_keys() {
+        let content = "---\nfrom: old@example.com\ntags:\n- a\nsubject: Hi\nfrom: new@example.com\ntags:\n- b\n---\n\nfrom: body\n";
+        assert!(serde_yaml::from_str::<Value>(&extract_frontmatter(content).unwrap().0).is_err());
+
+        let last = dedupe_frontmatter_keys(content, DuplicateKeyPolicy::KeepLast);
+        assert_eq!(last, "---\nsubject: Hi\nfrom: new@example.com\ntags:\n- b\n---\n\nfrom: body\n");
+        let value: Value = serde_yaml::from_str(&extract_frontmatter(&last).unwrap().0).unwrap();
+        assert_eq!(value["from"].as_str(), Some("new@example.com"));
+
+        let first = dedupe_frontmatter_keys(content, DuplicateKeyPolicy::KeepFirst);
+        assert_eq!(first, "---\nfrom: old@example.com\ntags:\n- a\nsubject: Hi\n---\n\nfrom: body\n");
+
+        // Nothing to do
+        let clean = "---\nfrom: a@example.com\n---\nBody";
+        assert_eq!(dedupe_frontmatter_keys(clean, DuplicateKeyPolicy::KeepLast), clean);
+    }
+
+    #[test]
+    fn test_validate_directory() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, content: &str| fs::write(temp.path().join(name), content).unwrap();
+        write("good.md", "---\nfrom: a@example.com\nsubject: Hi\n---\n\nBody\n");
+        write("colon.md", "---\nfrom: a@example.com\nsubject: Re: Hi: there\n---\n\nBody\n");
+        write("open.md", "---\nfrom: a@example.com\n");
+        write("plain.md", "Just text\n");
+
+        let problems = validate_directory(temp.path());
+        let files: Vec<_> = problems.iter().map(|(p, _)| p.file_name().unwrap().to_str().unwrap()).collect();
+        assert_eq!(files, vec!["colon.md", "open.md", "plain.md"]);
+        assert!(problems[0].1.contains("line 2"));
+        assert_eq!(problems[2].1, "no frontmatter");
+
+        // Read-only
+        assert_eq!(
+            fs::read_to_string(temp.path().join("colon.md")).unwrap(),
+            "---\nfrom: a@example.com\nsubject: Re: Hi: there\n---\n\nBody\n"
+        );
+        assert!(validate_directory(&temp.path().join("good.md")).is_empty());
+    }
+
+    #[test]
+    fn test_preview_fixes_shows_removed_python_tag() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let tagged = temp.path().join("tagged.md");
+        let original = "---\nfrom: a@example.com\nsubject: !!python/object:email.header.Header Hello\ndate: 2024-01-15\n---\n\n**Body** & *x*\n";
+        fs::write(&tagged, original).unwrap();
+        fs::write(temp.path().join("clean.md"), "---\nfrom: a@example.com\nsubject: 'Hello'\n---\n\nBody\n").unwrap();
+
+        let diffs = preview_fixes(temp.path(), DuplicateKeyPolicy::default()).unwrap();
+        assert_eq!(diffs.len(), 1);
+        let (path, diff) = &diffs[0];
+        assert_eq!(path, &tagged);
+        assert!(diff.contains("\n-subject: !!python/object:email.header.Header Hello\n"), "{}", diff);
+        assert!(diff.contains("\n+subject: "), "{}", diff);
+        assert!(diff.contains("\n from: a@example.com\n"), "{}", diff);
+        assert!(diff.contains("@@ -2,3 +2,3 @@"), "{}", diff);
+
+        assert!(!diff.contains("Body"), "{}", diff);
+
+        // Nothing was written
+        assert_eq!(fs::read_to_string(&tagged).unwrap(), original);
+
+        // Applying the fix changes only what the diff showed
+        assert!(fix_email_file(&tagged, false, false, DuplicateKeyPolicy::default(), Verbosity::Quiet).unwrap());
+        assert!(fs::read_to_string(&tagged).unwrap().ends_with("---\n\n**Body** & *x*\n"));
+    }
+
+    #[test]
+    fn test_restore_backups_only_markdown() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let mail = temp.path().join("INBOX/mail.md");
+        fs::create_dir_all(mail.parent().unwrap()).unwrap();
+        fs::write(&mail, "fixed").unwrap();
+        fs::write(backup_path(&mail), "original").unwrap();
+
+        // Attachments and other .bak files are not backups of ours
+        let attachment = temp.path().join("attachments/INBOX/mail/data.bak");
+        fs::create_dir_all(attachment.parent().unwrap()).unwrap();
+        fs::write(&attachment, "attachment").unwrap();
+        let notes = temp.path().join("notes.txt.bak");
+        fs::write(&notes, "notes").unwrap();
+        fs::write(temp.path().join("notes.txt"), "current").unwrap();
+
+        // A directory in place of the markdown file is not overwritten
+        let blocked = temp.path().join("blocked.md");
+        fs::create_dir(&blocked).unwrap();
+        fs::write(backup_path(&blocked), "original").unwrap();
+
+        assert_eq!(restore_backups(temp.path(), Verbosity::Quiet).unwrap(), 1);
+        assert_eq!(fs::read_to_string(&mail).unwrap(), "original");
+        assert!(attachment.exists());
+        assert!(notes.exists());
+        assert_eq!(fs::read_to_string(temp.path().join("notes.txt")).unwrap(), "current");
+        assert!(blocked.is_dir());
+        assert!(backup_path(&blocked).exists());
+    }
+
+    #[test]
+    fn test_chunks_subject_regex() {
+        // Both quote styles; a backreference in these patterns made `Regex::new` panic
+        for (chunk, expected) in [("'Salut'", "Salut"), ("\"Hello world\"", "Hello world")] {
+            let content = format!("from: a@example.com\nsubject: !!python/object:Header\n  _chunks: [- {}]\n", chunk);
+            let fixed = fix_complex_yaml_tags(&content);
+            assert!(fixed.contains(&format!("subject: \"{}\"", expected)), "{}", fixed);
+            assert!(!fixed.contains("_chunks"), "{}", fixed);
+
+            let simple = create_simple_frontmatter(&format!("subject: x {}\n", chunk));
+            assert_eq!(simple["subject"].as_str(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_fix_keeps_body() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let file = temp.path().join("mail.md");
+        let body = "\n**Important** see *notes* & Tom &amp; Jerry, ref &nbsp;x\n*x* and **bold**\n";
+        fs::write(&file, format!("---\nfrom: a@example.com\nsubject: Re: Meeting: Q3\n---\n{}", body)).unwrap();
+
+        assert!(fix_email_file(&file, false, false, DuplicateKeyPolicy::default(), Verbosity::Quiet).unwrap());
+        let fixed = fs::read_to_string(&file).unwrap();
+        assert_eq!(fixed, format!("---\nfrom: a@example.com\nsubject: \"Re: Meeting: Q3\"\n---\n{}", body));
+
+        // Frontmatter rebuilt from scratch: the body still survives
+        let broken = format!("---\nfrom: a@example.com\nsubject: !!python/object:email.header.Header\n  _chunks: [- 'Hello']\n---\n{}", body);
+        fs::write(&file, &broken).unwrap();
+        assert!(fix_email_file(&file, false, false, DuplicateKeyPolicy::default(), Verbosity::Quiet).unwrap());
+        assert!(fs::read_to_string(&file).unwrap().ends_with(&format!("---\n{}", body)));
+    }
+
+    #[test]
+    fn test_fix_email_file_expands_tabs() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let file = temp.path().join("mail.md");
+        fs::write(&file, "---\nfrom: a@example.com\nto:\n\t- b@example.com\n---\n\nBody\n").unwrap();
+
+        assert!(fix_email_file(&file, false, false, DuplicateKeyPolicy::default(), Verbosity::Quiet).unwrap());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "---\nfrom: a@example.com\nto:\n  - b@example.com\n---\n\nBody\n");
+        assert!(!fix_email_file(&file, false, false, DuplicateKeyPolicy::default(), Verbosity::Quiet).unwrap());
+
+        // Only the tab needs fixing: `*` and `&` in the body stay
+        let body = "\n**bold** and *x*, Tom &amp; Jerry\n";
+        fs::write(&file, format!("---\nfrom: a@example.com\nto:\n\t- b@example.com\n---\n{}", body)).unwrap();
+        assert!(fix_email_file(&file, false, false, DuplicateKeyPolicy::default(), Verbosity::Quiet).unwrap());
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            format!("---\nfrom: a@example.com\nto:\n  - b@example.com\n---\n{}", body)
+        );
+    }
+
     #[test]
     fn test_extract_frontmatter() {
         let content = "---\nfrom: test@example.com\n---\n\nBody content";
@@ -269,6 +883,16 @@ mod tests {
         assert!(body.contains("Body content"));
     }
 
+    #[test]
+    fn test_simple_frontmatter_keeps_schema_version() {
+        let content = "---\nschema_version: 1\nfrom: a@example.com\nsubject: 'Hi'\n---\n";
+        let frontmatter = create_simple_frontmatter(content);
+        assert_eq!(frontmatter["schema_version"].as_u64(), Some(1));
+
+        let legacy = create_simple_frontmatter("---\nfrom: a@example.com\n---\n");
+        assert!(legacy.get("schema_version").is_none());
+    }
+
     #[test]
     fn test_extract_frontmatter_no_closing() {
         let content = "---\nfrom: test@example.com\n\nBody content";
@@ -0,0 +1,168 @@
+//! Reading raw messages from files on disk instead of an IMAP server.
+//!
+//! Each importer returns the raw RFC 822 bytes of every message, ready for
+//! `export_to_markdown`. Supports `.eml` directories and Maildir; see
+//! `mbox.rs` for mbox files.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Read every `.eml` file under `dir` (recursively), paired with its path.
+///
+/// Files are returned in path order; other files are ignored.
+pub fn import_eml_dir(dir: &Path) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let mut paths: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_type().is_file()
+                && e.path()
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("eml"))
+        })
+        .map(|e| e.into_path())
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let raw = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+            Ok((path, raw))
+        })
+        .collect()
+}
+
+/// A message read from a Maildir.
+#[derive(Debug, Clone)]
+pub struct MaildirMessage {
+    pub path: PathBuf,
+    pub raw: Vec<u8>,
+    /// The `S` (seen) flag is set; always false for messages in `new/`.
+    pub seen: bool,
+}
+
+/// Read the messages of a Maildir (`cur/` then `new/`; `tmp/` is ignored).
+///
+/// Flags come from the filename suffix (`<unique>:2,<flags>`, or `!2,` on
+/// filesystems that forbid `:`).
+pub fn read_maildir(path: &Path) -> Result<Vec<MaildirMessage>> {
+    let mut messages = Vec::new();
+
+    for subdir in ["cur", "new"] {
+        let dir = path.join(subdir);
+        if !dir.is_dir() {
+            continue;
+        }
+
+        let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .collect();
+        files.sort();
+
+        for file in files {
+            let raw = fs::read(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+            let seen = subdir == "cur" && maildir_flags(&file).contains('S');
+            messages.push(MaildirMessage { path: file, raw, seen });
+        }
+    }
+
+    Ok(messages)
+}
+
+/// Flags encoded after `:2,` (or `!2,`) in a Maildir filename.
+fn maildir_flags(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    name.rsplit_once(":2,")
+        .or_else(|| name.rsplit_once("!2,"))
+        .map(|(_, flags)| flags.to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::email_export::export_to_markdown;
+    use crate::runner::load_account;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_import_eml_dir_and_export() {
+        let temp = TempDir::new().unwrap();
+        let eml_dir = temp.path().join("eml");
+        fs::create_dir_all(eml_dir.join("nested")).unwrap();
+        fs::write(
+            eml_dir.join("nested/hello.EML"),
+            "From: alice@example.com\r\nTo: bob@example.com\r\nSubject: Hello\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nHi Bob",
+        )
+        .unwrap();
+        fs::write(eml_dir.join("notes.txt"), "not an email").unwrap();
+
+        let messages = import_eml_dir(&eml_dir).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, eml_dir.join("nested/hello.EML"));
+
+        let export_dir = temp.path().join("export");
+        let accounts_path = temp.path().join("accounts.yaml");
+        fs::write(
+            &accounts_path,
+            "accounts:\n  - name: Test\n    server: imap.example.com\n    port: 993\n    username: user@example.com\n",
+        )
+        .unwrap();
+        let settings_path = temp.path().join("settings.yaml");
+        fs::write(&settings_path, format!("export_base_dir: {}\n", export_dir.display())).unwrap();
+        let account = load_account("Test", &accounts_path, &settings_path).unwrap();
+
+        let (_, raw) = &messages[0];
+        let filepath = export_to_markdown(
+            raw,
+            &export_dir.join("Test/eml"),
+            &export_dir.join("Test"),
+            vec!["eml".to_string()],
+            Some("eml"),
+            &account,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap()
+        .unwrap();
+
+        let content = fs::read_to_string(filepath).unwrap();
+        assert!(content.contains("subject: Hello"));
+        assert!(content.contains("Hi Bob"));
+    }
+
+    #[test]
+    fn test_read_maildir() {
+        let temp = TempDir::new().unwrap();
+        let maildir = temp.path();
+        for subdir in ["cur", "new", "tmp"] {
+            fs::create_dir(maildir.join(subdir)).unwrap();
+        }
+        // `!` separator so the fixture also works on Windows
+        fs::write(maildir.join("cur/1700000000.1.host!2,RS"), "Subject: Read\r\n\r\nA").unwrap();
+        fs::write(maildir.join("cur/1700000001.2.host!2,F"), "Subject: Flagged\r\n\r\nB").unwrap();
+        fs::write(maildir.join("new/1700000002.3.host"), "Subject: New\r\n\r\nC").unwrap();
+        fs::write(maildir.join("tmp/1700000003.4.host"), "Subject: Partial").unwrap();
+
+        let messages = read_maildir(maildir).unwrap();
+        let summary: Vec<(&[u8], bool)> = messages.iter().map(|m| (&m.raw[..13], m.seen)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (&b"Subject: Read"[..], true),
+                (&b"Subject: Flag"[..], false),
+                (&b"Subject: New\r"[..], false),
+            ]
+        );
+
+        assert_eq!(maildir_flags(Path::new("cur/1700000000.1.host:2,RS")), "RS");
+    }
+}
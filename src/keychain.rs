@@ -0,0 +1,139 @@
+//! Passwords stored in the OS keychain, as an alternative to `.env`.
+//!
+//! Entries live under the service `email-to-markdown`, keyed by the account
+//! username. Environment variables still win: the keychain is only asked for
+//! accounts that have no password in the environment. The OS keychain itself
+//! is only reachable with the `keyring` feature.
+
+use crate::config::Account;
+use anyhow::Result;
+
+/// Keychain service name of every entry.
+pub const SERVICE: &str = "email-to-markdown";
+
+/// A place to look up and save account passwords.
+pub trait CredentialStore {
+    /// Password stored for `username`, `None` if there is no entry.
+    fn get(&self, username: &str) -> Result<Option<String>>;
+
+    /// Store (or replace) the password of `username`.
+    fn set(&self, username: &str, password: &str) -> Result<()>;
+}
+
+/// The OS keychain (Keychain on macOS, Credential Manager on Windows, the
+/// Secret Service on Linux).
+///
+/// On Linux without a running Secret Service (headless machines), the kernel
+/// keyring is used instead; it is emptied on reboot.
+#[cfg(feature = "keyring")]
+pub struct KeyringStore;
+
+#[cfg(feature = "keyring")]
+impl KeyringStore {
+    /// Run `op` on the entry of `username`, on Linux falling back to the
+    /// kernel keyring when the Secret Service cannot be reached.
+    fn with_entry<T>(username: &str, op: impl Fn(&keyring::Entry) -> keyring::Result<T>) -> keyring::Result<T> {
+        let result = keyring::Entry::new(SERVICE, username).and_then(|entry| op(&entry));
+        #[cfg(target_os = "linux")]
+        if let Err(keyring::Error::PlatformFailure(_) | keyring::Error::NoStorageAccess(_)) = result {
+            let credential = keyring::keyutils::KeyutilsCredential::new_with_target(None, SERVICE, username)?;
+            return op(&keyring::Entry::new_with_credential(Box::new(credential)));
+        }
+        result
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl CredentialStore for KeyringStore {
+    fn get(&self, username: &str) -> Result<Option<String>> {
+        match Self::with_entry(username, keyring::Entry::get_password) {
+            Ok(password) => Ok(Some(password)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set(&self, username: &str, password: &str) -> Result<()> {
+        Self::with_entry(username, |entry| entry.set_password(password))?;
+        Ok(())
+    }
+}
+
+/// Password of `username` in the OS keychain.
+#[cfg(feature = "keyring")]
+pub fn get_password(username: &str) -> Result<Option<String>> {
+    KeyringStore.get(username)
+}
+
+/// Save the password of `username` in the OS keychain.
+#[cfg(feature = "keyring")]
+pub fn set_password(username: &str, password: &str) -> Result<()> {
+    KeyringStore.set(username, password)
+}
+
+/// Look up in `store` the password of every account that has none yet.
+///
+/// A store error (locked keychain, no secret service...) leaves the account
+/// without password, as if the entry were absent.
+pub fn fill_missing_passwords(accounts: &mut [Account], store: &dyn CredentialStore) {
+    for account in accounts.iter_mut().filter(|a| a.password.is_none()) {
+        match store.get(&account.username) {
+            Ok(password) => account.password = password,
+            Err(e) => eprintln!("Keychain lookup failed for {}: {}", account.name, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    /// In-memory stand-in for the OS keychain.
+    #[derive(Default)]
+    struct MemoryStore(RefCell<HashMap<String, String>>);
+
+    impl CredentialStore for MemoryStore {
+        fn get(&self, username: &str) -> Result<Option<String>> {
+            Ok(self.0.borrow().get(username).cloned())
+        }
+
+        fn set(&self, username: &str, password: &str) -> Result<()> {
+            self.0.borrow_mut().insert(username.to_string(), password.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_fill_missing_passwords() {
+        let temp = TempDir::new().unwrap();
+        let accounts_path = temp.path().join("accounts.yaml");
+        std::fs::write(
+            &accounts_path,
+            "accounts:\n  - name: KeychainA\n    server: imap.example.com\n    port: 993\n    username: a@example.com\n  - name: KeychainB\n    server: imap.example.com\n    port: 993\n    username: b@example.com\n",
+        )
+        .unwrap();
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(&settings_path, "export_base_dir: /tmp/emails\n").unwrap();
+
+        let store = MemoryStore::default();
+        store.set("a@example.com", "from-keychain-a").unwrap();
+        store.set("b@example.com", "from-keychain-b").unwrap();
+        let env_var = |var: &str| (var == "KEYCHAINA_PASSWORD").then(|| "from-env".to_string());
+        let accounts = Config::load_with_credentials(&accounts_path, &settings_path, &env_var, Some(&store))
+            .unwrap()
+            .accounts;
+
+        // The environment wins; the keychain fills the gaps
+        assert_eq!(accounts[0].password.as_deref(), Some("from-env"));
+        assert_eq!(accounts[1].password.as_deref(), Some("from-keychain-b"));
+
+        // No entry: still no password
+        let mut accounts = vec![Account { password: None, ..accounts[1].clone() }];
+        fill_missing_passwords(&mut accounts, &MemoryStore::default());
+        assert!(accounts[0].password.is_none());
+    }
+}
@@ -0,0 +1,155 @@
+//! Per-account ledger of exported Message-Id values.
+//!
+//! Stored one id per line at the root of the account's export directory, and
+//! appended to as emails are exported, like the export index.
+//! Looking a message up here is O(1), unlike the filename scan of
+//! `email_already_exported`, and survives renamed or moved files.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File name of the ledger inside the base export directory.
+pub const LEDGER_FILE_NAME: &str = ".exported_message_ids";
+
+/// Earlier ledger format: a JSON array, rewritten on every insert.
+/// Migrated to [`LEDGER_FILE_NAME`] when loaded.
+pub const LEGACY_LEDGER_FILE_NAME: &str = ".exported_message_ids.json";
+
+/// Set of Message-Id values already exported for an account.
+#[derive(Debug)]
+pub struct MessageIdLedger {
+    path: PathBuf,
+    ids: BTreeSet<String>,
+}
+
+impl MessageIdLedger {
+    /// Load the ledger stored in `base_export_directory` (empty if absent).
+    pub fn load(base_export_directory: &Path) -> Result<Self> {
+        let path = base_export_directory.join(LEDGER_FILE_NAME);
+
+        let ids = if path.exists() {
+            fs::read_to_string(&path)
+                .context("Failed to read Message-Id ledger")?
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(String::from)
+                .collect()
+        } else {
+            BTreeSet::new()
+        };
+
+        let mut ledger = MessageIdLedger { path, ids };
+
+        let legacy_path = base_export_directory.join(LEGACY_LEDGER_FILE_NAME);
+        if legacy_path.exists() {
+            let content =
+                fs::read_to_string(&legacy_path).context("Failed to read Message-Id ledger")?;
+            let legacy: BTreeSet<String> =
+                serde_json::from_str(&content).context("Failed to parse Message-Id ledger")?;
+            for id in &legacy {
+                ledger.insert(id)?;
+            }
+            fs::remove_file(&legacy_path).context("Failed to remove the old Message-Id ledger")?;
+        }
+
+        Ok(ledger)
+    }
+
+    /// Normalize a Message-Id header value (`<id@host>` → `id@host`).
+    pub fn normalize(message_id: &str) -> Option<String> {
+        let id = message_id
+            .trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .trim();
+
+        if id.is_empty() {
+            None
+        } else {
+            Some(id.to_string())
+        }
+    }
+
+    /// Check whether a (normalized) Message-Id was already exported.
+    pub fn contains(&self, message_id: &str) -> bool {
+        self.ids.contains(message_id)
+    }
+
+    /// Record a (normalized) Message-Id, appending it to the ledger file on disk.
+    pub fn insert(&mut self, message_id: &str) -> Result<()> {
+        if !self.ids.insert(message_id.to_string()) {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("Failed to open Message-Id ledger")?;
+        writeln!(file, "{}", message_id)?;
+
+        Ok(())
+    }
+
+    /// Number of recorded Message-Id values.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Whether the ledger is empty.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_ledger_load_and_save() {
+        let temp = TempDir::new().unwrap();
+
+        let mut ledger = MessageIdLedger::load(temp.path()).unwrap();
+        assert!(ledger.is_empty());
+
+        let id = MessageIdLedger::normalize(" <abc@example.com> ").unwrap();
+        assert_eq!(id, "abc@example.com");
+        ledger.insert(&id).unwrap();
+        ledger.insert(&id).unwrap();
+
+        let reloaded = MessageIdLedger::load(temp.path()).unwrap();
+        assert!(reloaded.contains("abc@example.com"));
+        assert_eq!(reloaded.len(), 1);
+
+        assert_eq!(MessageIdLedger::normalize("<>"), None);
+    }
+
+    #[test]
+    fn test_ledger_migrates_json_array() {
+        let temp = TempDir::new().unwrap();
+        fs::write(
+            temp.path().join(LEGACY_LEDGER_FILE_NAME),
+            r#"["a@example.com", "b@example.com"]"#,
+        )
+        .unwrap();
+
+        let mut ledger = MessageIdLedger::load(temp.path()).unwrap();
+        assert_eq!(ledger.len(), 2);
+        assert!(!temp.path().join(LEGACY_LEDGER_FILE_NAME).exists());
+        ledger.insert("c@example.com").unwrap();
+
+        let content = fs::read_to_string(temp.path().join(LEDGER_FILE_NAME)).unwrap();
+        assert_eq!(content, "a@example.com\nb@example.com\nc@example.com\n");
+        assert_eq!(MessageIdLedger::load(temp.path()).unwrap().len(), 3);
+    }
+}
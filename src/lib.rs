@@ -1,6 +1,16 @@
+pub mod checkpoint;
 pub mod email_export;
+pub mod export_index;
 pub mod fix_yaml;
+pub mod importer;
+pub mod keychain;
+pub mod ledger;
+pub mod mbox;
+pub mod run_log;
+pub mod run_state;
+pub mod runner;
 pub mod sort_emails;
+pub mod threads;
 pub mod config;
 pub mod utils;
 pub mod thunderbird;  // [1] Import automatique depuis Thunderbird
@@ -1,12 +1,19 @@
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
+#[cfg(feature = "keyring")]
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 use email_to_markdown::config::{self, Config, SortConfig};
-use email_to_markdown::email_export::ImapExporter;
+use email_to_markdown::email_export::{self, ImapExporter};
 use email_to_markdown::fix_yaml;
-use email_to_markdown::sort_emails::EmailSorter;
+use email_to_markdown::network::ConcurrencyLimit;
+use email_to_markdown::runner;
+use email_to_markdown::sort_emails::{EmailSorter, TruncationSummarizer};
 use email_to_markdown::thunderbird;  // [1] Import Thunderbird
+use email_to_markdown::utils::Verbosity;
 
 #[cfg(feature = "tray")]
 use email_to_markdown::tray;
@@ -72,6 +79,26 @@ enum Commands {
         /// Enable debug mode (verbose IMAP output)
         #[arg(short, long)]
         debug: bool,
+
+        /// Import messages from an mbox file instead of IMAP (requires a single --account)
+        #[arg(long, value_name = "FILE")]
+        mbox: Option<PathBuf>,
+
+        /// Stop after exporting N messages per account (overrides max_messages)
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// With --mbox: write one file per conversation instead of one per message
+        #[arg(long, requires = "mbox")]
+        by_thread: bool,
+
+        /// Export into a temporary directory and list the files a run would write
+        #[arg(long, conflicts_with_all = ["by_thread", "delete_after_export"])]
+        dry_run: bool,
+
+        /// Only fetch mail received since the last complete export of each account
+        #[arg(long, conflicts_with = "mbox")]
+        since_last_run: bool,
     },
 
     /// Fix malformed YAML in email files
@@ -86,6 +113,38 @@ enum Commands {
         /// Actually fix the files (default is dry-run)
         #[arg(long)]
         apply: bool,
+
+        /// Save each file to `<file>.bak` before rewriting it
+        #[arg(long)]
+        backup: bool,
+
+        /// Revert fixed files from their `.bak` backups
+        #[arg(long)]
+        restore: bool,
+
+        /// Print the frontmatter changes as a unified diff, without applying them
+        #[arg(long, conflicts_with_all = ["apply", "restore"])]
+        diff: bool,
+
+        /// Keep the first occurrence of a repeated frontmatter key (default: the last)
+        #[arg(long)]
+        keep_first_key: bool,
+
+        /// Only print errors and the summary
+        #[arg(short, long)]
+        quiet: bool,
+    },
+
+    /// List email files whose frontmatter is not valid YAML, without changing them
+    Validate {
+        /// Directory (or single file) to check
+        directory: PathBuf,
+    },
+
+    /// List exported emails whose body no longer matches its body_hash, e.g. after a copy
+    Verify {
+        /// Export directory to check
+        directory: PathBuf,
     },
 
     /// Sort emails into categories (delete/summarize/keep)
@@ -105,23 +164,62 @@ enum Commands {
         #[arg(short, long, default_value = "sort_report.json")]
         report: String,
 
+        /// Also write one JSON object per email to this file (JSON Lines)
+        #[arg(long, value_name = "FILE")]
+        jsonl: Option<PathBuf>,
+
         /// Show detailed output
         #[arg(short, long)]
         verbose: bool,
 
+        /// Only print errors and the summary
+        #[arg(short, long, conflicts_with = "verbose")]
+        quiet: bool,
+
         /// Simulate sorting without creating reports
         #[arg(long)]
         dry_run: bool,
 
+        /// Number of threads used to analyze files (default: one per CPU)
+        #[arg(long)]
+        threads: Option<usize>,
+
         /// List available accounts from accounts.yaml
         #[arg(long)]
         list_accounts: bool,
 
+        /// Write a <name>.summary.md next to each email of the summarize category
+        #[arg(long)]
+        summarize: bool,
+
         /// Create a default configuration file
         #[arg(long)]
         create_config: bool,
     },
 
+    /// Show the resolved settings of an account and where each one comes from
+    Config {
+        /// Account name
+        #[arg(short, long)]
+        account: String,
+
+        /// Path to accounts.yaml (default: platform config dir)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+    },
+
+    /// Save an account password in the OS keychain, typed without echo or piped on stdin (requires --features keyring)
+    #[cfg(feature = "keyring")]
+    SetPassword {
+        /// Account name
+        #[arg(short, long)]
+        account: String,
+
+        /// Path to accounts.yaml (default: platform config dir)
+        #[arg(short, long)]
+        config: Option<PathBuf>,
+    },
+
     /// Run as system tray application (requires --features tray)
     #[cfg(feature = "tray")]
     Tray,
@@ -191,8 +289,21 @@ fn main() -> Result<()> {
             println!("Using Thunderbird profile: {} ({})", tb_profile.name, tb_profile.path.display());
 
             // Extract accounts
-            let accounts = thunderbird::extract_accounts(&tb_profile)
+            let import = thunderbird::extract_import(&tb_profile)
                 .context("Failed to extract accounts from Thunderbird")?;
+            let accounts = import.accounts;
+
+            if !import.skipped.is_empty() {
+                println!("{} account(s) skipped (not IMAP):", import.skipped.len());
+                for server in &import.skipped {
+                    println!(
+                        "   - {} ({}, {})",
+                        server.name,
+                        server.server_type,
+                        server.hostname.as_deref().unwrap_or("no hostname")
+                    );
+                }
+            }
 
             if accounts.is_empty() {
                 println!("No IMAP accounts found in Thunderbird profile");
@@ -216,10 +327,21 @@ fn main() -> Result<()> {
             std::fs::write(&output, &yaml_content)?;
             println!("\nGenerated: {}", output.display());
 
+            let settings_path = config::settings_path();
+            let renamed = thunderbird::write_folder_names(&accounts, &settings_path)?;
+            if renamed > 0 {
+                println!(
+                    "{} export folder(s) renamed to stay distinct, see {}",
+                    renamed,
+                    settings_path.display()
+                );
+            }
+
             // Generate .env template if requested
             if generate_env {
                 let env_template_path = output.parent().unwrap_or(Path::new(".")).join(".env.template");
-                let env_content = thunderbird::generate_env_template(&accounts);
+                let saved_servers = thunderbird::list_saved_credentials(&tb_profile).unwrap_or_default();
+                let env_content = thunderbird::generate_env_template(&accounts, &saved_servers);
                 std::fs::write(&env_template_path, &env_content)?;
                 println!("Generated: {}", env_template_path.display());
                 println!("\nRemember to:");
@@ -270,8 +392,71 @@ fn main() -> Result<()> {
             delete_after_export,
             config,
             debug,
+            mbox,
+            limit,
+            by_thread,
+            dry_run,
+            since_last_run,
         } => {
             let config_path = config.unwrap_or_else(config::accounts_yaml_path);
+
+            if let Some(mbox_path) = mbox {
+                let account_name = match account.as_deref() {
+                    Some(name) if !name.contains(',') => name.trim(),
+                    _ => anyhow::bail!("--mbox requires a single --account for the export settings"),
+                };
+
+                if dry_run {
+                    let scratch_dir = runner::preview_scratch_dir(account_name)?;
+                    let files = runner::run_mbox_preview(
+                        &mbox_path,
+                        account_name,
+                        &config_path,
+                        &config::settings_path(),
+                        limit,
+                        &scratch_dir,
+                    )?;
+                    print_preview(&files, &scratch_dir);
+                    return Ok(());
+                }
+
+                if by_thread {
+                    let (files, stats) = runner::run_mbox_thread_export(
+                        &mbox_path,
+                        account_name,
+                        &config_path,
+                        &config::settings_path(),
+                    )?;
+                    println!(
+                        "\nWrote {} conversation file(s) from {}: {} exported, {} skipped, {} errors",
+                        files.len(),
+                        mbox_path.display(),
+                        stats.exported,
+                        stats.skipped,
+                        stats.errors
+                    );
+                    return Ok(());
+                }
+
+                let stats = runner::run_mbox_export(
+                    &mbox_path,
+                    account_name,
+                    &config_path,
+                    &config::settings_path(),
+                    limit,
+                )?;
+                let outcome = if runner::interrupt_flag().load(Ordering::SeqCst) { "interrupted" } else { "completed" };
+                println!(
+                    "\nImport {} from {}: {} exported, {} skipped, {} errors",
+                    outcome,
+                    mbox_path.display(),
+                    stats.exported,
+                    stats.skipped,
+                    stats.errors
+                );
+                return Ok(());
+            }
+
             let config = Config::load(&config_path)
                 .context("Failed to load configuration")?;
 
@@ -323,20 +508,46 @@ fn main() -> Result<()> {
 
             println!("Exporting {} account(s)", accounts_to_export.len());
 
-            for mut account in accounts_to_export {
+            let max_concurrency = config::Settings::load(&config::settings_path())
+                .unwrap_or_default()
+                .max_concurrency
+                .unwrap_or(1);
+            let fetch_limit = Arc::new(ConcurrencyLimit::new(max_concurrency));
+            let stop = runner::reset_interrupt_flag();
+            let export_one = |mut account: config::Account| -> Result<()> {
+                if stop.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
                 println!("\nProcessing account: {} -> {}", account.name, account.export_directory);
 
-                if account.password.is_none() {
+                if !account.has_credentials() {
                     println!(
-                        "Error for {}: No password found. Check your .env file.",
+                        "Error for {}: No password or OAuth2 token found. Check your .env file.",
                         account.name
                     );
-                    continue;
+                    return Ok(());
                 }
 
                 account.delete_after_export = delete_after_export || account.delete_after_export;
+                account.max_messages = limit.or(account.max_messages);
 
-                let mut exporter = ImapExporter::new(account.clone(), debug);
+                let scratch_dir = if dry_run {
+                    let dir = runner::preview_scratch_dir(&account.name)?;
+                    account = runner::prepare_preview(&account, &dir)?;
+                    Some(dir)
+                } else {
+                    None
+                };
+
+                let mut exporter = ImapExporter::new(account.clone(), debug)
+                    .with_stop_flag(stop.clone())
+                    .with_fetch_limit(fetch_limit.clone());
+                if dry_run {
+                    exporter = exporter.without_checkpoint();
+                }
+                if since_last_run {
+                    exporter = exporter.since_last_run();
+                }
 
                 match exporter.connect() {
                     Ok(_) => {
@@ -346,10 +557,14 @@ fn main() -> Result<()> {
                                 let total_skipped: usize = results.values().map(|s| s.skipped).sum();
                                 let total_errors: usize = results.values().map(|s| s.errors).sum();
 
+                                let outcome = if stop.load(Ordering::SeqCst) { "interrupted" } else { "completed" };
                                 println!(
-                                    "\nExport completed for {}: {} exported, {} skipped, {} errors",
-                                    account.name, total_exported, total_skipped, total_errors
+                                    "\nExport {} for {}: {} exported, {} skipped, {} errors",
+                                    outcome, account.name, total_exported, total_skipped, total_errors
                                 );
+                                if let Some(dir) = &scratch_dir {
+                                    print_preview(&runner::preview_files(dir)?, dir);
+                                }
                             }
                             Err(e) => {
                                 println!("Export failed for {}: {}", account.name, e);
@@ -364,6 +579,25 @@ fn main() -> Result<()> {
                         println!("Connection failed for {}: {}", account.name, e);
                     }
                 }
+                Ok(())
+            };
+
+            // Fetches share `fetch_limit`; beyond it the accounts run side by side
+            if max_concurrency > 1 {
+                let export_one = &export_one;
+                std::thread::scope(|scope| {
+                    let handles: Vec<_> = accounts_to_export
+                        .into_iter()
+                        .map(|account| scope.spawn(move || export_one(account)))
+                        .collect();
+                    handles
+                        .into_iter()
+                        .try_for_each(|handle| handle.join().expect("export thread panicked"))
+                })?;
+            } else {
+                for account in accounts_to_export {
+                    export_one(account)?;
+                }
             }
         }
 
@@ -371,29 +605,92 @@ fn main() -> Result<()> {
             directory,
             dry_run,
             apply,
+            backup,
+            restore,
+            diff,
+            keep_first_key,
+            quiet,
         } => {
+            let verbosity = if quiet { Verbosity::Quiet } else { Verbosity::Normal };
+            let policy = if keep_first_key {
+                fix_yaml::DuplicateKeyPolicy::KeepFirst
+            } else {
+                fix_yaml::DuplicateKeyPolicy::KeepLast
+            };
+
             if !directory.exists() {
                 println!("Directory not found: {}", directory.display());
                 return Ok(());
             }
 
+            if restore {
+                let restored = fix_yaml::restore_backups(&directory, verbosity)?;
+                println!("Restored {} file(s) from backups", restored);
+                return Ok(());
+            }
+
+            if diff {
+                let diffs = fix_yaml::preview_fixes(&directory, policy)?;
+                for (_, diff) in &diffs {
+                    println!("{}", diff);
+                }
+                println!("{} file(s) would be fixed; use --apply to fix them", diffs.len());
+                return Ok(());
+            }
+
             println!("Scanning for malformed email files in: {}", directory.display());
 
             // Default to dry-run unless --apply is specified
             let is_dry_run = !apply || dry_run;
 
-            let stats = fix_yaml::scan_and_fix_directory(&directory, is_dry_run)?;
+            let stats = fix_yaml::scan_and_fix_directory(&directory, is_dry_run, backup, policy, verbosity)?;
             fix_yaml::print_summary(&stats, is_dry_run);
         }
 
+        Commands::Validate { directory } => {
+            if !directory.exists() {
+                println!("Directory not found: {}", directory.display());
+                return Ok(());
+            }
+
+            let problems = fix_yaml::validate_directory(&directory);
+            for (path, problem) in &problems {
+                println!("{}: {}", path.display(), problem);
+            }
+            if !problems.is_empty() {
+                anyhow::bail!("{} file(s) with invalid frontmatter", problems.len());
+            }
+            println!("All frontmatter is valid");
+        }
+
+        Commands::Verify { directory } => {
+            if !directory.exists() {
+                println!("Directory not found: {}", directory.display());
+                return Ok(());
+            }
+
+            let mismatches = email_export::verify_export(&directory)?;
+            for (path, expected, actual) in &mismatches {
+                println!("{}: body_hash {} expected, {} found", path.display(), expected, actual);
+            }
+            if !mismatches.is_empty() {
+                anyhow::bail!("{} file(s) changed since export", mismatches.len());
+            }
+            println!("All body hashes match");
+        }
+
         Commands::Sort {
             directory,
             account,
             config,
             report,
+            jsonl,
             verbose,
+            quiet,
             dry_run,
+            threads,
             list_accounts,
+            summarize,
             create_config,
         } => {
             if create_config {
@@ -443,10 +740,20 @@ fn main() -> Result<()> {
             // Load sort config
             let sort_config = SortConfig::load(&config.unwrap_or_else(config::sort_config_path))?;
 
-            let mut sorter = EmailSorter::new(sort_directory, sort_config);
+            let verbosity = match (quiet, verbose) {
+                (true, _) => Verbosity::Quiet,
+                (_, true) => Verbosity::Verbose,
+                _ => Verbosity::Normal,
+            };
+            let mut sorter = EmailSorter::new(sort_directory, sort_config).with_verbosity(verbosity);
+            if let Some(threads) = threads {
+                sorter = sorter.with_threads(threads);
+            }
 
             if dry_run {
                 println!("DRY RUN MODE: Analyzing emails without creating reports");
+            } else if let Some(jsonl) = &jsonl {
+                sorter.write_jsonl(jsonl)?;
             }
 
             sorter.sort_emails()?;
@@ -459,6 +766,11 @@ fn main() -> Result<()> {
                 println!("DRY RUN: Would create report at: {}", report);
             }
 
+            if summarize && !dry_run {
+                let written = sorter.write_summaries(&TruncationSummarizer::default())?;
+                println!("Summaries written: {}", written);
+            }
+
             sorter.print_summary();
 
             if verbose {
@@ -483,6 +795,36 @@ fn main() -> Result<()> {
             }
         }
 
+        Commands::Config { account, config } => {
+            let config_path = config.unwrap_or_else(config::accounts_yaml_path);
+            let description = Config::describe(&config_path, &config::settings_path(), &account)
+                .context("Failed to describe account")?;
+            println!("{}", description);
+        }
+
+        #[cfg(feature = "keyring")]
+        Commands::SetPassword { account, config } => {
+            let config_path = config.unwrap_or_else(config::accounts_yaml_path);
+            let account = runner::load_account(&account, &config_path, &config::settings_path())?;
+
+            let prompt = format!("Password for {} ({}): ", account.name, account.username);
+            let password = if std::io::stdin().is_terminal() {
+                rpassword::prompt_password(prompt)?
+            } else {
+                let mut password = String::new();
+                std::io::stdin().read_line(&mut password)?;
+                password
+            };
+            let password = password.trim_end_matches(['\r', '\n']);
+            if password.is_empty() {
+                anyhow::bail!("Empty password, nothing saved");
+            }
+
+            email_to_markdown::keychain::set_password(&account.username, password)
+                .context("Failed to save the password in the keychain")?;
+            println!("Saved in the keychain (service '{}')", email_to_markdown::keychain::SERVICE);
+        }
+
         #[cfg(feature = "tray")]
         Commands::Tray => {
             println!("Starting system tray application...");
@@ -492,3 +834,12 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// List the files written by a `--dry-run` export.
+fn print_preview(files: &[(PathBuf, u64)], scratch_dir: &Path) {
+    println!("\nDry run: {} file(s) would be written", files.len());
+    for (path, size) in files {
+        println!("  {} ({} bytes)", path.display(), size);
+    }
+    println!("Preview kept in {}", scratch_dir.display());
+}
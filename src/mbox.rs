@@ -0,0 +1,143 @@
+//! Reading messages from mbox files (e.g. Gmail Takeout exports).
+//!
+//! Messages are separated by `From ` lines at the start of the file or
+//! after a blank line. Body lines escaped as `>From ` (mboxrd) are
+//! unescaped by removing one `>`.
+
+use crate::runner::MessageSource;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Read an mbox file and return its raw messages, ready for `export_to_markdown`.
+pub fn read_mbox(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let content = fs::read(path).with_context(|| format!("Failed to read mbox {}", path.display()))?;
+    Ok(split_mbox(&content))
+}
+
+/// Split mbox content into raw messages.
+pub fn split_mbox(content: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    let mut previous_blank = true;
+
+    for line in content.split_inclusive(|&b| b == b'\n') {
+        if previous_blank && line.starts_with(b"From ") {
+            if let Some(message) = current.take() {
+                messages.push(trim_separator_blank_line(message));
+            }
+            current = Some(Vec::new());
+            previous_blank = false;
+            continue;
+        }
+
+        previous_blank = line == b"\n" || line == b"\r\n";
+
+        if let Some(message) = current.as_mut() {
+            message.extend_from_slice(unescape_from_line(line));
+        }
+    }
+
+    if let Some(message) = current {
+        messages.push(trim_separator_blank_line(message));
+    }
+
+    messages
+}
+
+/// `>From ` → `From `, `>>From ` → `>From `, other lines unchanged.
+fn unescape_from_line(line: &[u8]) -> &[u8] {
+    let quotes = line.iter().take_while(|&&b| b == b'>').count();
+    if quotes > 0 && line[quotes..].starts_with(b"From ") {
+        &line[1..]
+    } else {
+        line
+    }
+}
+
+/// Drop the blank line that precedes the next `From ` separator.
+fn trim_separator_blank_line(mut message: Vec<u8>) -> Vec<u8> {
+    if message.ends_with(b"\r\n\r\n") {
+        message.truncate(message.len() - 2);
+    } else if message.ends_with(b"\n\n") {
+        message.truncate(message.len() - 1);
+    }
+    message
+}
+
+/// An mbox file exposed as a single folder named after the file.
+pub struct MboxSource {
+    path: PathBuf,
+    folder: String,
+}
+
+impl MboxSource {
+    pub fn new(path: &Path) -> Self {
+        let folder = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "mbox".to_string());
+
+        MboxSource {
+            path: path.to_path_buf(),
+            folder,
+        }
+    }
+}
+
+impl MessageSource for MboxSource {
+    fn folders(&mut self) -> Result<Vec<String>> {
+        Ok(vec![self.folder.clone()])
+    }
+
+    fn messages(&mut self, _folder: &str) -> Result<Vec<Vec<u8>>> {
+        read_mbox(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const FIXTURE: &str = "From alice@example.com Mon Jan 15 10:00:00 2024\n\
+From: alice@example.com\n\
+To: bob@example.com\n\
+Subject: First\n\
+\n\
+Hello Bob.\n\
+>From the archives: nothing.\n\
+>>From stays quoted once.\n\
+\n\
+From bob@example.com Tue Jan 16 11:00:00 2024\n\
+From: bob@example.com\n\
+To: alice@example.com\n\
+Subject: Second\n\
+\n\
+Hi Alice,\n\
+From now on, reply inline.\n";
+
+    #[test]
+    fn test_read_mbox() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("Takeout.mbox");
+        fs::write(&path, FIXTURE).unwrap();
+
+        let messages = read_mbox(&path).unwrap();
+        assert_eq!(messages.len(), 2);
+
+        let first = String::from_utf8(messages[0].clone()).unwrap();
+        assert!(first.starts_with("From: alice@example.com\n"));
+        assert!(first.ends_with(
+            "Hello Bob.\nFrom the archives: nothing.\n>From stays quoted once.\n"
+        ));
+
+        // "From " inside a body without a preceding blank line is not a separator
+        let second = String::from_utf8(messages[1].clone()).unwrap();
+        assert!(second.contains("Subject: Second\n"));
+        assert!(second.ends_with("Hi Alice,\nFrom now on, reply inline.\n"));
+
+        let mut source = MboxSource::new(&path);
+        assert_eq!(source.folders().unwrap(), vec!["Takeout"]);
+    }
+}
@@ -1,7 +1,10 @@
 // [4] Module pour la gestion reseau avec retry automatique
 // [5] Timeout configurable
 
-use std::time::Duration;
+use crate::utils::Verbosity;
+use std::io::IsTerminal;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
 use std::thread;
 
 /// Configuration for network operations
@@ -17,6 +20,16 @@ pub struct NetworkConfig {
     pub connect_timeout: Duration,
     /// Read timeout
     pub read_timeout: Duration,
+    /// Random spread applied to each retry delay (0.5 turns 4s into 2s..6s)
+    pub jitter_fraction: f64,
+    /// Maximum total time spent in `with_retry`, across all attempts and delays
+    pub total_deadline: Option<Duration>,
+    /// Minimum time between two message fetches, for servers that throttle
+    pub fetch_delay: Option<Duration>,
+    /// Maximum number of message fetches in flight at once (1: one at a time)
+    pub max_concurrency: usize,
+    /// `Quiet` hides the "retrying" notices; final failures are always printed
+    pub verbosity: Verbosity,
 }
 
 impl Default for NetworkConfig {
@@ -27,6 +40,11 @@ impl Default for NetworkConfig {
             max_retry_delay: Duration::from_secs(30),
             connect_timeout: Duration::from_secs(30),
             read_timeout: Duration::from_secs(60),
+            jitter_fraction: 0.0,
+            total_deadline: None,
+            fetch_delay: None,
+            max_concurrency: 1,
+            verbosity: Verbosity::default(),
         }
     }
 }
@@ -39,6 +57,8 @@ where
 {
     let mut attempts = 0;
     let mut delay = config.initial_retry_delay;
+    let mut rng = XorShift::from_time();
+    let started = Instant::now();
 
     loop {
         attempts += 1;
@@ -54,12 +74,27 @@ where
                     return Err(e);
                 }
 
-                eprintln!(
-                    "  {} failed (attempt {}/{}): {}. Retrying in {:?}...",
-                    operation_name, attempts, config.max_retries, e, delay
-                );
+                let sleep_for = apply_jitter(delay, config.jitter_fraction, &mut rng);
+
+                // [5] Give up now rather than sleeping past the total deadline
+                if let Some(deadline) = config.total_deadline {
+                    if started.elapsed() + sleep_for >= deadline {
+                        eprintln!(
+                            "  {} failed after {} attempts (deadline of {:?} reached): {}",
+                            operation_name, attempts, deadline, e
+                        );
+                        return Err(e);
+                    }
+                }
+
+                if config.verbosity.shows(Verbosity::Normal) {
+                    eprintln!(
+                        "  {} failed (attempt {}/{}): {}. Retrying in {:?}...",
+                        operation_name, attempts, config.max_retries, e, sleep_for
+                    );
+                }
 
-                thread::sleep(delay);
+                thread::sleep(sleep_for);
 
                 // Exponential backoff
                 delay = std::cmp::min(delay * 2, config.max_retry_delay);
@@ -68,24 +103,164 @@ where
     }
 }
 
+/// Spaces operations at least `delay` apart (e.g. IMAP fetches)
+pub struct Pacer {
+    delay: Option<Duration>,
+    last: Option<Instant>,
+}
+
+impl Pacer {
+    pub fn new(delay: Option<Duration>) -> Self {
+        Pacer { delay, last: None }
+    }
+
+    /// Sleep until `delay` has passed since the previous call; the first call never waits
+    pub fn wait(&mut self) {
+        if let (Some(delay), Some(last)) = (self.delay, self.last) {
+            let elapsed = last.elapsed();
+            if elapsed < delay {
+                thread::sleep(delay - elapsed);
+            }
+        }
+        self.last = Some(Instant::now());
+    }
+}
+
+/// Bounds how many operations (e.g. IMAP fetches) run at the same time.
+///
+/// Shared through an `Arc` between the workers hitting the same server.
+pub struct ConcurrencyLimit {
+    max: usize,
+    running: Mutex<usize>,
+    freed: Condvar,
+}
+
+impl ConcurrencyLimit {
+    /// A limit of `max` simultaneous operations; 0 is treated as 1.
+    pub fn new(max: usize) -> Self {
+        ConcurrencyLimit { max: max.max(1), running: Mutex::new(0), freed: Condvar::new() }
+    }
+
+    /// Block until fewer than `max` operations run; the slot is released
+    /// when the returned permit is dropped.
+    pub fn acquire(&self) -> ConcurrencyPermit<'_> {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        while *running >= self.max {
+            running = self.freed.wait(running).unwrap_or_else(|e| e.into_inner());
+        }
+        *running += 1;
+        ConcurrencyPermit { limit: self }
+    }
+}
+
+/// A running operation counted by a [`ConcurrencyLimit`].
+pub struct ConcurrencyPermit<'a> {
+    limit: &'a ConcurrencyLimit,
+}
+
+impl Drop for ConcurrencyPermit<'_> {
+    fn drop(&mut self) {
+        let mut running = self.limit.running.lock().unwrap_or_else(|e| e.into_inner());
+        *running -= 1;
+        self.limit.freed.notify_one();
+    }
+}
+
+/// Spread `delay` uniformly over `[delay * (1 - fraction), delay * (1 + fraction)]`
+fn apply_jitter(delay: Duration, fraction: f64, rng: &mut XorShift) -> Duration {
+    if fraction <= 0.0 {
+        return delay;
+    }
+    let fraction = fraction.min(1.0);
+    let factor = 1.0 - fraction + 2.0 * fraction * rng.next_f64();
+    delay.mul_f64(factor)
+}
+
+/// Minimal xorshift generator, good enough to de-synchronise retries
+struct XorShift(u64);
+
+impl XorShift {
+    fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        // The state must never be zero
+        XorShift(nanos | 1)
+    }
+
+    /// Next value in `[0, 1)`
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// How a progress indicator renders its output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// Redraw a single line in place with `\r` (terminals)
+    Interactive,
+    /// Print a new line at regular steps (files, pipes, logs)
+    Lines,
+}
+
+impl ProgressMode {
+    /// Pick `Interactive` when stderr is a terminal, `Lines` otherwise
+    pub fn detect() -> Self {
+        if std::io::stderr().is_terminal() {
+            ProgressMode::Interactive
+        } else {
+            ProgressMode::Lines
+        }
+    }
+}
+
+/// In `Lines` mode, report every 10% when the total is known...
+const LINES_PERCENT_STEP: usize = 10;
+/// ...or every 100 items when it is not
+const LINES_ITEM_STEP: usize = 100;
+
+/// In `Interactive` mode, back to the start of the line and erase it, so a
+/// shorter redraw leaves nothing of the previous one
+const REDRAW: &str = "\r\x1b[K";
+
 /// [3] Simple progress indicator for terminal
 pub struct ProgressIndicator {
     total: usize,
     current: usize,
     label: String,
     show_percentage: bool,
+    mode: ProgressMode,
+    last_reported_step: Option<usize>,
+    start: Instant,
 }
 
 impl ProgressIndicator {
     pub fn new(label: &str, total: usize) -> Self {
+        Self::with_mode(label, total, ProgressMode::detect())
+    }
+
+    /// Create a progress indicator with an explicit output mode
+    pub fn with_mode(label: &str, total: usize, mode: ProgressMode) -> Self {
         ProgressIndicator {
             total,
             current: 0,
             label: label.to_string(),
             show_percentage: total > 0,
+            mode,
+            last_reported_step: None,
+            start: Instant::now(),
         }
     }
 
+    /// Output mode in use
+    pub fn mode(&self) -> ProgressMode {
+        self.mode
+    }
+
     /// Update progress and print status
     pub fn update(&mut self, current: usize) {
         self.current = current;
@@ -98,8 +273,42 @@ impl ProgressIndicator {
         self.print();
     }
 
+    /// Items processed per second since creation
+    pub fn rate(&self) -> f64 {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.current as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Estimated time remaining, if the total is known and progress was made
+    pub fn eta(&self) -> Option<Duration> {
+        let rate = self.rate();
+        if self.total == 0 || rate <= 0.0 {
+            return None;
+        }
+        let remaining = self.total.saturating_sub(self.current) as f64;
+        Some(Duration::from_secs_f64(remaining / rate))
+    }
+
+    /// Rate and ETA suffix appended to the rendered progress
+    fn timing_suffix(&self) -> String {
+        let rate = format!("{:.1}/s", self.rate());
+        match self.eta() {
+            Some(eta) if self.show_percentage => format!(" {} ETA {}", rate, format_duration(eta)),
+            _ => format!(" {}", rate),
+        }
+    }
+
     /// Print current progress
-    fn print(&self) {
+    fn print(&mut self) {
+        if self.mode == ProgressMode::Lines {
+            self.print_line();
+            return;
+        }
+
         if self.show_percentage && self.total > 0 {
             let percentage = (self.current as f64 / self.total as f64 * 100.0) as u32;
             let bar_width = 30;
@@ -107,40 +316,87 @@ impl ProgressIndicator {
             let empty = bar_width - filled;
 
             eprint!(
-                "\r  {} [{}{}] {}/{} ({}%)",
+                "{}  {} [{}{}] {}/{} ({}%){}",
+                REDRAW,
                 self.label,
                 "=".repeat(filled),
                 " ".repeat(empty),
                 self.current,
                 self.total,
-                percentage
+                percentage,
+                self.timing_suffix()
             );
         } else {
-            eprint!("\r  {} {}", self.label, self.current);
+            eprint!("{}  {} {}{}", REDRAW, self.label, self.current, self.timing_suffix());
+        }
+    }
+
+    /// In `Lines` mode, print a full line only when a new step is reached
+    fn print_line(&mut self) {
+        let step = if self.show_percentage && self.total > 0 {
+            self.current * 100 / self.total / LINES_PERCENT_STEP
+        } else {
+            self.current / LINES_ITEM_STEP
+        };
+
+        if self.last_reported_step == Some(step) {
+            return;
+        }
+        self.last_reported_step = Some(step);
+
+        if self.show_percentage && self.total > 0 {
+            eprintln!(
+                "  {} {}/{} ({}%){}",
+                self.label,
+                self.current,
+                self.total,
+                self.current * 100 / self.total,
+                self.timing_suffix()
+            );
+        } else {
+            eprintln!("  {} {}{}", self.label, self.current, self.timing_suffix());
         }
     }
 
     /// Finish and print newline
     pub fn finish(&self) {
+        let elapsed = format_duration(self.start.elapsed());
+
+        if self.mode == ProgressMode::Lines {
+            eprintln!("  {} {} - Done in {}", self.label, self.current, elapsed);
+            return;
+        }
+
         if self.show_percentage && self.total > 0 {
             eprintln!(
-                "\r  {} [{}] {}/{} (100%)",
+                "{}  {} [{}] {}/{} (100%) in {}",
+                REDRAW,
                 self.label,
                 "=".repeat(30),
                 self.total,
-                self.total
+                self.total,
+                elapsed
             );
         } else {
-            eprintln!("\r  {} {} - Done", self.label, self.current);
+            eprintln!("{}  {} {} - Done in {}", REDRAW, self.label, self.current, elapsed);
         }
     }
 
     /// Finish with custom message
     pub fn finish_with_message(&self, msg: &str) {
-        eprintln!("\r  {} - {}", self.label, msg);
+        match self.mode {
+            ProgressMode::Interactive => eprintln!("{}  {} - {}", REDRAW, self.label, msg),
+            ProgressMode::Lines => eprintln!("  {} - {}", self.label, msg),
+        }
     }
 }
 
+/// Format a duration as `HH:MM:SS`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+}
+
 /// [3] Spinner for operations with unknown duration
 pub struct Spinner {
     frames: Vec<char>,
@@ -183,6 +439,7 @@ mod tests {
         let config = NetworkConfig::default();
         assert_eq!(config.max_retries, 3);
         assert_eq!(config.connect_timeout, Duration::from_secs(30));
+        assert_eq!(config.max_concurrency, 1);
     }
 
     #[test]
@@ -192,6 +449,56 @@ mod tests {
         assert_eq!(progress.current, 5);
     }
 
+    #[test]
+    fn test_progress_lines_mode_reports_by_step() {
+        let mut progress = ProgressIndicator::with_mode("Test", 100, ProgressMode::Lines);
+        assert_eq!(progress.mode(), ProgressMode::Lines);
+
+        progress.update(5);
+        assert_eq!(progress.last_reported_step, Some(0));
+        progress.update(9);
+        assert_eq!(progress.last_reported_step, Some(0));
+        progress.update(25);
+        assert_eq!(progress.last_reported_step, Some(2));
+
+        let mut unknown = ProgressIndicator::with_mode("Test", 0, ProgressMode::Lines);
+        unknown.update(250);
+        assert_eq!(unknown.last_reported_step, Some(2));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(133)), "00:02:13");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "01:02:05");
+    }
+
+    #[test]
+    fn test_progress_eta() {
+        let mut progress = ProgressIndicator::with_mode("Test", 10, ProgressMode::Lines);
+        assert!(progress.eta().is_none());
+
+        progress.start -= Duration::from_secs(10);
+        progress.update(5);
+        let eta = progress.eta().unwrap();
+        assert!(eta.as_secs() >= 9 && eta.as_secs() <= 10);
+
+        let mut unknown = ProgressIndicator::with_mode("Test", 0, ProgressMode::Lines);
+        unknown.update(3);
+        assert!(unknown.eta().is_none());
+    }
+
+    #[test]
+    fn test_apply_jitter_bounds() {
+        let mut rng = XorShift::from_time();
+        let delay = Duration::from_secs(4);
+
+        assert_eq!(apply_jitter(delay, 0.0, &mut rng), delay);
+        for _ in 0..100 {
+            let jittered = apply_jitter(delay, 0.5, &mut rng);
+            assert!(jittered >= Duration::from_secs(2) && jittered <= Duration::from_secs(6));
+        }
+    }
+
     #[test]
     fn test_with_retry_success() {
         let config = NetworkConfig::default();
@@ -200,6 +507,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_with_retry_failure() {
         let mut config = NetworkConfig::default();
         config.max_retries = 2;
@@ -216,10 +524,31 @@ mod tests {
     }
 
     #[test]
+    fn test_with_retry_total_deadline() {
+        let config = NetworkConfig {
+            max_retries: 10,
+            initial_retry_delay: Duration::from_millis(20),
+            total_deadline: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+
+        let mut attempts = 0;
+        let result: Result<i32, &str> = with_retry(&config, "test", || {
+            attempts += 1;
+            Err("always fails")
+        });
+
+        assert!(result.is_err());
+        assert!(attempts < config.max_retries);
+    }
+
+    #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_with_retry_eventual_success() {
         let mut config = NetworkConfig::default();
         config.max_retries = 3;
         config.initial_retry_delay = Duration::from_millis(10);
+        config.verbosity = Verbosity::Quiet;
 
         let mut attempts = 0;
         let result: Result<i32, &str> = with_retry(&config, "test", || {
@@ -234,4 +563,52 @@ mod tests {
         assert_eq!(result.unwrap(), 42);
         assert_eq!(attempts, 2);
     }
+
+    #[test]
+    fn test_concurrency_limit_bounds_simultaneous_fetches() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // Counting mock: records the peak number of fetches in flight
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let fetch = || {
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(10));
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+        };
+
+        for max in [1, 3] {
+            peak.store(0, Ordering::SeqCst);
+            let limit = ConcurrencyLimit::new(max);
+            thread::scope(|scope| {
+                for _ in 0..8 {
+                    scope.spawn(|| {
+                        let _permit = limit.acquire();
+                        fetch();
+                    });
+                }
+            });
+            assert!(peak.load(Ordering::SeqCst) <= max, "max {}", max);
+        }
+        assert_eq!(ConcurrencyLimit::new(0).max, 1);
+    }
+
+    #[test]
+    fn test_pacer_honors_delay() {
+        let started = Instant::now();
+        let mut pacer = Pacer::new(Some(Duration::from_millis(20)));
+        for _ in 0..4 {
+            pacer.wait();
+        }
+        // Three gaps after the first, immediate call
+        assert!(started.elapsed() >= Duration::from_millis(60));
+
+        let started = Instant::now();
+        let mut pacer = Pacer::new(None);
+        for _ in 0..4 {
+            pacer.wait();
+        }
+        assert!(started.elapsed() < Duration::from_millis(20));
+    }
 }
@@ -0,0 +1,112 @@
+//! Append-only, timestamped log files for export and sort runs.
+//!
+//! The tray hides stdout, so each run also writes one line per message
+//! (exported, skipped or errored, with the reason) into
+//! `app_config_dir/logs/<kind>_<timestamp>.log`.
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Outcome recorded for a log line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStatus {
+    Info,
+    Exported,
+    Skipped,
+    Error,
+}
+
+impl LogStatus {
+    fn label(self) -> &'static str {
+        match self {
+            LogStatus::Info => "INFO",
+            LogStatus::Exported => "EXPORTED",
+            LogStatus::Skipped => "SKIPPED",
+            LogStatus::Error => "ERROR",
+        }
+    }
+}
+
+/// A log file opened for appending.
+#[derive(Debug)]
+pub struct RunLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl RunLog {
+    /// Create a new log for a run of `kind` (e.g. "export", "sort") in the default logs directory.
+    pub fn create(kind: &str) -> Result<Self> {
+        Self::create_in(&crate::config::logs_dir(), kind)
+    }
+
+    /// Create a new log for a run of `kind` inside `dir`.
+    pub fn create_in(dir: &Path, kind: &str) -> Result<Self> {
+        fs::create_dir_all(dir).context("Failed to create logs directory")?;
+
+        let path = dir.join(format!(
+            "{}_{}.log",
+            kind,
+            Local::now().format("%Y%m%d_%H%M%S")
+        ));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .context("Failed to open log file")?;
+
+        Ok(RunLog { path, file })
+    }
+
+    /// Path of the log file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append one line. Logging is best-effort and never fails the run.
+    pub fn write(&self, status: LogStatus, message: &str) {
+        let line = format!(
+            "{} {:<8} {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S"),
+            status.label(),
+            message
+        );
+        let _ = (&self.file).write_all(line.as_bytes());
+    }
+
+    /// Most recently modified `.log` file in `dir`, if any.
+    pub fn latest_in(dir: &Path) -> Option<PathBuf> {
+        fs::read_dir(dir)
+            .ok()?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "log"))
+            .max_by_key(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_run_log_appends_lines() {
+        let temp = TempDir::new().unwrap();
+
+        let log = RunLog::create_in(temp.path(), "export").unwrap();
+        log.write(LogStatus::Exported, "INBOX uid 1 -> mail.md");
+        log.write(LogStatus::Error, "INBOX uid 2: parse failure");
+
+        let content = fs::read_to_string(log.path()).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("EXPORTED"));
+        assert!(lines[1].ends_with("INBOX uid 2: parse failure"));
+
+        assert_eq!(RunLog::latest_in(temp.path()).as_deref(), Some(log.path()));
+    }
+}
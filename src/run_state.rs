@@ -0,0 +1,103 @@
+//! Per-account state kept between export runs.
+//!
+//! Stored in `state/<account>.json` under the app config directory. Holds
+//! the start time of the last export that went through every folder without
+//! a failed message, so the next one can ask the server only for newer mail
+//! (`SEARCH SINCE`).
+
+use crate::config;
+use crate::utils::sanitize_filename;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What an account remembers of its previous exports.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountState {
+    /// Start of the last complete export (RFC 3339).
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl AccountState {
+    /// State file of `account_name` in the app config directory.
+    pub fn account_path(account_name: &str) -> PathBuf {
+        config::state_dir().join(format!("{}.json", sanitize_filename(account_name)))
+    }
+
+    /// Load the state stored at `path` (empty if absent).
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut state: AccountState = if path.exists() {
+            let content = fs::read_to_string(path).context("Failed to read account state")?;
+            serde_json::from_str(&content).context("Failed to parse account state")?
+        } else {
+            AccountState::default()
+        };
+        state.path = path.to_path_buf();
+        Ok(state)
+    }
+
+    /// Write the state to disk.
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(&self.path, content).context("Failed to write account state")?;
+        Ok(())
+    }
+
+    /// Record a complete export started at `started` and save.
+    pub fn record_run(&mut self, started: DateTime<Utc>) -> Result<()> {
+        self.last_run = Some(started);
+        self.save()
+    }
+
+    /// IMAP `SEARCH` criteria for the messages received since the last run
+    /// (`SINCE 14-Jan-2024` after a run on the 15th), or every message before
+    /// the first run.
+    ///
+    /// `SINCE` compares dates in the server's time zone, which may be a day
+    /// behind UTC, so the search starts the day before the last run; the
+    /// ledger skips what was already exported.
+    pub fn search_criteria(&self) -> String {
+        match self.last_run {
+            Some(last_run) => {
+                let since = last_run.date_naive().pred_opt().unwrap_or(last_run.date_naive());
+                format!("SINCE {}", since.format("%-d-%b-%Y"))
+            }
+            None => "ALL".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_account_state_round_trip() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("state/Gmail.json");
+
+        let mut state = AccountState::load(&path).unwrap();
+        assert_eq!(state.last_run, None);
+        assert_eq!(state.search_criteria(), "ALL");
+        assert!(!path.exists());
+
+        let started = Utc.with_ymd_and_hms(2024, 1, 5, 9, 30, 0).unwrap();
+        state.record_run(started).unwrap();
+        assert!(fs::read_to_string(&path).unwrap().contains("2024-01-05T09:30:00Z"));
+
+        let reloaded = AccountState::load(&path).unwrap();
+        assert_eq!(reloaded, state);
+        assert_eq!(reloaded.search_criteria(), "SINCE 4-Jan-2024");
+    }
+}
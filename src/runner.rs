@@ -0,0 +1,442 @@
+//! Single entry point for exporting an account.
+//!
+//! Loads accounts.yaml + settings.yaml, resolves the account and runs the
+//! export, returning totals over all folders. Messages come from the IMAP
+//! server by default, from an mbox file, or from any [`MessageSource`].
+
+use crate::config::{self, Account, Config, ConfigError};
+use crate::email_export::{self, export_batch, ContactsCollector, ExportStats, ImapExporter};
+use crate::export_index::INDEX_FILE_NAME;
+use crate::ledger::{LEDGER_FILE_NAME, LEGACY_LEDGER_FILE_NAME};
+use crate::mbox::{read_mbox, MboxSource};
+use crate::network::NetworkConfig;
+use crate::utils::cleanup_empty_dirs;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Provider of raw RFC 822 messages, grouped by folder.
+pub trait MessageSource {
+    /// Folders to export, in order.
+    fn folders(&mut self) -> Result<Vec<String>>;
+
+    /// Raw messages of `folder`.
+    fn messages(&mut self, folder: &str) -> Result<Vec<Vec<u8>>>;
+}
+
+/// Load the configuration and return the account named `account_name` (case-insensitive).
+pub fn load_account(account_name: &str, accounts_path: &Path, settings_path: &Path) -> Result<Account> {
+    let config = Config::load_with_settings(accounts_path, settings_path)
+        .context("Failed to load configuration")?;
+
+    let account = config
+        .get_account(account_name)
+        .ok_or_else(|| ConfigError::AccountNotFound(account_name.to_string()))?
+        .clone();
+
+    Ok(account)
+}
+
+/// Like [`load_account`], failing if no password or OAuth2 token is configured for the account.
+fn load_account_with_password(account_name: &str, accounts_path: &Path, settings_path: &Path) -> Result<Account> {
+    let account = load_account(account_name, accounts_path, settings_path)?;
+
+    if !account.has_credentials() {
+        return Err(ConfigError::NoPassword(format!(
+            "{}. Check {}",
+            account.name,
+            config::env_file_path().display()
+        ))
+        .into());
+    }
+
+    Ok(account)
+}
+
+/// Connect to an account's IMAP server and return its folders, without exporting.
+pub fn run_test_connection(account_name: &str, accounts_path: &Path, settings_path: &Path) -> Result<Vec<String>> {
+    let account = load_account_with_password(account_name, accounts_path, settings_path)?;
+    email_export::test_connection(&account, &NetworkConfig::default())
+}
+
+/// Flag set by Ctrl-C, so that an export stops between two messages, keeps
+/// its checkpoint and ledger and reports partial stats instead of dying
+/// mid-write. A second Ctrl-C exits at once.
+///
+/// The handler is installed on the first call (`ctrlc` feature); without
+/// the feature the flag is never set.
+pub fn interrupt_flag() -> Arc<AtomicBool> {
+    static FLAG: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    FLAG.get_or_init(|| {
+        let flag = Arc::new(AtomicBool::new(false));
+        #[cfg(feature = "ctrlc")]
+        {
+            let handler_flag = flag.clone();
+            let installed = ctrlc::set_handler(move || {
+                if handler_flag.swap(true, Ordering::SeqCst) {
+                    std::process::exit(130);
+                }
+                eprintln!("\nInterrupted: finishing the current message (Ctrl-C again to quit now)");
+            });
+            if let Err(e) = installed {
+                eprintln!("Could not install the Ctrl-C handler: {}", e);
+            }
+        }
+        flag
+    })
+    .clone()
+}
+
+/// [`interrupt_flag`], cleared for a new run: a Ctrl-C that stopped an
+/// earlier export (e.g. from the tray) must not stop this one at once.
+pub fn reset_interrupt_flag() -> Arc<AtomicBool> {
+    let flag = interrupt_flag();
+    flag.store(false, Ordering::SeqCst);
+    flag
+}
+
+/// Export every folder of an account from its IMAP server.
+pub fn run_export(account_name: &str, accounts_path: &Path, settings_path: &Path) -> Result<ExportStats> {
+    run_export_with(account_name, accounts_path, settings_path, |exporter| exporter)
+}
+
+/// Like [`run_export`], letting the caller configure the exporter
+/// (progress callback, run log...) before it connects.
+pub fn run_export_with<F>(
+    account_name: &str,
+    accounts_path: &Path,
+    settings_path: &Path,
+    configure: F,
+) -> Result<ExportStats>
+where
+    F: FnOnce(ImapExporter) -> ImapExporter,
+{
+    let account = load_account_with_password(account_name, accounts_path, settings_path)?;
+
+    let mut exporter = configure(ImapExporter::new(account, false).with_stop_flag(reset_interrupt_flag()));
+    exporter.connect().context("Failed to connect to IMAP server")?;
+
+    let results = exporter.export_account().context("Export failed")?;
+
+    exporter.disconnect().ok();
+
+    Ok(results
+        .into_values()
+        .fold(ExportStats::default(), ExportStats::merge))
+}
+
+/// Export the messages of an mbox file with the settings of `account_name`.
+///
+/// The account only provides the export directory and options; no
+/// connection is made, so no password is needed. `limit` overrides the
+/// account's `max_messages`.
+pub fn run_mbox_export(
+    mbox_path: &Path,
+    account_name: &str,
+    accounts_path: &Path,
+    settings_path: &Path,
+    limit: Option<usize>,
+) -> Result<ExportStats> {
+    let mut account = load_account(account_name, accounts_path, settings_path)?;
+    account.max_messages = limit.or(account.max_messages);
+    export_from_source_until(&account, &mut MboxSource::new(mbox_path), &reset_interrupt_flag())
+}
+
+/// Preview the export of an mbox file into `scratch_dir` (see [`preview`]).
+pub fn run_mbox_preview(
+    mbox_path: &Path,
+    account_name: &str,
+    accounts_path: &Path,
+    settings_path: &Path,
+    limit: Option<usize>,
+    scratch_dir: &Path,
+) -> Result<Vec<(PathBuf, u64)>> {
+    let mut account = load_account(account_name, accounts_path, settings_path)?;
+    account.max_messages = limit.or(account.max_messages);
+    preview(&account, &mut MboxSource::new(mbox_path), scratch_dir)
+}
+
+/// Scratch directory of a preview run for `account_name`, emptied first.
+pub fn preview_scratch_dir(account_name: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir()
+        .join("email-to-markdown-preview")
+        .join(crate::utils::sanitize_filename(account_name));
+    if dir.exists() {
+        fs::remove_dir_all(&dir).context("Failed to clear the preview directory")?;
+    }
+    Ok(dir)
+}
+
+/// Export an mbox file as one markdown file per conversation (see
+/// [`email_export::export_threads`]); returns the files written and the
+/// message counts.
+pub fn run_mbox_thread_export(
+    mbox_path: &Path,
+    account_name: &str,
+    accounts_path: &Path,
+    settings_path: &Path,
+) -> Result<(Vec<PathBuf>, ExportStats)> {
+    let account = load_account(account_name, accounts_path, settings_path)?;
+    let messages = read_mbox(mbox_path)?;
+    email_export::export_threads(&messages, &account)
+}
+
+/// Export `source` into `scratch_dir` instead of the account's export
+/// directory and list what would be written (see [`preview_files`]).
+///
+/// Already-exported messages are skipped as in a real run (see
+/// [`prepare_preview`]); nothing is deleted, whatever `delete_after_export` says.
+pub fn preview(account: &Account, source: &mut dyn MessageSource, scratch_dir: &Path) -> Result<Vec<(PathBuf, u64)>> {
+    let scratch_account = prepare_preview(account, scratch_dir)?;
+    export_from_source(&scratch_account, source)?;
+    preview_files(scratch_dir)
+}
+
+/// Copy of `account` that exports into `scratch_dir` and never deletes messages.
+///
+/// The export index and Message-Id ledger of the real export directory are
+/// copied into `scratch_dir`, so `skip_existing` behaves as in a real run.
+pub fn prepare_preview(account: &Account, scratch_dir: &Path) -> Result<Account> {
+    fs::create_dir_all(scratch_dir)?;
+    let real_dir = Path::new(&account.export_directory);
+    for name in [INDEX_FILE_NAME, LEDGER_FILE_NAME, LEGACY_LEDGER_FILE_NAME] {
+        if real_dir.join(name).is_file() {
+            fs::copy(real_dir.join(name), scratch_dir.join(name))
+                .with_context(|| format!("Failed to copy {} to the preview directory", name))?;
+        }
+    }
+
+    let mut preview_account = account.clone();
+    preview_account.export_directory = scratch_dir.to_string_lossy().replace('\\', "/");
+    preview_account.delete_after_export = false;
+    Ok(preview_account)
+}
+
+/// Files written under `scratch_dir`, as `(relative path, size in bytes)` in
+/// path order. Bookkeeping files (names starting with `.`) are left out.
+pub fn preview_files(scratch_dir: &Path) -> Result<Vec<(PathBuf, u64)>> {
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(scratch_dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() || entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(scratch_dir).unwrap_or(entry.path()).to_path_buf();
+        files.push((relative, entry.metadata()?.len()));
+    }
+    Ok(files)
+}
+
+/// Export an account's messages taken from `source` instead of IMAP.
+///
+/// Ignored folders are skipped and contacts are collected as for an IMAP export.
+/// The export stops after `max_messages` exported messages, across folders.
+pub fn export_from_source(account: &Account, source: &mut dyn MessageSource) -> Result<ExportStats> {
+    export_from_source_until(account, source, &AtomicBool::new(false))
+}
+
+/// Like [`export_from_source`], stopping between two messages once `stop`
+/// is set and returning the stats of what was exported.
+pub fn export_from_source_until(
+    account: &Account,
+    source: &mut dyn MessageSource,
+    stop: &AtomicBool,
+) -> Result<ExportStats> {
+    let should_stop = || stop.load(Ordering::SeqCst);
+    let mut stats = ExportStats::default();
+    let mut contacts_collector = if account.collect_contacts {
+        Some(ContactsCollector::for_account(account))
+    } else {
+        None
+    };
+
+    for folder in source.folders()? {
+        if account.is_folder_ignored(&folder) {
+            continue;
+        }
+
+        let remaining = account.max_messages.map(|max| max.saturating_sub(stats.exported));
+        if remaining == Some(0) || should_stop() {
+            break;
+        }
+
+        let messages = source.messages(&folder)?;
+        let folder_stats = export_batch(account, &folder, &messages, remaining, contacts_collector.as_mut(), false, &should_stop)?;
+        stats = stats.merge(folder_stats);
+    }
+
+    let attachments_dir = PathBuf::from(&account.export_directory).join("attachments");
+    cleanup_empty_dirs(&attachments_dir).context("Failed to remove empty attachment directories")?;
+
+    if let Some(collector) = contacts_collector {
+        collector.generate_csv(&PathBuf::from(&account.export_directory), &account.name)?;
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    struct FakeSource(Vec<(String, Vec<Vec<u8>>)>);
+
+    impl MessageSource for FakeSource {
+        fn folders(&mut self) -> Result<Vec<String>> {
+            Ok(self.0.iter().map(|(folder, _)| folder.clone()).collect())
+        }
+
+        fn messages(&mut self, folder: &str) -> Result<Vec<Vec<u8>>> {
+            Ok(self
+                .0
+                .iter()
+                .find(|(f, _)| f == folder)
+                .map(|(_, messages)| messages.clone())
+                .unwrap_or_default())
+        }
+    }
+
+    fn message(id: &str, subject: &str) -> Vec<u8> {
+        format!(
+            "From: alice@example.com\r\nTo: bob@example.com\r\nSubject: {}\r\nMessage-ID: <{}>\r\nDate: Mon, 15 Jan 2024 10:00:00 +0000\r\n\r\nBody of {}",
+            subject, id, subject
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_export_from_source() {
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("export");
+
+        let accounts_path = temp.path().join("accounts.yaml");
+        fs::write(
+            &accounts_path,
+            "accounts:\n  - name: Test\n    server: imap.example.com\n    port: 993\n    username: user@example.com\n    ignored_folders:\n      - Spam\n",
+        )
+        .unwrap();
+        let settings_path = temp.path().join("settings.yaml");
+        fs::write(&settings_path, format!("export_base_dir: {}\n", export_dir.display())).unwrap();
+
+        let account = load_account("test", &accounts_path, &settings_path).unwrap();
+        assert!(load_account("Missing", &accounts_path, &settings_path).is_err());
+
+        // No password: fails before any connection attempt
+        let err = run_test_connection("Test", &accounts_path, &settings_path).unwrap_err();
+        assert!(matches!(err.downcast_ref::<ConfigError>(), Some(ConfigError::NoPassword(_))));
+
+        let mut source = FakeSource(vec![
+            ("INBOX".to_string(), vec![message("1@x", "One"), message("2@x", "Two")]),
+            ("Archive".to_string(), vec![message("1@x", "One again")]),
+            ("Spam".to_string(), vec![message("3@x", "Spam")]),
+        ]);
+
+        let stats = export_from_source(&account, &mut source).unwrap();
+        assert_eq!((stats.exported, stats.skipped, stats.errors), (2, 1, 0));
+        assert!(export_dir.join("Test/INBOX").is_dir());
+        assert!(!export_dir.join("Test/Spam").exists());
+
+        let mbox_path = temp.path().join("Takeout.mbox");
+        let mut mbox = b"From alice@example.com Mon Jan 15 10:00:00 2024\n".to_vec();
+        mbox.extend(message("4@x", "From mbox"));
+        fs::write(&mbox_path, mbox).unwrap();
+
+        let stats = run_mbox_export(&mbox_path, "Test", &accounts_path, &settings_path, None).unwrap();
+        assert_eq!(stats.exported, 1);
+        assert!(export_dir.join("Test/Takeout").is_dir());
+    }
+
+    #[test]
+    fn test_export_from_source_max_messages() {
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("export");
+        let accounts_path = temp.path().join("accounts.yaml");
+        fs::write(
+            &accounts_path,
+            "accounts:\n  - name: Test\n    server: imap.example.com\n    port: 993\n    username: user@example.com\n",
+        )
+        .unwrap();
+        let settings_path = temp.path().join("settings.yaml");
+        fs::write(
+            &settings_path,
+            format!("export_base_dir: {}\ndefaults:\n  max_messages: 2\n", export_dir.display()),
+        )
+        .unwrap();
+        let account = load_account("Test", &accounts_path, &settings_path).unwrap();
+        assert_eq!(account.max_messages, Some(2));
+
+        // An already-exported message is skipped and does not count
+        let mut source = FakeSource(vec![(
+            "INBOX".to_string(),
+            vec![message("1@x", "One"), message("1@x", "One again")],
+        )]);
+        let stats = export_from_source(&account, &mut source).unwrap();
+        assert_eq!((stats.exported, stats.skipped), (1, 1));
+
+        let mut source = FakeSource(vec![
+            ("INBOX".to_string(), (2..5).map(|i| message(&format!("{}@x", i), &format!("Message {}", i))).collect()),
+            ("Archive".to_string(), vec![message("5@x", "Message 5"), message("6@x", "Message 6")]),
+        ]);
+        let stats = export_from_source(&account, &mut source).unwrap();
+        assert_eq!(stats.exported, 2);
+
+        let files = walkdir::WalkDir::new(&export_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "md"))
+            .count();
+        assert_eq!(files, 3);
+        assert!(!export_dir.join("Test/Archive").exists());
+    }
+
+    #[test]
+    fn test_preview_into_scratch_dir() {
+        let temp = TempDir::new().unwrap();
+        let export_dir = temp.path().join("export");
+        let accounts_path = temp.path().join("accounts.yaml");
+        fs::write(
+            &accounts_path,
+            "accounts:\n  - name: Test\n    server: imap.example.com\n    port: 993\n    username: user@example.com\n",
+        )
+        .unwrap();
+        let settings_path = temp.path().join("settings.yaml");
+        fs::write(
+            &settings_path,
+            format!("export_base_dir: {}\ndefaults:\n  delete_after_export: true\n", export_dir.display()),
+        )
+        .unwrap();
+        let account = load_account("Test", &accounts_path, &settings_path).unwrap();
+
+        // One message already in the real vault
+        let mut source = FakeSource(vec![("INBOX".to_string(), vec![message("1@x", "One")])]);
+        export_from_source(&account, &mut source).unwrap();
+        let real_files = preview_files(&export_dir).unwrap();
+
+        let scratch = temp.path().join("scratch");
+        let mut source = FakeSource(vec![
+            ("INBOX".to_string(), vec![message("1@x", "One"), message("2@x", "Two")]),
+            ("Archive".to_string(), vec![message("3@x", "Three")]),
+        ]);
+        let files = preview(&account, &mut source, &scratch).unwrap();
+
+        let hash = |subject: &str| crate::utils::hash_md5_prefix(subject, 6);
+        let names: Vec<PathBuf> = files.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(
+            names,
+            vec![
+                PathBuf::from("Archive/email_2024-01-15_ALI_to_BOB.md"),
+                PathBuf::from("INBOX/email_2024-01-15_ALI_to_BOB.md"),
+            ]
+        );
+        assert!(fs::read_to_string(scratch.join(&names[1])).unwrap().contains(&hash("Two")));
+        assert!(files.iter().all(|(path, size)| fs::metadata(scratch.join(path)).unwrap().len() == *size));
+
+        // The real vault is untouched
+        assert_eq!(preview_files(&export_dir).unwrap(), real_files);
+        assert!(!prepare_preview(&account, &scratch).unwrap().delete_after_export);
+    }
+}
@@ -1,12 +1,23 @@
 use crate::config::SortConfig;
+use crate::fix_yaml::expand_frontmatter_tabs;
+use crate::run_log::{LogStatus, RunLog};
+use crate::utils::{
+    extract_emails, fold_diacritics, hash_md5_prefix, keyword_regex, markdown_files,
+    normalize_subject, truncate_on_char_boundary, Verbosity,
+};
 use anyhow::{Context, Result};
-use chrono::{DateTime, FixedOffset, Utc};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_yaml::Value;
 use std::collections::HashMap;
 use std::fs;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 /// Email sorting category.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -15,6 +26,8 @@ pub enum Category {
     Delete,
     Summarize,
     Keep,
+    /// Borderline score, left for manual review.
+    Review,
 }
 
 impl std::fmt::Display for Category {
@@ -23,10 +36,50 @@ impl std::fmt::Display for Category {
             Category::Delete => write!(f, "delete"),
             Category::Summarize => write!(f, "summarize"),
             Category::Keep => write!(f, "keep"),
+            Category::Review => write!(f, "review"),
         }
     }
 }
 
+impl Category {
+    /// ANSI color of the category in the terminal summary.
+    fn ansi_color(&self) -> &'static str {
+        match self {
+            Category::Delete => "31",
+            Category::Summarize => "33",
+            Category::Keep => "32",
+            Category::Review => "36",
+        }
+    }
+}
+
+/// Width of the count bars in the summary.
+const SUMMARY_BAR_WIDTH: usize = 30;
+
+/// Whether the summary may use colors: stdout is a terminal and `NO_COLOR` is unset.
+fn use_color() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Wrap `text` in an ANSI color when `enabled`.
+fn paint(text: &str, color: &str, enabled: bool) -> String {
+    if enabled {
+        format!("\x1b[{}m{}\x1b[0m", color, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Bar of `width` characters at most, proportional to `count` out of `total`.
+fn count_bar(count: usize, total: usize, width: usize) -> String {
+    if total == 0 {
+        return String::new();
+    }
+    // At least one mark for a non-zero count
+    let filled = (count * width).div_ceil(total).min(width);
+    "#".repeat(filled)
+}
+
 /// Email type classification.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -64,9 +117,17 @@ pub struct EmailData {
     pub sender: String,
     pub recipients: Vec<String>,
     pub subject: String,
+    pub message_id: Option<String>,
+    pub body_hash: Option<String>,
     pub tags: Vec<String>,
     pub email_type: EmailSortType,
+    /// Out-of-office or other automatic reply (`auto_submitted: auto-replied`).
+    pub auto_reply: bool,
+    /// Links of the exported `unsubscribe` field.
+    pub unsubscribe: Vec<String>,
     pub score: i32,
+    /// Individual scoring contributions; `score` is their sum.
+    pub reasons: Vec<(String, i32)>,
     pub category: Category,
 }
 
@@ -74,6 +135,8 @@ pub struct EmailData {
 #[derive(Debug, Default, Serialize)]
 pub struct SortStats {
     pub total_emails: usize,
+    /// Files under `skip_below_chars`, not sorted.
+    pub skipped_empty: usize,
     pub by_category: HashMap<String, usize>,
     pub by_type: HashMap<String, usize>,
     pub by_sender: HashMap<String, usize>,
@@ -86,11 +149,16 @@ pub struct SortReport {
     pub summary: SortSummary,
     pub details: SortDetails,
     pub categories: HashMap<String, Vec<EmailSummary>>,
+    /// Groups of files that look like copies of the same email.
+    pub duplicates: Vec<Vec<String>>,
+    /// Unsubscribe links of the emails to delete.
+    pub unsubscribe: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct SortSummary {
     pub total_emails: usize,
+    pub skipped_empty: usize,
     pub categories: HashMap<String, usize>,
     pub recommendations: HashMap<String, String>,
 }
@@ -109,18 +177,66 @@ pub struct EmailSummary {
     pub sender: String,
     pub date: String,
     pub score: i32,
+    pub reasons: Vec<(String, i32)>,
     #[serde(rename = "type")]
     pub email_type: String,
     pub size: u64,
     pub attachments: usize,
 }
 
+/// A delete/keep keyword compiled once per sorter.
+enum Keyword {
+    /// Plain entry, matched as a substring of the lowercased text.
+    Substring(String),
+    /// Entry written as `/regex/`.
+    Pattern(Regex),
+}
+
+impl Keyword {
+    /// Compile config entries, keeping the original text for score reasons.
+    ///
+    /// With `fold`, entries are stripped of accents like the text they are matched against.
+    /// Invalid regexes are rejected by `SortConfig::validate`; any left here are ignored.
+    fn compile(entries: &[String], fold: bool) -> Vec<(String, Keyword)> {
+        entries
+            .iter()
+            .filter_map(|entry| {
+                let normalized = if fold { fold_diacritics(entry) } else { entry.clone() };
+                let keyword = match keyword_regex(&normalized) {
+                    Some(Ok(regex)) => Keyword::Pattern(regex),
+                    Some(Err(_)) => return None,
+                    None => Keyword::Substring(normalized.to_lowercase()),
+                };
+                Some((entry.clone(), keyword))
+            })
+            .collect()
+    }
+
+    fn is_match(&self, text_lower: &str) -> bool {
+        match self {
+            Keyword::Substring(s) => text_lower.contains(s.as_str()),
+            Keyword::Pattern(regex) => regex.is_match(text_lower),
+        }
+    }
+}
+
 /// Email sorter.
 pub struct EmailSorter {
     base_directory: PathBuf,
     config: SortConfig,
+    delete_keywords: Vec<(String, Keyword)>,
+    keep_keywords: Vec<(String, Keyword)>,
     categories: HashMap<Category, Vec<EmailData>>,
     stats: SortStats,
+    run_log: Option<RunLog>,
+    verbosity: Verbosity,
+    /// Files skipped as empty by `analyze_email_file` (counted across threads).
+    skipped_empty: AtomicUsize,
+    /// Analysis threads (`None`: one per CPU). Ignored without the `rayon` feature.
+    #[cfg_attr(not(feature = "rayon"), allow(dead_code))]
+    threads: Option<usize>,
+    /// JSON Lines output fed by the analysis threads, set by `write_jsonl`.
+    jsonl: Option<(PathBuf, Mutex<std::io::BufWriter<fs::File>>)>,
 }
 
 impl EmailSorter {
@@ -129,49 +245,117 @@ impl EmailSorter {
         stats.by_category.insert("delete".to_string(), 0);
         stats.by_category.insert("summarize".to_string(), 0);
         stats.by_category.insert("keep".to_string(), 0);
+        stats.by_category.insert("review".to_string(), 0);
 
         EmailSorter {
             base_directory,
+            delete_keywords: Keyword::compile(&config.delete_keywords, config.accent_insensitive),
+            keep_keywords: Keyword::compile(&config.keep_keywords, config.accent_insensitive),
             config,
             categories: HashMap::new(),
             stats,
+            run_log: None,
+            verbosity: Verbosity::default(),
+            skipped_empty: AtomicUsize::new(0),
+            threads: None,
+            jsonl: None,
+        }
+    }
+
+    /// Limit the number of threads used to analyze files.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    /// How much to print while sorting: `Quiet` leaves only parse errors,
+    /// `Verbose` adds the category of every file.
+    pub fn with_verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Record every analyzed or skipped file in a run log.
+    pub fn with_run_log(mut self, run_log: RunLog) -> Self {
+        self.run_log = Some(run_log);
+        self
+    }
+
+    /// Lowercase text for matching, stripping accents if configured.
+    fn normalize_text(&self, text: &str) -> String {
+        if self.config.accent_insensitive {
+            fold_diacritics(text).to_lowercase()
+        } else {
+            text.to_lowercase()
+        }
+    }
+
+    /// Print `message` if the verbosity reaches `level`.
+    fn say(&self, level: Verbosity, message: impl FnOnce() -> String) {
+        if self.verbosity.shows(level) {
+            println!("{}", message());
         }
     }
 
+    fn log(&self, status: LogStatus, file_path: &Path, detail: &str) {
+        if let Some(run_log) = &self.run_log {
+            run_log.write(status, &format!("{}: {}", file_path.display(), detail));
+        }
+    }
+
+    /// Count `file_path` as skipped for being empty.
+    fn skip_empty(&self, file_path: &Path) {
+        self.skipped_empty.fetch_add(1, Ordering::Relaxed);
+        self.say(Verbosity::Normal, || format!("  Skipping empty file: {}", file_path.display()));
+        self.log(LogStatus::Skipped, file_path, "empty file");
+    }
+
     /// Analyze a single email markdown file.
     pub fn analyze_email_file(&self, file_path: &Path) -> Result<Option<EmailData>> {
         let content = fs::read_to_string(file_path)
             .context("Failed to read file")?;
 
-        // Handle empty or very small files
-        if content.trim().len() < 10 {
-            println!("  Skipping empty file: {}", file_path.display());
+        // Handle empty files
+        if content.trim().is_empty() {
+            self.skip_empty(file_path);
             return Ok(None);
         }
 
         // Handle files with no frontmatter
         if !content.starts_with("---") {
-            println!(
-                "  Skipping file with no YAML frontmatter: {}",
-                file_path.display()
-            );
+            self.say(Verbosity::Normal, || {
+                format!("  Skipping file with no YAML frontmatter: {}", file_path.display())
+            });
+            self.log(LogStatus::Skipped, file_path, "no YAML frontmatter");
             return Ok(None);
         }
 
-        // Extract frontmatter and body
-        let (frontmatter, body) = match extract_frontmatter(&content) {
+        // Extract frontmatter and body (tab indentation is invalid YAML)
+        let (frontmatter, body) = match extract_frontmatter(&expand_frontmatter_tabs(&content)) {
             Some(parts) => parts,
             None => {
-                println!("  No valid frontmatter in: {}", file_path.display());
+                self.say(Verbosity::Normal, || format!("  No valid frontmatter in: {}", file_path.display()));
+                self.log(LogStatus::Skipped, file_path, "invalid frontmatter");
                 return Ok(None);
             }
         };
 
+        // Handle (nearly) empty bodies: read receipts, delivery notifications...
+        if body.trim().chars().count() < self.config.skip_below_chars {
+            self.skip_empty(file_path);
+            return Ok(None);
+        }
+
         // Parse frontmatter
         let fm: Value = match serde_yaml::from_str(&frontmatter) {
             Ok(v) => v,
             Err(e) => {
-                println!("  Could not parse frontmatter: {}...", &e.to_string()[..100.min(e.to_string().len())]);
+                eprintln!(
+                    "  Could not parse frontmatter of {}: {}...",
+                    file_path.display(),
+                    truncate_on_char_boundary(&e.to_string(), 100)
+                );
+                self.log(LogStatus::Error, file_path, &format!("could not parse frontmatter: {}", e));
                 return Ok(None);
             }
         };
@@ -193,6 +377,24 @@ impl EmailSorter {
             .get("date")
             .and_then(|v| v.as_str())
             .unwrap_or("");
+        let message_id = fm
+            .get("message_id")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(String::from);
+        let body_hash = fm
+            .get("body_hash")
+            .and_then(|v| v.as_str())
+            .map(String::from);
+
+        let auto_reply = fm
+            .get("auto_submitted")
+            .and_then(|v| v.as_str())
+            .is_some_and(|v| v.starts_with("auto-replied"));
+
+        let to = addresses(&fm, "to");
+        let cc = addresses(&fm, "cc");
 
         let attachments = fm
             .get("attachments")
@@ -200,26 +402,33 @@ impl EmailSorter {
             .map(|s| s.len())
             .unwrap_or(0);
 
-        let tags: Vec<String> = fm
-            .get("tags")
-            .and_then(|v| v.as_sequence())
-            .map(|s| {
-                s.iter()
-                    .filter_map(|v| v.as_str())
-                    .map(String::from)
-                    .collect()
-            })
-            .unwrap_or_default();
+        let string_list = |key: &str| -> Vec<String> {
+            fm.get(key)
+                .and_then(|v| v.as_sequence())
+                .map(|s| {
+                    s.iter()
+                        .filter_map(|v| v.as_str())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let tags = string_list("tags");
+        let unsubscribe = string_list("unsubscribe");
 
         // Parse date
-        let date = parse_date(date_str);
+        let naive_offset = self
+            .config
+            .naive_date_utc_offset_minutes
+            .and_then(|minutes| FixedOffset::east_opt(minutes * 60));
+        let date = parse_date(date_str, naive_offset);
         let age_days = date.map(|d| {
             let now = Utc::now();
             (now.signed_duration_since(d.with_timezone(&Utc))).num_days()
         });
 
         // Determine email type
-        let email_type = self.determine_email_type(&subject, &fm);
+        let email_type = self.determine_email_type(&subject, &to, &cc);
 
         // Build email data
         let mut email_data = EmailData {
@@ -236,16 +445,22 @@ impl EmailSorter {
             date,
             age_days,
             sender,
-            recipients: Vec::new(),
+            recipients: to.into_iter().chain(cc).collect(),
             subject,
+            message_id,
+            body_hash,
             tags,
             email_type,
+            auto_reply,
+            unsubscribe,
             score: 0,
+            reasons: Vec::new(),
             category: Category::Summarize,
         };
 
         // Calculate score
-        email_data.score = self.calculate_score(&email_data, &body);
+        email_data.reasons = self.calculate_score(&email_data, &body);
+        email_data.score = email_data.reasons.iter().map(|(_, points)| points).sum();
 
         // Determine category
         email_data.category = self.determine_category(&email_data, &body);
@@ -253,11 +468,13 @@ impl EmailSorter {
         Ok(Some(email_data))
     }
 
-    /// Determine email type from subject and frontmatter.
-    fn determine_email_type(&self, subject: &str, _fm: &Value) -> EmailSortType {
+    /// Determine email type from subject and recipients (same rules as the exporter).
+    fn determine_email_type(&self, subject: &str, to: &[String], cc: &[String]) -> EmailSortType {
         let subject_lower = subject.to_lowercase();
 
-        if subject_lower.contains("newsletter")
+        if to.len() > 1 || cc.len() > 1 {
+            EmailSortType::Group
+        } else if subject_lower.contains("newsletter")
             || subject_lower.contains("bulletin")
             || subject_lower.contains("digest")
         {
@@ -267,85 +484,89 @@ impl EmailSorter {
         }
     }
 
-    /// Calculate a score for the email.
-    fn calculate_score(&self, email_data: &EmailData, body: &str) -> i32 {
-        let mut score: i32 = 0;
+    /// Calculate the score contributions for an email.
+    ///
+    /// Each entry is a human-readable reason and the points it adds.
+    fn calculate_score(&self, email_data: &EmailData, body: &str) -> Vec<(String, i32)> {
+        let mut reasons: Vec<(String, i32)> = Vec::new();
 
         // Type weight
         let type_key = email_data.email_type.to_string();
         if let Some(&weight) = self.config.type_weights.get(&type_key) {
-            score += weight;
+            if weight != 0 {
+                reasons.push((type_key, weight));
+            }
+        }
+
+        if email_data.auto_reply && self.config.auto_reply_weight != 0 {
+            reasons.push(("auto-reply".to_string(), self.config.auto_reply_weight));
         }
 
         // Age factors
         if let Some(age) = email_data.age_days {
             if age <= self.config.recent_threshold_days {
-                score += 2;
+                reasons.push(("recent".to_string(), 2));
             } else if age >= self.config.old_threshold_days {
-                score -= 1;
+                reasons.push(("old".to_string(), -1));
             }
         }
 
         // Size factors
         if email_data.body_length <= self.config.small_email_threshold {
-            score -= 1;
+            reasons.push(("small".to_string(), -1));
         } else if email_data.body_length >= self.config.large_email_threshold {
-            score += 1;
+            reasons.push(("large".to_string(), 1));
         }
 
         // Attachment factors
         if email_data.has_attachments {
             if self.config.keep_with_attachments {
-                score += 2;
+                reasons.push(("attachments".to_string(), 2));
             } else {
-                score -= 1;
+                reasons.push(("attachments".to_string(), -1));
             }
         }
 
         // Subject analysis
-        let subject_lower = email_data.subject.to_lowercase();
+        let subject_lower = self.normalize_text(&email_data.subject);
 
         // Delete keywords
-        let delete_count = self
-            .config
-            .delete_keywords
-            .iter()
-            .filter(|k| subject_lower.contains(&k.to_lowercase()))
-            .count() as i32;
-        score -= delete_count;
+        for (k, keyword) in &self.delete_keywords {
+            if keyword.is_match(&subject_lower) {
+                reasons.push((format!("delete keyword '{}'", k), -1));
+            }
+        }
 
         // Keep keywords
-        let keep_count = self
-            .config
-            .keep_keywords
-            .iter()
-            .filter(|k| subject_lower.contains(&k.to_lowercase()))
-            .count() as i32;
-        score += keep_count * 2;
+        for (k, keyword) in &self.keep_keywords {
+            if keyword.is_match(&subject_lower) {
+                reasons.push((format!("keep keyword '{}'", k), 2));
+            }
+        }
 
         // Sender analysis
-        let sender_lower = email_data.sender.to_lowercase();
+        let sender_lower = self.normalize_text(&email_data.sender);
 
-        if self
+        if let Some(s) = self
             .config
             .delete_senders
             .iter()
-            .any(|s| sender_lower.contains(&s.to_lowercase()))
+            .find(|s| sender_lower.contains(&self.normalize_text(s)))
         {
-            score -= 3;
+            reasons.push((format!("delete sender '{}'", s), -3));
         }
 
-        if self
+        if let Some(s) = self
             .config
             .keep_senders
             .iter()
-            .any(|s| sender_lower.contains(&s.to_lowercase()))
+            .find(|s| sender_lower.contains(&self.normalize_text(s)))
         {
-            score += 3;
+            reasons.push((format!("keep sender '{}'", s), 3));
         }
 
         // Body content analysis
-        let body_lower = body.to_lowercase();
+        let body_lower = self.normalize_text(body);
         let important_keywords = [
             "contract",
             "invoice",
@@ -358,14 +579,14 @@ impl EmailSorter {
             "payment",
         ];
 
-        if important_keywords
+        if let Some(k) = important_keywords
             .iter()
-            .any(|&k| body_lower.contains(k))
+            .find(|&&k| body_lower.contains(k))
         {
-            score += 2;
+            reasons.push((format!("important body keyword '{}'", k), 2));
         }
 
-        score
+        reasons
     }
 
     /// Determine the category for an email.
@@ -375,45 +596,71 @@ impl EmailSorter {
             return Category::Keep;
         }
 
-        let subject_lower = email_data.subject.to_lowercase();
-        let sender_lower = email_data.sender.to_lowercase();
-        let body_lower = body.to_lowercase();
+        let subject_lower = self.normalize_text(&email_data.subject);
+        let sender_lower = self.normalize_text(&email_data.sender);
+        let body_lower = self.normalize_text(body);
+
+        // Explicit keep senders come next: they win over the email type, its
+        // age, delete keywords and delete senders
+        if self
+            .config
+            .keep_senders
+            .iter()
+            .any(|s| sender_lower.contains(&self.normalize_text(s)))
+        {
+            return Category::Keep;
+        }
+
+        // Bulk mail is only deleted once it is no longer recent: this week's
+        // newsletter falls through to Summarize instead
+        let is_bulk = matches!(
+            email_data.email_type,
+            EmailSortType::Newsletter | EmailSortType::MailingList
+        );
+        let is_recent = email_data
+            .age_days
+            .is_some_and(|age| age <= self.config.recent_threshold_days);
+        let recent_bulk = is_bulk && is_recent;
 
         // Strong delete indicators
-        let delete_indicators = email_data.email_type == EmailSortType::Newsletter
-            || self
-                .config
-                .delete_keywords
-                .iter()
-                .any(|k| subject_lower.contains(&k.to_lowercase()))
+        let delete_indicators = (is_bulk && !is_recent)
+            || (!recent_bulk
+                && self
+                    .delete_keywords
+                    .iter()
+                    .any(|(_, k)| k.is_match(&subject_lower)))
             || self
                 .config
                 .delete_senders
                 .iter()
-                .any(|s| sender_lower.contains(&s.to_lowercase()));
+                .any(|s| sender_lower.contains(&self.normalize_text(s)));
 
         // Strong keep indicators
         let keep_indicators = self
-            .config
             .keep_keywords
             .iter()
-            .any(|k| subject_lower.contains(&k.to_lowercase()))
-            || self
-                .config
-                .keep_senders
-                .iter()
-                .any(|s| sender_lower.contains(&s.to_lowercase()))
+            .any(|(_, k)| k.is_match(&subject_lower))
             || (email_data.has_attachments && self.config.keep_with_attachments)
             || ["contract", "invoice", "legal", "urgent", "important"]
                 .iter()
                 .any(|&k| body_lower.contains(k));
 
+        // Scores close to a threshold are left for manual review
+        let margin = self.config.review_margin;
+        let borderline = margin > 0
+            && ((email_data.score - self.config.delete_score_threshold).abs() <= margin
+                || (email_data.score - self.config.keep_score_threshold).abs() <= margin);
+
         // Apply rules
         if keep_indicators {
             Category::Keep
-        } else if delete_indicators || email_data.score <= -2 {
+        } else if delete_indicators {
+            Category::Delete
+        } else if borderline {
+            Category::Review
+        } else if !recent_bulk && email_data.score <= self.config.delete_score_threshold {
             Category::Delete
-        } else if email_data.score >= 2
+        } else if email_data.score >= self.config.keep_score_threshold
             || email_data.body_length > self.config.summarize_max_length
         {
             Category::Keep
@@ -423,55 +670,151 @@ impl EmailSorter {
     }
 
     /// Sort all emails in the directory.
+    ///
+    /// Files are analyzed in parallel, then folded into the statistics in path
+    /// order so the report does not depend on the thread count.
     pub fn sort_emails(&mut self) -> Result<()> {
-        println!("Sorting emails in: {}", self.base_directory.display());
+        self.say(Verbosity::Normal, || format!("Sorting emails in: {}", self.base_directory.display()));
 
-        let entries: Vec<PathBuf> = WalkDir::new(&self.base_directory)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| {
-                e.path().extension().map_or(false, |ext| ext == "md")
-                    && !e.path().to_string_lossy().contains("attachments")
-            })
-            .map(|e| e.path().to_path_buf())
-            .collect();
+        self.skipped_empty.store(0, Ordering::Relaxed);
+        let mut analyzed = self.analyze_files()?;
+        analyzed.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        self.stats.skipped_empty += self.skipped_empty.load(Ordering::Relaxed);
 
-        for file_path in entries {
-            if let Some(email_data) = self.analyze_email_file(&file_path)? {
-                self.stats.total_emails += 1;
-
-                let category = email_data.category.clone();
-                let category_key = category.to_string();
-                *self
-                    .stats
-                    .by_category
-                    .entry(category_key)
-                    .or_insert(0) += 1;
-
-                let type_key = email_data.email_type.to_string();
-                *self.stats.by_type.entry(type_key).or_insert(0) += 1;
-
-                *self
-                    .stats
-                    .by_sender
-                    .entry(email_data.sender.clone())
-                    .or_insert(0) += 1;
-
-                if let Some(date) = &email_data.date {
-                    let date_key = date.format("%Y-%m").to_string();
-                    *self.stats.by_date.entry(date_key).or_insert(0) += 1;
-                }
+        if let Some((path, writer)) = self.jsonl.take() {
+            writer.into_inner().unwrap_or_else(|e| e.into_inner()).flush()?;
+            self.say(Verbosity::Normal, || format!("JSON Lines saved to: {}", path.display()));
+        }
 
-                self.categories
-                    .entry(category)
-                    .or_insert_with(Vec::new)
-                    .push(email_data);
+        for email_data in analyzed {
+            self.log(LogStatus::Info, &email_data.file_path, &format!("sorted as {}", email_data.category));
+            self.say(Verbosity::Verbose, || {
+                format!("  {}: {}", email_data.file_path.display(), email_data.category)
+            });
+            self.stats.total_emails += 1;
+
+            let category = email_data.category.clone();
+            let category_key = category.to_string();
+            *self
+                .stats
+                .by_category
+                .entry(category_key)
+                .or_insert(0) += 1;
+
+            let type_key = email_data.email_type.to_string();
+            *self.stats.by_type.entry(type_key).or_insert(0) += 1;
+
+            *self
+                .stats
+                .by_sender
+                .entry(email_data.sender.clone())
+                .or_insert(0) += 1;
+
+            if let Some(date) = &email_data.date {
+                let date_key = date.format("%Y-%m").to_string();
+                *self.stats.by_date.entry(date_key).or_insert(0) += 1;
             }
+
+            self.categories
+                .entry(category)
+                .or_default()
+                .push(email_data);
         }
 
         Ok(())
     }
 
+    /// Group sorted emails that look like copies of the same message.
+    ///
+    /// Emails are matched by Message-Id when present, then by the exported
+    /// `body_hash`, otherwise by a hash of sender, subject (without `Re:`/`Fwd:`
+    /// prefixes) and date. Only groups with more than one file are returned.
+    pub fn find_duplicates(&self) -> Vec<Vec<PathBuf>> {
+        let mut groups: HashMap<String, Vec<PathBuf>> = HashMap::new();
+
+        for email in self.categories.values().flatten() {
+            let key = match (&email.message_id, &email.body_hash) {
+                (Some(id), _) => format!("id:{}", id),
+                (None, Some(hash)) => format!("body:{}", hash),
+                (None, None) => {
+                    let date = email.date.map(|d| d.to_rfc3339()).unwrap_or_default();
+                    let identity = format!("{}\n{}\n{}", email.sender, normalize_subject(&email.subject), date);
+                    format!("hash:{}", hash_md5_prefix(&identity, 32))
+                }
+            };
+            groups.entry(key).or_default().push(email.file_path.clone());
+        }
+
+        let mut duplicates: Vec<Vec<PathBuf>> = groups
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .map(|mut paths| {
+                paths.sort();
+                paths
+            })
+            .collect();
+        duplicates.sort();
+        duplicates
+    }
+
+    /// Unique unsubscribe links of the Delete emails that are newsletters or
+    /// mailing lists, sorted: lists to leave rather than keep deleting.
+    pub fn unsubscribe_links(&self) -> Vec<String> {
+        let mut links: Vec<String> = self
+            .categories
+            .get(&Category::Delete)
+            .into_iter()
+            .flatten()
+            .filter(|email| matches!(email.email_type, EmailSortType::Newsletter | EmailSortType::MailingList))
+            .flat_map(|email| email.unsubscribe.iter().cloned())
+            .collect();
+        links.sort();
+        links.dedup();
+        links
+    }
+
+    /// Analyze one file and stream it to the JSON Lines output, if any.
+    fn analyze_and_stream(&self, file_path: &Path) -> Result<Option<EmailData>> {
+        let Some(email_data) = self.analyze_email_file(file_path)? else {
+            return Ok(None);
+        };
+        if let Some((_, writer)) = &self.jsonl {
+            let line = serde_json::to_vec(&email_data)?;
+            let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+            writer.write_all(&line)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(Some(email_data))
+    }
+
+    /// Analyze every markdown file, in parallel when the `rayon` feature is enabled.
+    #[cfg(feature = "rayon")]
+    fn analyze_files(&self) -> Result<Vec<EmailData>> {
+        let analyze = || -> Result<Vec<EmailData>> {
+            markdown_files(&self.base_directory)
+                .par_bridge()
+                .filter_map(|file_path| self.analyze_and_stream(&file_path).transpose())
+                .collect()
+        };
+
+        match self.threads {
+            Some(threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .context("Failed to start sorter thread pool")?
+                .install(analyze),
+            None => analyze(),
+        }
+    }
+
+    /// Analyze every markdown file sequentially.
+    #[cfg(not(feature = "rayon"))]
+    fn analyze_files(&self) -> Result<Vec<EmailData>> {
+        markdown_files(&self.base_directory)
+            .filter_map(|file_path| self.analyze_and_stream(&file_path).transpose())
+            .collect()
+    }
+
     /// Generate a sorting report.
     pub fn generate_report(&self) -> SortReport {
         let total = self.stats.total_emails as f64;
@@ -481,6 +824,7 @@ impl EmailSorter {
             let delete_pct = (self.stats.by_category.get("delete").unwrap_or(&0) * 100) as f64 / total;
             let summarize_pct = (self.stats.by_category.get("summarize").unwrap_or(&0) * 100) as f64 / total;
             let keep_pct = (self.stats.by_category.get("keep").unwrap_or(&0) * 100) as f64 / total;
+            let review_pct = (self.stats.by_category.get("review").unwrap_or(&0) * 100) as f64 / total;
 
             recommendations.insert(
                 "delete".to_string(),
@@ -494,11 +838,16 @@ impl EmailSorter {
                 "keep".to_string(),
                 format!("{:.1}% of emails should be kept in full", keep_pct),
             );
+            recommendations.insert(
+                "review".to_string(),
+                format!("{:.1}% of emails are borderline and need a manual review", review_pct),
+            );
         }
 
         // Get top senders
         let mut sender_counts: Vec<_> = self.stats.by_sender.iter().collect();
-        sender_counts.sort_by(|a, b| b.1.cmp(a.1));
+        // Ties broken by name so the report is stable across runs
+        sender_counts.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
         let top_senders: Vec<(String, usize)> = sender_counts
             .into_iter()
             .take(10)
@@ -524,6 +873,7 @@ impl EmailSorter {
                         .map(|d| d.format("%Y-%m-%d").to_string())
                         .unwrap_or_else(|| "Unknown".to_string()),
                     score: e.score,
+                    reasons: e.reasons.clone(),
                     email_type: e.email_type.to_string(),
                     size: e.file_size,
                     attachments: e.attachment_count,
@@ -533,9 +883,26 @@ impl EmailSorter {
             categories.insert(category.to_string(), summaries);
         }
 
+        let duplicates = self
+            .find_duplicates()
+            .into_iter()
+            .map(|paths| {
+                paths
+                    .iter()
+                    .map(|p| {
+                        p.strip_prefix(&self.base_directory)
+                            .unwrap_or(p)
+                            .to_string_lossy()
+                            .to_string()
+                    })
+                    .collect()
+            })
+            .collect();
+
         SortReport {
             summary: SortSummary {
                 total_emails: self.stats.total_emails,
+                skipped_empty: self.stats.skipped_empty,
                 categories: self.stats.by_category.clone(),
                 recommendations,
             },
@@ -545,6 +912,8 @@ impl EmailSorter {
                 by_date: self.stats.by_date.clone(),
             },
             categories,
+            duplicates,
+            unsubscribe: self.unsubscribe_links(),
         }
     }
 
@@ -553,10 +922,19 @@ impl EmailSorter {
         let output_path = self.base_directory.join(output_file);
         let content = serde_json::to_string_pretty(report)?;
         fs::write(&output_path, content)?;
-        println!("Report saved to: {}", output_path.display());
+        self.say(Verbosity::Normal, || format!("Report saved to: {}", output_path.display()));
         Ok(output_path)
     }
 
+    /// Stream one JSON object per analyzed email to `path` (JSON Lines) during
+    /// the next `sort_emails`, each line written as soon as its file is
+    /// analyzed, so the order follows the analysis threads.
+    pub fn write_jsonl(&mut self, path: &Path) -> Result<()> {
+        let file = fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        self.jsonl = Some((path.to_path_buf(), Mutex::new(std::io::BufWriter::new(file))));
+        Ok(())
+    }
+
     /// Print summary of sorting results.
     pub fn print_summary(&self) {
         println!("\n==================================================");
@@ -564,45 +942,60 @@ impl EmailSorter {
         println!("==================================================");
 
         println!("Total emails analyzed: {}", self.stats.total_emails);
-        println!(
-            "To delete: {}",
-            self.stats.by_category.get("delete").unwrap_or(&0)
-        );
-        println!(
-            "To summarize: {}",
-            self.stats.by_category.get("summarize").unwrap_or(&0)
-        );
-        println!(
-            "To keep: {}",
-            self.stats.by_category.get("keep").unwrap_or(&0)
-        );
+        if self.stats.skipped_empty > 0 {
+            println!("Skipped as empty: {}", self.stats.skipped_empty);
+        }
+        let color = use_color();
+        for (category, label) in [
+            (Category::Delete, "To delete:"),
+            (Category::Summarize, "To summarize:"),
+            (Category::Keep, "To keep:"),
+            (Category::Review, "To review:"),
+        ] {
+            let count = *self.stats.by_category.get(&category.to_string()).unwrap_or(&0);
+            let bar = count_bar(count, self.stats.total_emails, SUMMARY_BAR_WIDTH);
+            let line = format!("{:<13} {:>6} {}", label, count, bar);
+            println!("{}", paint(line.trim_end(), category.ansi_color(), color));
+        }
 
         if self.stats.total_emails > 0 {
             let total = self.stats.total_emails as f64;
             let delete_pct = (self.stats.by_category.get("delete").unwrap_or(&0) * 100) as f64 / total;
             let summarize_pct = (self.stats.by_category.get("summarize").unwrap_or(&0) * 100) as f64 / total;
             let keep_pct = (self.stats.by_category.get("keep").unwrap_or(&0) * 100) as f64 / total;
+            let review_pct = (self.stats.by_category.get("review").unwrap_or(&0) * 100) as f64 / total;
 
             println!("\nPercentages:");
             println!("   Delete: {:.1}%", delete_pct);
             println!("   Summarize: {:.1}%", summarize_pct);
             println!("   Keep: {:.1}%", keep_pct);
+            println!("   Review: {:.1}%", review_pct);
         }
 
         println!("\nEmail types found:");
         let mut types: Vec<_> = self.stats.by_type.iter().collect();
-        types.sort_by(|a, b| b.1.cmp(a.1));
+        types.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
         for (email_type, count) in types {
             println!("   {}: {}", email_type, count);
         }
 
         println!("\nTop senders:");
         let mut senders: Vec<_> = self.stats.by_sender.iter().collect();
-        senders.sort_by(|a, b| b.1.cmp(a.1));
+        senders.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
         for (sender, count) in senders.iter().take(5) {
             println!("   {}: {}", sender, count);
         }
 
+        let duplicates = self.find_duplicates();
+        if !duplicates.is_empty() {
+            println!("\nDuplicate groups: {} (see sort_report.json)", duplicates.len());
+        }
+
+        let unsubscribe = self.unsubscribe_links();
+        if !unsubscribe.is_empty() {
+            println!("\nUnsubscribe links: {} (see sort_report.json)", unsubscribe.len());
+        }
+
         println!("==================================================");
     }
 
@@ -615,6 +1008,102 @@ impl EmailSorter {
     pub fn stats(&self) -> &SortStats {
         &self.stats
     }
+
+    /// Write `<name>.summary.md` next to every email of the Summarize category.
+    ///
+    /// Existing summaries are overwritten. Returns the number written.
+    pub fn write_summaries(&self, summarizer: &dyn Summarizer) -> Result<usize> {
+        let Some(emails) = self.categories.get(&Category::Summarize) else {
+            return Ok(0);
+        };
+
+        for email in emails {
+            let content = fs::read_to_string(&email.file_path)
+                .with_context(|| format!("Failed to read {}", email.file_path.display()))?;
+            let body = extract_frontmatter(&content).map(|(_, body)| body).unwrap_or(content);
+            let summary = summarizer.summarize(email, &body)?;
+
+            let summary_path = summary_path(&email.file_path);
+            fs::write(
+                &summary_path,
+                format!(
+                    "# {}\n\nSource: [{}]({})\n\n{}\n",
+                    email.subject,
+                    email.file_name,
+                    email.file_name,
+                    summary.trim_end()
+                ),
+            )
+            .with_context(|| format!("Failed to write {}", summary_path.display()))?;
+        }
+
+        Ok(emails.len())
+    }
+}
+
+/// Produces the summary written for emails of the Summarize category.
+pub trait Summarizer {
+    fn summarize(&self, email: &EmailData, body: &str) -> Result<String>;
+}
+
+/// Summary made of the first paragraph of the body, cut at `max_chars`.
+///
+/// Quoted lines (`>`) are skipped. Needs no external service.
+pub struct TruncationSummarizer {
+    pub max_chars: usize,
+}
+
+impl Default for TruncationSummarizer {
+    fn default() -> Self {
+        TruncationSummarizer { max_chars: 500 }
+    }
+}
+
+impl Summarizer for TruncationSummarizer {
+    fn summarize(&self, _email: &EmailData, body: &str) -> Result<String> {
+        let paragraph = body
+            .split("\n\n")
+            .map(|p| {
+                p.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('>'))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .find(|p| !p.is_empty())
+            .unwrap_or_default();
+
+        // `max_chars` counts characters, not bytes
+        let max_bytes = paragraph
+            .char_indices()
+            .nth(self.max_chars)
+            .map_or(paragraph.len(), |(i, _)| i);
+        if max_bytes < paragraph.len() {
+            Ok(format!("{}…", truncate_on_char_boundary(&paragraph, max_bytes).trim_end()))
+        } else {
+            Ok(paragraph)
+        }
+    }
+}
+
+/// `<dir>/<stem>.summary.md` for `<dir>/<stem>.md`.
+fn summary_path(file_path: &Path) -> PathBuf {
+    let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+    file_path.with_file_name(format!("{}.summary.md", stem))
+}
+
+/// Email addresses of a frontmatter recipient field, written as a list or (before
+/// frontmatter version 2) as a single header string.
+fn addresses(fm: &Value, key: &str) -> Vec<String> {
+    match fm.get(key) {
+        Some(Value::String(header)) => extract_emails(Some(header)),
+        Some(Value::Sequence(values)) => values
+            .iter()
+            .filter_map(|v| v.as_str())
+            .flat_map(|v| extract_emails(Some(v)))
+            .collect(),
+        _ => Vec::new(),
+    }
 }
 
 /// Extract frontmatter and body from markdown content.
@@ -651,7 +1140,10 @@ fn extract_frontmatter(content: &str) -> Option<(String, String)> {
 }
 
 /// Parse date string into DateTime.
-fn parse_date(date_str: &str) -> Option<DateTime<FixedOffset>> {
+///
+/// Dates without an offset are read in `naive_offset`, or in the local
+/// timezone when `None`.
+fn parse_date(date_str: &str, naive_offset: Option<FixedOffset>) -> Option<DateTime<FixedOffset>> {
     if date_str.is_empty() {
         return None;
     }
@@ -662,18 +1154,20 @@ fn parse_date(date_str: &str) -> Option<DateTime<FixedOffset>> {
     }
 
     // Try other common formats
-    let formats = ["%Y-%m-%d", "%Y-%m-%d %H:%M:%S", "%d/%m/%Y", "%m/%d/%Y"];
-    for fmt in &formats {
-        if let Ok(naive) = chrono::NaiveDate::parse_from_str(date_str, fmt) {
-            let dt = naive
-                .and_hms_opt(0, 0, 0)?
-                .and_local_timezone(FixedOffset::east_opt(0)?)
-                .single()?;
-            return Some(dt);
-        }
+    let naive = NaiveDateTime::parse_from_str(date_str, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .or_else(|| {
+            ["%Y-%m-%d", "%d/%m/%Y", "%m/%d/%Y"]
+                .iter()
+                .find_map(|fmt| chrono::NaiveDate::parse_from_str(date_str, fmt).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+
+    match naive_offset {
+        Some(offset) => naive.and_local_timezone(offset).single(),
+        // Earliest: a midnight skipped by a DST change still resolves
+        None => naive.and_local_timezone(Local).earliest().map(|dt| dt.fixed_offset()),
     }
-
-    None
 }
 
 #[cfg(test)]
@@ -691,22 +1185,444 @@ mod tests {
         assert!(body.contains("Body content"));
     }
 
+    #[test]
+    fn test_summary_bars_and_colors() {
+        assert_eq!(count_bar(0, 10, 30), "");
+        assert_eq!(count_bar(5, 10, 30), "#".repeat(15));
+        assert_eq!(count_bar(10, 10, 30), "#".repeat(30));
+        assert_eq!(count_bar(1, 1000, 30), "#");
+        assert_eq!(count_bar(3, 0, 30), "");
+
+        assert_eq!(paint("To keep: 3", Category::Keep.ansi_color(), false), "To keep: 3");
+        assert_eq!(paint("To keep: 3", Category::Keep.ansi_color(), true), "\x1b[32mTo keep: 3\x1b[0m");
+    }
+
+    #[test]
+    fn test_tab_indented_frontmatter() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default());
+        let frontmatter = "from: alice@example.com\nsubject: Lunch\nto:\n\t- bob@example.com\n\t- carol@example.com\n";
+
+        let email = analyze(&sorter, temp.path(), frontmatter, "See you at noon.");
+        assert_eq!(email.subject, "Lunch");
+        assert_eq!(email.recipients, vec!["bob@example.com", "carol@example.com"]);
+    }
+
     #[test]
     fn test_parse_date_iso() {
-        let result = parse_date("2024-01-15T10:30:00+00:00");
+        let result = parse_date("2024-01-15T10:30:00+00:00", None);
         assert!(result.is_some());
     }
 
     #[test]
     fn test_parse_date_simple() {
-        let result = parse_date("2024-01-15");
+        let result = parse_date("2024-01-15", None);
         assert!(result.is_some());
     }
 
+    #[test]
+    fn test_parse_date_naive_offset() {
+        let offset = FixedOffset::east_opt(14 * 3600);
+        let result = parse_date("2024-01-01", offset).unwrap();
+        assert_eq!(result.to_rfc3339(), "2024-01-01T00:00:00+14:00");
+        // Still Dec 31 in UTC: assuming UTC would be off by a day
+        assert_eq!(result.with_timezone(&Utc).to_rfc3339(), "2023-12-31T10:00:00+00:00");
+
+        let result = parse_date("2024-01-01 23:30:00", offset).unwrap();
+        assert_eq!(result.to_rfc3339(), "2024-01-01T23:30:00+14:00");
+    }
+
+    #[test]
+    fn test_age_days_naive_date_near_midnight() {
+        let temp = tempfile::TempDir::new().unwrap();
+
+        // At the extremes of the offset range, "yesterday" in that zone is a
+        // different UTC day for half of the day; the age must still be 1.
+        for minutes in [14 * 60, -12 * 60] {
+            let config = SortConfig {
+                naive_date_utc_offset_minutes: Some(minutes),
+                ..Default::default()
+            };
+            let sorter = EmailSorter::new(temp.path().to_path_buf(), config);
+
+            let offset = FixedOffset::east_opt(minutes * 60).unwrap();
+            let yesterday = Utc::now().with_timezone(&offset).date_naive() - chrono::Duration::days(1);
+            let frontmatter = format!(
+                "from: a@example.com\nsubject: Hello\ndate: '{}'\n",
+                yesterday.format("%Y-%m-%d")
+            );
+
+            let email = analyze(&sorter, temp.path(), &frontmatter, "Body of the email");
+            assert_eq!(email.age_days, Some(1), "offset {} minutes", minutes);
+        }
+    }
+
     #[test]
     fn test_category_display() {
         assert_eq!(Category::Delete.to_string(), "delete");
         assert_eq!(Category::Summarize.to_string(), "summarize");
         assert_eq!(Category::Keep.to_string(), "keep");
     }
+
+    fn analyze(sorter: &EmailSorter, dir: &Path, frontmatter: &str, body: &str) -> EmailData {
+        let path = dir.join("email.md");
+        fs::write(&path, format!("---\n{}---\n\n{}", frontmatter, body)).unwrap();
+        sorter.analyze_email_file(&path).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_newsletter_deleted_only_when_old() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default());
+
+        let recent = (Utc::now() - chrono::Duration::days(2)).to_rfc3339();
+        let old = (Utc::now() - chrono::Duration::days(400)).to_rfc3339();
+        let frontmatter = |date: &str| {
+            format!("from: news@shop.example\nsubject: Weekly Newsletter\ndate: '{}'\n", date)
+        };
+
+        let email = analyze(&sorter, temp.path(), &frontmatter(&recent), "This week in news.");
+        assert_eq!(email.email_type, EmailSortType::Newsletter);
+        assert_eq!(email.category, Category::Summarize);
+
+        let email = analyze(&sorter, temp.path(), &frontmatter(&old), "This week in news.");
+        assert_eq!(email.category, Category::Delete);
+    }
+
+    #[test]
+    fn test_keep_sender_overrides_newsletter() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let config = SortConfig {
+            keep_senders: vec!["shop.example".into()],
+            delete_senders: vec!["news@".into()],
+            ..Default::default()
+        };
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), config);
+
+        let old = (Utc::now() - chrono::Duration::days(400)).to_rfc3339();
+        let frontmatter = format!(
+            "from: news@shop.example\nsubject: Weekly Newsletter - unsubscribe\ndate: '{}'\n",
+            old
+        );
+        let email = analyze(&sorter, temp.path(), &frontmatter, "This week in news.");
+        assert_eq!(email.email_type, EmailSortType::Newsletter);
+        assert_eq!(email.category, Category::Keep);
+    }
+
+    #[test]
+    fn test_keep_score_threshold() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let recent = (Utc::now() - chrono::Duration::days(2)).to_rfc3339();
+        let frontmatter = format!("from: alice@example.com\nsubject: Lunch\ndate: '{}'\n", recent);
+        // Between the small and summarize thresholds: no size bonus or penalty
+        let body = "Shall we meet at noon on Friday at the usual place near the office? ".repeat(10);
+
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default());
+        let email = analyze(&sorter, temp.path(), &frontmatter, &body);
+        assert_eq!(email.score, 3);
+        assert_eq!(email.category, Category::Keep);
+
+        let config = SortConfig {
+            keep_score_threshold: 5,
+            ..Default::default()
+        };
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), config);
+        let email = analyze(&sorter, temp.path(), &frontmatter, &body);
+        assert_ne!(email.category, Category::Keep);
+    }
+
+    #[test]
+    fn test_score_reasons() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default());
+
+        let old = (Utc::now() - chrono::Duration::days(400)).to_rfc3339();
+        let frontmatter = format!(
+            "from: news@shop.example\nsubject: Newsletter - summer sale\ndate: '{}'\n",
+            old
+        );
+        let email = analyze(&sorter, temp.path(), &frontmatter, "Deals inside.");
+
+        let reasons: Vec<(&str, i32)> = email.reasons.iter().map(|(r, p)| (r.as_str(), *p)).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                ("newsletter", -2),
+                ("old", -1),
+                ("small", -1),
+                ("delete keyword 'newsletter'", -1),
+                ("delete keyword 'sale'", -1),
+            ]
+        );
+        assert_eq!(email.score, -6);
+    }
+
+    #[test]
+    fn test_regex_keywords() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let config = SortConfig {
+            delete_keywords: vec!["/\\bsale\\b/".into(), "coupon".into()],
+            keep_keywords: vec!["/invoice #\\d+/".into()],
+            ..Default::default()
+        };
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), config);
+        let subject_reasons = |subject: &str| {
+            let email = analyze(
+                &sorter,
+                temp.path(),
+                &format!("from: a@example.com\nsubject: '{}'\n", subject),
+                "Body of the email",
+            );
+            email
+                .reasons
+                .into_iter()
+                .map(|(r, _)| r)
+                .filter(|r| r.contains("keyword"))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(subject_reasons("Big SALE today"), vec!["delete keyword '/\\bsale\\b/'"]);
+        assert!(subject_reasons("Wholesale prices").is_empty());
+        assert_eq!(subject_reasons("Your coupons"), vec!["delete keyword 'coupon'"]);
+        assert_eq!(subject_reasons("Invoice #123"), vec!["keep keyword '/invoice #\\d+/'"]);
+        assert!(subject_reasons("Invoice pending").is_empty());
+    }
+
+    #[test]
+    fn test_accent_insensitive_keywords() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let frontmatter = "from: a@example.com\nsubject: Votre Résumé\n";
+
+        let config = SortConfig {
+            keep_keywords: vec!["resume".into()],
+            ..Default::default()
+        };
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), config.clone());
+        let email = analyze(&sorter, temp.path(), frontmatter, "Body of the email");
+        assert!(!email.reasons.iter().any(|(r, _)| r == "keep keyword 'resume'"));
+
+        let config = SortConfig {
+            accent_insensitive: true,
+            ..config
+        };
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), config);
+        let email = analyze(&sorter, temp.path(), frontmatter, "Body of the email");
+        assert!(email.reasons.iter().any(|(r, _)| r == "keep keyword 'resume'"));
+        assert_eq!(email.category, Category::Keep);
+    }
+
+    #[test]
+    fn test_group_from_recipients() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default());
+
+        let email = analyze(
+            &sorter,
+            temp.path(),
+            "from: a@example.com\nsubject: Plan\nto:\n- Bob <bob@example.com>\ncc:\n- carol@example.com\n- Dave <dave@example.com>\n",
+            "Body of the email",
+        );
+        assert_eq!(email.email_type, EmailSortType::Group);
+        assert_eq!(email.recipients, vec!["bob@example.com", "carol@example.com", "dave@example.com"]);
+
+        // Single-string `to` from older exports
+        let email = analyze(&sorter, temp.path(), "from: a@example.com\nsubject: Plan\nto: bob@example.com\n", "Body of the email");
+        assert_eq!(email.email_type, EmailSortType::Direct);
+        assert_eq!(email.recipients, vec!["bob@example.com"]);
+    }
+
+    #[test]
+    fn test_auto_reply_scores_toward_delete() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default());
+        let frontmatter = "from: bob@example.com\nsubject: 'Absence : Re: Planning'\nto: alice@example.com\n";
+
+        let email = analyze(&sorter, temp.path(), &format!("{}auto_submitted: auto-replied\n", frontmatter), "Je suis absent jusqu'au 3 mars.");
+        assert!(email.auto_reply);
+        assert!(email.reasons.contains(&("auto-reply".to_string(), -3)));
+        assert_eq!(email.category, Category::Delete);
+
+        let email = analyze(&sorter, temp.path(), frontmatter, "Je suis absent jusqu'au 3 mars.");
+        assert!(!email.auto_reply);
+        assert_ne!(email.category, Category::Delete);
+    }
+
+    #[test]
+    fn test_write_summaries() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let recent = Utc::now().format("%Y-%m-%dT%H:%M:%S+00:00").to_string();
+        let write = |name: &str, subject: &str, body: &str| {
+            fs::write(
+                temp.path().join(name),
+                format!("---\nfrom: alice@example.com\nsubject: {}\ndate: '{}'\n---\n\n{}", subject, recent, body),
+            )
+            .unwrap();
+        };
+        let body = format!("> quoted reply\nProject update:\nall tasks on track.\n\n{}", "More details. ".repeat(20));
+        write("update.md", "Project update", &body);
+        write("lunch.md", "Lunch", "Yes, see you at noon");
+
+        let config = SortConfig {
+            small_email_threshold: 0,
+            keep_score_threshold: 100,
+            delete_score_threshold: -100,
+            ..Default::default()
+        };
+        let mut sorter = EmailSorter::new(temp.path().to_path_buf(), config);
+        sorter.sort_emails().unwrap();
+        assert_eq!(sorter.categories()[&Category::Summarize].len(), 2);
+
+        let written = sorter.write_summaries(&TruncationSummarizer { max_chars: 20 }).unwrap();
+        assert_eq!(written, 2);
+        let summary = fs::read_to_string(temp.path().join("update.summary.md")).unwrap();
+        assert_eq!(summary, "# Project update\n\nSource: [update.md](update.md)\n\nProject update: all…\n");
+
+        // Summaries are not sorted as emails
+        let mut sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default());
+        sorter.sort_emails().unwrap();
+        assert_eq!(sorter.stats().total_emails, 2);
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let write = |name: &str, frontmatter: &str| {
+            fs::write(temp.path().join(name), format!("---\n{}---\n\nBody of the email", frontmatter)).unwrap();
+        };
+
+        // Same Message-Id in two folders
+        fs::create_dir(temp.path().join("Sent")).unwrap();
+        write("a.md", "from: a@x.com\nsubject: Hi\nmessage_id: id1@x.com\n");
+        write("Sent/a.md", "from: a@x.com\nsubject: Hi (copy)\nmessage_id: id1@x.com\n");
+        // No Message-Id: same sender, subject (prefixes aside) and date
+        write("b.md", "from: b@x.com\nsubject: Report\ndate: '2024-01-01T10:00:00+00:00'\n");
+        write("b2.md", "from: b@x.com\nsubject: 'TR: Report'\ndate: '2024-01-01T10:00:00+00:00'\n");
+        write("c.md", "from: b@x.com\nsubject: Report\ndate: '2024-01-02T10:00:00+00:00'\n");
+        // Same body, different metadata
+        write("d.md", "from: d@x.com\nsubject: Fwd\nbody_hash: abc123\n");
+        write("d2.md", "from: e@x.com\nsubject: Copy\nbody_hash: abc123\n");
+
+        let mut sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default());
+        sorter.sort_emails().unwrap();
+
+        let duplicates = sorter.find_duplicates();
+        assert_eq!(
+            duplicates,
+            vec![
+                vec![temp.path().join("Sent/a.md"), temp.path().join("a.md")],
+                vec![temp.path().join("b.md"), temp.path().join("b2.md")],
+                vec![temp.path().join("d.md"), temp.path().join("d2.md")],
+            ]
+        );
+        assert_eq!(sorter.generate_report().duplicates[1], vec!["b.md", "b2.md"]);
+    }
+
+    #[test]
+    fn test_unsubscribe_links() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let old = (Utc::now() - chrono::Duration::days(400)).to_rfc3339();
+        let recent = (Utc::now() - chrono::Duration::days(2)).to_rfc3339();
+        let write = |name: &str, date: &str, links: &str| {
+            fs::write(
+                temp.path().join(name),
+                format!(
+                    "---\nfrom: news@shop.example\nsubject: Weekly Newsletter\ndate: '{}'\nunsubscribe:\n{}---\n\nThis week in news.",
+                    date, links
+                ),
+            )
+            .unwrap();
+        };
+        write("a.md", &old, "- https://shop.example/unsub\n- mailto:unsub@shop.example\n");
+        write("b.md", &old, "- https://shop.example/unsub\n");
+        // Recent: summarized, not deleted
+        write("c.md", &recent, "- https://other.example/unsub\n");
+
+        let mut sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default());
+        sorter.sort_emails().unwrap();
+
+        let expected = vec!["https://shop.example/unsub", "mailto:unsub@shop.example"];
+        assert_eq!(sorter.unsubscribe_links(), expected);
+        assert_eq!(sorter.generate_report().unsubscribe, expected);
+    }
+
+    #[test]
+    fn test_skip_below_chars() {
+        let temp = tempfile::TempDir::new().unwrap();
+        // A read receipt: a long frontmatter, but a 13-character body
+        let noise = "---\nfrom: postmaster@mail.example.com\nsubject: Read receipt for your message about Friday\n---\n\nDisplayed ok.";
+        assert!(noise.chars().count() > 100);
+        fs::write(temp.path().join("receipt.md"), noise).unwrap();
+        let real = format!("---\nfrom: b@x.com\nsubject: Plan\n---\n\n{}", "Let us meet on Friday. ".repeat(5));
+        fs::write(temp.path().join("plan.md"), real).unwrap();
+
+        let config = SortConfig {
+            skip_below_chars: 100,
+            ..Default::default()
+        };
+        let mut sorter = EmailSorter::new(temp.path().to_path_buf(), config).with_verbosity(Verbosity::Quiet);
+        sorter.sort_emails().unwrap();
+        assert_eq!(sorter.stats().total_emails, 1);
+        assert_eq!(sorter.stats().skipped_empty, 1);
+        assert_eq!(sorter.generate_report().summary.skipped_empty, 1);
+
+        // Default threshold (10): the 13-character receipt is sorted, a 3-character body is not
+        fs::write(temp.path().join("ok.md"), "---\nfrom: c@x.com\nsubject: Re: Plan\n---\n\nOk.\n").unwrap();
+        assert_eq!(SortConfig::default().skip_below_chars, 10);
+        let mut sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default()).with_verbosity(Verbosity::Quiet);
+        sorter.sort_emails().unwrap();
+        assert_eq!(sorter.stats().total_emails, 2);
+        assert_eq!(sorter.stats().skipped_empty, 1);
+    }
+
+    #[test]
+    fn test_write_jsonl() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path().join("mails");
+        fs::create_dir(&dir).unwrap();
+        for (name, subject) in [("a.md", "Lunch"), ("b.md", "Weekly Newsletter"), ("c.md", "Contract")] {
+            fs::write(dir.join(name), format!("---\nfrom: a@x.com\nsubject: {}\n---\n\nBody text of the email", subject)).unwrap();
+        }
+        fs::write(dir.join("empty.md"), "").unwrap();
+
+        let path = temp.path().join("emails.jsonl");
+        let mut sorter = EmailSorter::new(dir, SortConfig::default()).with_verbosity(Verbosity::Quiet);
+        sorter.write_jsonl(&path).unwrap();
+        sorter.sort_emails().unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let mut lines: Vec<serde_json::Value> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), sorter.stats().total_emails);
+        assert_eq!(lines.len(), 3);
+
+        // Written in analysis order
+        lines.sort_by_key(|line| line["subject"].as_str().unwrap().to_string());
+        assert_eq!(lines[0]["subject"], "Contract");
+        assert_eq!(lines[1]["subject"], "Lunch");
+        assert_eq!(lines[2]["email_type"], "newsletter");
+    }
+
+    #[test]
+    fn test_review_margin() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let recent = (Utc::now() - chrono::Duration::days(2)).to_rfc3339();
+        let frontmatter = format!("from: alice@example.com\nsubject: Lunch\ndate: '{}'\n", recent);
+        let body = "Shall we meet at noon on Friday at the usual place near the office? ".repeat(10);
+
+        // Score 3 is one above the keep threshold
+        let config = SortConfig {
+            review_margin: 1,
+            ..Default::default()
+        };
+        let mut sorter = EmailSorter::new(temp.path().to_path_buf(), config);
+        let email = analyze(&sorter, temp.path(), &frontmatter, &body);
+        assert_eq!(email.score, 3);
+        assert_eq!(email.category, Category::Review);
+
+        sorter.sort_emails().unwrap();
+        assert_eq!(sorter.stats().by_category["review"], 1);
+        let report = sorter.generate_report();
+        assert!(report.summary.recommendations.contains_key("review"));
+        assert_eq!(report.categories["review"].len(), 1);
+    }
 }
+
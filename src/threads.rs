@@ -0,0 +1,276 @@
+//! Conversation threads rebuilt from exported markdown files.
+//!
+//! Uses the `message_id`, `in_reply_to` and `references` frontmatter fields
+//! written by the exporter. Messages without any known relative form
+//! singleton threads.
+
+use crate::fix_yaml::extract_frontmatter;
+use crate::utils::normalize_subject;
+use anyhow::Result;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Frontmatter fields needed to link messages together.
+#[derive(Debug, Default, Deserialize)]
+struct ThreadFrontmatter {
+    #[serde(default)]
+    message_id: Option<String>,
+    #[serde(default)]
+    in_reply_to: Option<String>,
+    #[serde(default)]
+    references: Vec<String>,
+    #[serde(default)]
+    subject: String,
+    #[serde(default)]
+    date: String,
+}
+
+/// A message placed in a thread.
+#[derive(Debug, Clone)]
+pub struct ThreadMessage {
+    pub path: PathBuf,
+    pub message_id: Option<String>,
+    pub subject: String,
+    pub date: String,
+    /// Reply depth (0 for the thread root).
+    pub depth: usize,
+}
+
+/// A conversation: messages in parent/child order (each parent before its replies).
+#[derive(Debug, Clone)]
+pub struct Thread {
+    pub messages: Vec<ThreadMessage>,
+}
+
+impl Thread {
+    /// The first message of the thread.
+    pub fn root(&self) -> &ThreadMessage {
+        &self.messages[0]
+    }
+}
+
+/// Read every exported markdown file under `dir` and group messages into threads.
+///
+/// Threads are ordered by the date of their root; replies are ordered by date.
+pub fn build_threads(dir: &Path) -> Result<Vec<Thread>> {
+    let mut entries: Vec<(PathBuf, ThreadFrontmatter)> = Vec::new();
+
+    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "md") {
+            continue;
+        }
+
+        let content = fs::read_to_string(path)?;
+        let fm = extract_frontmatter(&content)
+            .and_then(|(frontmatter, _)| serde_yaml::from_str::<ThreadFrontmatter>(&frontmatter).ok());
+        if let Some(fm) = fm {
+            entries.push((path.to_path_buf(), fm));
+        }
+    }
+
+    // Stable order: by date, then path
+    entries.sort_by(|a, b| a.1.date.cmp(&b.1.date).then_with(|| a.0.cmp(&b.0)));
+
+    let links: Vec<ThreadLinks> = entries
+        .iter()
+        .map(|(_, fm)| ThreadLinks {
+            message_id: fm.message_id.as_deref(),
+            in_reply_to: fm.in_reply_to.as_deref(),
+            references: &fm.references,
+            subject: &fm.subject,
+        })
+        .collect();
+
+    let threads = group_threads(&links)
+        .into_iter()
+        .map(|members| Thread {
+            messages: members
+                .into_iter()
+                .map(|(i, depth)| {
+                    let (path, fm) = &entries[i];
+                    ThreadMessage {
+                        path: path.clone(),
+                        message_id: fm.message_id.clone(),
+                        subject: fm.subject.clone(),
+                        date: fm.date.clone(),
+                        depth,
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(threads)
+}
+
+/// Threading headers of one message.
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadLinks<'a> {
+    pub message_id: Option<&'a str>,
+    pub in_reply_to: Option<&'a str>,
+    pub references: &'a [String],
+    pub subject: &'a str,
+}
+
+/// Group messages into threads of `(index, depth)` pairs, parents before replies.
+///
+/// `links` should be in date order: threads come out in the order of their
+/// root and replies in their own order. A reply (`Re:`, `Fwd:`...) whose
+/// headers link to nothing exported joins the earliest message with the same
+/// [normalized subject](normalize_subject).
+pub fn group_threads(links: &[ThreadLinks]) -> Vec<Vec<(usize, usize)>> {
+    let by_id: HashMap<&str, usize> = links
+        .iter()
+        .enumerate()
+        .filter_map(|(i, l)| l.message_id.map(|id| (id, i)))
+        .collect();
+
+    // Parent: In-Reply-To if exported, else the closest exported reference
+    let parents: Vec<Option<usize>> = links
+        .iter()
+        .enumerate()
+        .map(|(i, l)| {
+            l.in_reply_to
+                .into_iter()
+                .chain(l.references.iter().rev().map(String::as_str))
+                .filter_map(|id| by_id.get(id).copied())
+                .find(|&p| p != i)
+        })
+        .collect();
+
+    // Earliest message of each normalized subject
+    let normalized: Vec<String> = links.iter().map(|l| normalize_subject(l.subject)).collect();
+    let subjects: Vec<String> = normalized.iter().map(|s| s.to_lowercase()).collect();
+    let mut first_by_subject: HashMap<&str, usize> = HashMap::new();
+    for (i, subject) in subjects.iter().enumerate() {
+        if !subject.is_empty() {
+            first_by_subject.entry(subject).or_insert(i);
+        }
+    }
+    let parents: Vec<Option<usize>> = parents
+        .into_iter()
+        .enumerate()
+        .map(|(i, parent)| {
+            let is_reply = normalized[i] != links[i].subject.trim();
+            parent.or_else(|| {
+                is_reply
+                    .then(|| first_by_subject.get(subjects[i].as_str()).copied())
+                    .flatten()
+                    .filter(|&p| p < i)
+            })
+        })
+        .collect();
+
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); links.len()];
+    for (i, parent) in parents.iter().enumerate() {
+        if let Some(p) = parent {
+            children[*p].push(i);
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut threads = Vec::new();
+
+    // Roots first, then anything left over (only reachable through a reference cycle)
+    let roots = (0..links.len())
+        .filter(|&i| parents[i].is_none())
+        .chain(0..links.len());
+
+    for root in roots {
+        if visited.contains(&root) {
+            continue;
+        }
+
+        let mut members = Vec::new();
+        let mut stack = vec![(root, 0usize)];
+        while let Some((i, depth)) = stack.pop() {
+            if !visited.insert(i) {
+                continue;
+            }
+
+            members.push((i, depth));
+
+            // Reverse so the earliest reply is popped first
+            for &child in children[i].iter().rev() {
+                stack.push((child, depth + 1));
+            }
+        }
+
+        threads.push(members);
+    }
+
+    threads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_email(dir: &Path, name: &str, frontmatter: &str) {
+        fs::write(dir.join(name), format!("---\n{}---\n\nBody\n", frontmatter)).unwrap();
+    }
+
+    #[test]
+    fn test_build_threads() {
+        let temp = TempDir::new().unwrap();
+        write_email(
+            temp.path(),
+            "a.md",
+            "subject: Plan\ndate: 2024-01-01T10:00:00+00:00\nmessage_id: a@x\n",
+        );
+        write_email(
+            temp.path(),
+            "c.md",
+            "subject: 'Re: Re: Plan'\ndate: 2024-01-03T10:00:00+00:00\nmessage_id: c@x\nin_reply_to: b@x\nreferences:\n- a@x\n- b@x\n",
+        );
+        write_email(
+            temp.path(),
+            "b.md",
+            "subject: 'Re: Plan'\ndate: 2024-01-02T10:00:00+00:00\nmessage_id: b@x\nin_reply_to: a@x\nreferences:\n- a@x\n",
+        );
+        write_email(
+            temp.path(),
+            "lone.md",
+            "subject: Other\ndate: 2024-01-05T10:00:00+00:00\nmessage_id: z@x\n",
+        );
+
+        let threads = build_threads(temp.path()).unwrap();
+        assert_eq!(threads.len(), 2);
+
+        let ids: Vec<_> = threads[0]
+            .messages
+            .iter()
+            .map(|m| (m.message_id.as_deref().unwrap(), m.depth))
+            .collect();
+        assert_eq!(ids, vec![("a@x", 0), ("b@x", 1), ("c@x", 2)]);
+
+        assert_eq!(threads[1].messages.len(), 1);
+        assert_eq!(threads[1].root().subject, "Other");
+    }
+
+    #[test]
+    fn test_group_threads_by_subject() {
+        let references = Vec::new();
+        let links: Vec<ThreadLinks> = ["Budget 2024", "RE : budget 2024", "Budget 2024", "TR: Re: Budget 2024", "Other"]
+            .iter()
+            .enumerate()
+            .map(|(i, subject)| ThreadLinks {
+                message_id: None,
+                in_reply_to: (i == 4).then_some("missing@x"),
+                references: &references,
+                subject,
+            })
+            .collect();
+
+        // Replies join the first "Budget 2024"; a second bare subject starts its own thread
+        assert_eq!(
+            group_threads(&links),
+            vec![vec![(0, 0), (1, 1), (3, 1)], vec![(2, 0)], vec![(4, 0)]]
+        );
+    }
+}
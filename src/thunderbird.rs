@@ -3,14 +3,16 @@ use anyhow::{Context, Result};
 use base64::{engine::general_purpose::STANDARD, Engine};
 use libloading::Library;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::ffi::CString;
 use std::fs;
 use std::os::raw::{c_char, c_void};
 use std::path::{Path, PathBuf};
 
-use crate::config::Account;
+use crate::config::{
+    Account, AccountAuth, FilenameRecipient, MissingDatePolicy, QuoteDepth, RawAccount, Settings,
+};
 
 /// Thunderbird profile information
 #[derive(Debug, Clone)]
@@ -159,8 +161,31 @@ fn scan_profile_directories(profiles_dir: &Path) -> Result<Vec<ThunderbirdProfil
     Ok(profiles)
 }
 
+/// A Thunderbird server that was not imported because it is not IMAP
+#[derive(Debug, Clone)]
+pub struct SkippedServer {
+    pub name: String,
+    /// Thunderbird server type: `pop3`, `ews`, `nntp`, ...
+    pub server_type: String,
+    pub hostname: Option<String>,
+}
+
+/// Result of reading a profile's prefs.js
+#[derive(Debug, Default)]
+pub struct PrefsImport {
+    /// Importable IMAP accounts
+    pub accounts: Vec<Account>,
+    /// Non-IMAP servers (POP, Exchange, ...) that were left out
+    pub skipped: Vec<SkippedServer>,
+}
+
 /// Extract IMAP accounts from a Thunderbird profile
 pub fn extract_accounts(profile: &ThunderbirdProfile) -> Result<Vec<Account>> {
+    Ok(extract_import(profile)?.accounts)
+}
+
+/// Extract IMAP accounts and report the servers that cannot be imported
+pub fn extract_import(profile: &ThunderbirdProfile) -> Result<PrefsImport> {
     let prefs_file = profile.path.join("prefs.js");
 
     if !prefs_file.exists() {
@@ -173,30 +198,152 @@ pub fn extract_accounts(profile: &ThunderbirdProfile) -> Result<Vec<Account>> {
     parse_prefs_js(&content)
 }
 
-/// Parse prefs.js and extract IMAP account configurations
-fn parse_prefs_js(content: &str) -> Result<Vec<Account>> {
-    let mut servers: HashMap<String, HashMap<String, String>> = HashMap::new();
+/// Parse every `user_pref("key", value);` statement of prefs.js into a key → value map.
+///
+/// String values may contain commas, parentheses, escaped quotes (`\"`) and
+/// backslashes (`\\`), and may span several lines.
+fn parse_user_prefs(content: &str) -> Result<HashMap<String, String>> {
+    let mut prefs = HashMap::new();
+    let mut rest = content;
 
     // Pattern: user_pref("mail.server.server1.property", "value");
-    let re = Regex::new(r#"user_pref\("mail\.server\.([^.]+)\.([^"]+)",\s*"?([^")]+)"?\);"#)?;
+    while let Some(start) = rest.find("user_pref(") {
+        rest = &rest[start + "user_pref(".len()..];
+
+        let Some((key, after_key)) = parse_js_string(rest.trim_start()) else {
+            continue;
+        };
+        let Some(after_comma) = after_key.trim_start().strip_prefix(',') else {
+            continue;
+        };
+        let value_start = after_comma.trim_start();
+
+        let (value, after_value) = match parse_js_string(value_start) {
+            Some(parsed) => parsed,
+            None => {
+                // Bare value: number or boolean
+                let end = value_start.find(')').unwrap_or(value_start.len());
+                (value_start[..end].trim().to_string(), &value_start[end..])
+            }
+        };
+
+        prefs.insert(key, value);
+        rest = after_value;
+    }
+
+    Ok(prefs)
+}
 
-    for cap in re.captures_iter(content) {
-        let server_id = cap.get(1).map(|m| m.as_str()).unwrap_or("");
-        let property = cap.get(2).map(|m| m.as_str()).unwrap_or("");
-        let value = cap.get(3).map(|m| m.as_str()).unwrap_or("");
+/// Parse a double-quoted JS string literal at the start of `input`.
+///
+/// Returns the unescaped value and the remaining input after the closing quote.
+fn parse_js_string(input: &str) -> Option<(String, &str)> {
+    let body = input.strip_prefix('"')?;
+    let mut value = String::new();
+    let mut chars = body.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &body[i + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                other => value.push(other),
+            },
+            _ => value.push(c),
+        }
+    }
+
+    None
+}
+
+/// Group prefs under `prefix` by id: `mail.server.server1.hostname` → `server1` → `hostname`
+fn group_prefs(
+    prefs: &HashMap<String, String>,
+    prefix: &str,
+) -> HashMap<String, HashMap<String, String>> {
+    let mut groups: HashMap<String, HashMap<String, String>> = HashMap::new();
+
+    for (key, value) in prefs {
+        if let Some((id, property)) = key
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.split_once('.'))
+        {
+            groups
+                .entry(id.to_string())
+                .or_default()
+                .insert(property.to_string(), value.clone());
+        }
+    }
+
+    groups
+}
 
-        servers
-            .entry(server_id.to_string())
-            .or_default()
-            .insert(property.to_string(), value.to_string());
+/// Link each server id to its account's default (first) identity.
+///
+/// Follows `mail.accountmanager.accounts` → `mail.account.X.server` /
+/// `mail.account.X.identities` → `mail.identity.Y.*`.
+fn identities_by_server(
+    prefs: &HashMap<String, String>,
+) -> HashMap<String, HashMap<String, String>> {
+    let accounts = group_prefs(prefs, "mail.account.");
+    let identities = group_prefs(prefs, "mail.identity.");
+
+    // Prefer the account manager order; fall back to every mail.account.* entry
+    let account_ids: Vec<String> = match prefs.get("mail.accountmanager.accounts") {
+        Some(list) => list.split(',').map(|s| s.trim().to_string()).collect(),
+        None => accounts.keys().cloned().collect(),
+    };
+
+    let mut linked = HashMap::new();
+
+    for account_id in account_ids {
+        let Some(account) = accounts.get(&account_id) else {
+            continue;
+        };
+        let Some(server_id) = account.get("server") else {
+            continue;
+        };
+        let identity = account
+            .get("identities")
+            .and_then(|ids| ids.split(',').next())
+            .and_then(|id| identities.get(id.trim()));
+
+        if let Some(identity) = identity {
+            linked.insert(server_id.clone(), identity.clone());
+        }
     }
 
+    linked
+}
+
+/// Parse prefs.js into IMAP accounts plus the non-IMAP servers that were skipped
+fn parse_prefs_js(content: &str) -> Result<PrefsImport> {
+    let prefs = parse_user_prefs(content)?;
+    let identities = identities_by_server(&prefs);
+
+    // Sort by server id so the generated accounts.yaml is stable run-to-run
+    let mut servers: Vec<_> = group_prefs(&prefs, "mail.server.").into_iter().collect();
+    servers.sort_by(|(a, _), (b, _)| server_id_order(a, b));
+
     let mut accounts = Vec::new();
+    let mut skipped = Vec::new();
+    let mut used_dir_names = HashSet::new();
 
     for (server_id, props) in servers {
-        // Only process IMAP accounts
+        // Only process IMAP accounts ("none" is Local Folders, not an account)
         let server_type = props.get("type").map(|s| s.as_str()).unwrap_or("");
         if server_type != "imap" {
+            if !server_type.is_empty() && server_type != "none" {
+                skipped.push(SkippedServer {
+                    name: props
+                        .get("name")
+                        .cloned()
+                        .unwrap_or_else(|| format!("Account_{}", server_id)),
+                    server_type: server_type.to_string(),
+                    hostname: props.get("hostname").cloned(),
+                });
+            }
             continue;
         }
 
@@ -205,19 +352,29 @@ fn parse_prefs_js(content: &str) -> Result<Vec<Account>> {
             None => continue,
         };
 
-        let username = props.get("userName").cloned().unwrap_or_default();
+        // The identity's email is a better name than the server nickname
+        let identity_email = identities
+            .get(&server_id)
+            .and_then(|identity| identity.get("useremail"))
+            .filter(|email| !email.is_empty())
+            .cloned();
+
+        let username = props
+            .get("userName")
+            .cloned()
+            .or_else(|| identity_email.clone())
+            .unwrap_or_default();
         let port = props
             .get("port")
             .and_then(|p| p.parse().ok())
             .unwrap_or(993);
 
-        let name = props
-            .get("name")
-            .cloned()
+        let name = identity_email
+            .or_else(|| props.get("name").cloned())
             .unwrap_or_else(|| format!("Account_{}", server_id));
 
         // Clean the name for use as export directory
-        let safe_name = sanitize_name(&name);
+        let safe_name = unique_dir_name(&sanitize_name(&name), &mut used_dir_names);
 
         accounts.push(Account {
             name: name.clone(),
@@ -225,17 +382,42 @@ fn parse_prefs_js(content: &str) -> Result<Vec<Account>> {
             port,
             username,
             password: None, // Passwords are stored separately in Thunderbird
+            auth: AccountAuth::default(),
             export_directory: format!("./exports/{}", safe_name),
             ignored_folders: default_ignored_folders(&name),
-            quote_depth: 1,
+            quote_depth: QuoteDepth::Limit(1),
             skip_existing: true,
             collect_contacts: false,
             skip_signature_images: true,
             delete_after_export: false,
+            keep_ics: false,
+            keep_raw_eml: false,
+            preserve_folder_structure: true,
+            subject_in_filename: false,
+            filename_template: None,
+            missing_date_policy: MissingDatePolicy::default(),
+            filename_recipient: FilenameRecipient::default(),
+            redact: false,
+            dedup_attachments: false,
+            fetch_delay_ms: None,
+            max_messages: None,
+            tag_prefix: None,
+            max_body_chars: None,
+            aliases: Vec::new(),
         });
     }
 
-    Ok(accounts)
+    Ok(PrefsImport { accounts, skipped })
+}
+
+/// Order server ids numerically by their trailing number (`server2` < `server10`).
+fn server_id_order(a: &str, b: &str) -> std::cmp::Ordering {
+    let split = |id: &str| {
+        let digits = id.len() - id.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        let (prefix, number) = id.split_at(id.len() - digits);
+        (prefix.to_string(), number.parse::<u64>().unwrap_or(0))
+    };
+    split(a).cmp(&split(b)).then_with(|| a.cmp(b))
 }
 
 /// Sanitize account name for use as directory name
@@ -244,6 +426,20 @@ fn sanitize_name(name: &str) -> String {
     re.replace_all(name, "_").to_string()
 }
 
+/// `name`, or `name_2`, `name_3`... if already in `used`, which it is added to.
+///
+/// Compared case-insensitively, as on Windows and macOS filesystems, so two
+/// accounts never share an export directory.
+fn unique_dir_name(name: &str, used: &mut HashSet<String>) -> String {
+    let mut candidate = name.to_string();
+    let mut suffix = 1;
+    while !used.insert(candidate.to_lowercase()) {
+        suffix += 1;
+        candidate = format!("{}_{}", name, suffix);
+    }
+    candidate
+}
+
 /// Get default ignored folders based on account name
 fn default_ignored_folders(name: &str) -> Vec<String> {
     let name_lower = name.to_lowercase();
@@ -271,35 +467,86 @@ fn default_ignored_folders(name: &str) -> Vec<String> {
     }
 }
 
+/// Serialisable shape of accounts.yaml
+#[derive(serde::Serialize)]
+struct AccountsYaml {
+    accounts: Vec<RawAccount>,
+}
+
 /// Generate accounts.yaml content from extracted accounts
 pub fn generate_accounts_yaml(accounts: &[Account]) -> String {
     let mut yaml = String::from("# Auto-generated from Thunderbird configuration\n");
     yaml.push_str("# Connection info only — behaviour is configured in settings.yaml\n\n");
-    yaml.push_str("accounts:\n");
+
+    let file = AccountsYaml {
+        accounts: accounts
+            .iter()
+            .map(|account| RawAccount {
+                name: account.name.clone(),
+                server: account.server.clone(),
+                port: account.port,
+                username: account.username.clone(),
+                ignored_folders: account.ignored_folders.clone(),
+            })
+            .collect(),
+    };
+
+    // serde_yaml takes care of quoting names with colons, quotes or backslashes
+    yaml.push_str(&serde_yaml::to_string(&file).unwrap_or_default());
+    yaml
+}
+
+/// Keep the export directories picked at import time distinct at runtime.
+///
+/// `Config::load` exports to `export_base_dir/<folder_name or name>`, so an
+/// account whose directory got a suffix from [`unique_dir_name`] is given
+/// that directory as `folder_name` in the settings at `settings_path`.
+/// Existing `folder_name` overrides are kept. Returns how many were added.
+pub fn write_folder_names(accounts: &[Account], settings_path: &Path) -> Result<usize> {
+    let mut settings = Settings::load(settings_path)?;
+    let mut added = 0;
 
     for account in accounts {
-        yaml.push_str(&format!("  - name: \"{}\"\n", account.name));
-        yaml.push_str(&format!("    server: \"{}\"\n", account.server));
-        yaml.push_str(&format!("    port: {}\n", account.port));
-        yaml.push_str(&format!("    username: \"{}\"\n", account.username));
-        yaml.push_str("    ignored_folders:\n");
-        for folder in &account.ignored_folders {
-            yaml.push_str(&format!("      - \"{}\"\n", folder));
+        let dir = Path::new(&account.export_directory)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if dir.is_empty() || dir == sanitize_name(&account.name) {
+            continue;
+        }
+
+        let behavior = settings.accounts.entry(account.name.clone()).or_default();
+        if behavior.folder_name.is_none() {
+            behavior.folder_name = Some(dir);
+            added += 1;
         }
-        yaml.push('\n');
     }
 
-    yaml
+    if added > 0 {
+        settings.save(settings_path)?;
+    }
+    Ok(added)
 }
 
 /// Generate .env template from extracted accounts
-pub fn generate_env_template(accounts: &[Account]) -> String {
+///
+/// `saved_servers` lists the IMAP hosts Thunderbird holds a password for
+/// (see [`list_saved_credentials`]); each entry is annotated accordingly.
+pub fn generate_env_template(accounts: &[Account], saved_servers: &[String]) -> String {
     let mut env = String::from("# Email passwords\n");
     env.push_str("# Replace 'your_password' with actual passwords\n");
     env.push_str("# For Gmail with 2FA, use App Password\n\n");
 
     for account in accounts {
-        let env_var = account.name.to_uppercase().replace(' ', "_").replace('-', "_");
+        let env_var = crate::config::env_prefix(&account.name);
+        if saved_servers
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(&account.server))
+        {
+            env.push_str("# Saved in Thunderbird: run `import --extract-passwords` to fill it in\n");
+        } else {
+            env.push_str("# Not saved in Thunderbird: enter it manually\n");
+        }
         env.push_str(&format!("{}_PASSWORD=your_password\n", env_var));
         // Also add APPLICATION_PASSWORD variant for Gmail-like accounts
         if account.server.contains("gmail") {
@@ -345,6 +592,50 @@ struct LoginEntry {
     encrypted_password: String,
 }
 
+/// Read the IMAP entries of `logins.json` (empty if the file is absent).
+fn read_imap_logins(profile: &ThunderbirdProfile) -> Result<Vec<LoginEntry>> {
+    let logins_path = profile.path.join("logins.json");
+
+    if !logins_path.exists() {
+        return Ok(vec![]);
+    }
+
+    let content = fs::read_to_string(&logins_path).context("Failed to read logins.json")?;
+    let logins: LoginsJson =
+        serde_json::from_str(&content).context("Failed to parse logins.json")?;
+
+    Ok(logins
+        .logins
+        .into_iter()
+        .filter(|e| e.hostname.starts_with("imap://"))
+        .collect())
+}
+
+/// Strip the `imap://` scheme and optional port from a logins.json hostname.
+fn imap_login_host(hostname: &str) -> String {
+    hostname
+        .strip_prefix("imap://")
+        .unwrap_or(hostname)
+        .split(':')
+        .next()
+        .unwrap_or("")
+        .to_string()
+}
+
+/// List the IMAP servers that have a saved password in `logins.json`.
+///
+/// Does not decrypt anything, so it works without NSS and while Thunderbird runs.
+pub fn list_saved_credentials(profile: &ThunderbirdProfile) -> Result<Vec<String>> {
+    let mut servers: Vec<String> = read_imap_logins(profile)?
+        .iter()
+        .map(|e| imap_login_host(&e.hostname))
+        .filter(|h| !h.is_empty())
+        .collect();
+    servers.sort();
+    servers.dedup();
+    Ok(servers)
+}
+
 /// Find the nss3 shared library for the current platform.
 pub fn find_nss_library_path(_profile: &ThunderbirdProfile) -> Option<PathBuf> {
     #[cfg(target_os = "windows")]
@@ -440,21 +731,7 @@ pub fn extract_passwords(
     profile: &ThunderbirdProfile,
     master_password: Option<&str>,
 ) -> Result<Vec<ThunderbirdPassword>> {
-    let logins_path = profile.path.join("logins.json");
-
-    if !logins_path.exists() {
-        return Ok(vec![]);
-    }
-
-    let content = fs::read_to_string(&logins_path).context("Failed to read logins.json")?;
-    let logins: LoginsJson =
-        serde_json::from_str(&content).context("Failed to parse logins.json")?;
-
-    let imap_entries: Vec<&LoginEntry> = logins
-        .logins
-        .iter()
-        .filter(|e| e.hostname.starts_with("imap://"))
-        .collect();
+    let imap_entries = read_imap_logins(profile)?;
 
     if imap_entries.is_empty() {
         return Ok(vec![]);
@@ -539,15 +816,7 @@ pub fn extract_passwords(
     // Decrypt all IMAP entries
     let mut passwords = Vec::new();
     for entry in &imap_entries {
-        // Strip "imap://" prefix and optional port
-        let imap_server = entry
-            .hostname
-            .strip_prefix("imap://")
-            .unwrap_or(&entry.hostname)
-            .split(':')
-            .next()
-            .unwrap_or("")
-            .to_string();
+        let imap_server = imap_login_host(&entry.hostname);
 
         let username = match decrypt_nss_string(&nss, &entry.encrypted_username) {
             Ok(u) => u,
@@ -626,11 +895,8 @@ pub fn write_passwords_to_env(
             }
         };
 
-        let env_key = account
-            .name
-            .to_uppercase()
-            .replace(' ', "_")
-            .replace('-', "_");
+        // The variable `Config::load` looks up
+        let env_key = format!("{}_PASSWORD", crate::config::env_prefix(&account.name));
         let env_line = format!("{}={}", env_key, pw.password);
         let key_prefix = format!("{}=", env_key);
 
@@ -685,9 +951,247 @@ user_pref("mail.server.server1.port", "993");
 user_pref("mail.server.server1.userName", "test@gmail.com");
 user_pref("mail.server.server1.name", "Gmail");
 "#;
-        let accounts = parse_prefs_js(prefs).unwrap();
+        let accounts = parse_prefs_js(prefs).unwrap().accounts;
         assert_eq!(accounts.len(), 1);
         assert_eq!(accounts[0].server, "imap.gmail.com");
         assert_eq!(accounts[0].username, "test@gmail.com");
     }
+
+    #[test]
+    fn test_list_saved_credentials() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let logins = r#"{"logins": [
+            {"hostname": "imap://imap.gmail.com", "encryptedUsername": "x", "encryptedPassword": "y"},
+            {"hostname": "smtp://smtp.gmail.com", "encryptedUsername": "x", "encryptedPassword": "y"},
+            {"hostname": "imap://mail.example.com:143", "encryptedUsername": "x", "encryptedPassword": "y"}
+        ]}"#;
+        fs::write(temp.path().join("logins.json"), logins).unwrap();
+
+        let profile = ThunderbirdProfile {
+            name: "test".into(),
+            path: temp.path().to_path_buf(),
+            is_default: true,
+        };
+        let servers = list_saved_credentials(&profile).unwrap();
+        assert_eq!(servers, vec!["imap.gmail.com", "mail.example.com"]);
+
+        let accounts = parse_prefs_js(
+            r#"user_pref("mail.server.server1.type", "imap");
+user_pref("mail.server.server1.hostname", "imap.gmail.com");
+user_pref("mail.server.server1.name", "Gmail");
+user_pref("mail.server.server2.type", "imap");
+user_pref("mail.server.server2.hostname", "imap.other.com");
+user_pref("mail.server.server2.name", "Other");
+"#,
+        )
+        .unwrap()
+        .accounts;
+        let env = generate_env_template(&accounts, &servers);
+        assert!(env.contains("# Saved in Thunderbird"));
+        assert!(env.contains("# Not saved in Thunderbird"));
+    }
+
+    #[test]
+    fn test_parse_prefs_js_identity_linkage() {
+        let prefs = r#"
+user_pref("mail.accountmanager.accounts", "account1,account2");
+user_pref("mail.account.account1.identities", "id1");
+user_pref("mail.account.account1.server", "server1");
+user_pref("mail.account.account2.server", "server2");
+user_pref("mail.identity.id1.fullName", "John Doe");
+user_pref("mail.identity.id1.useremail", "john.doe@example.com");
+user_pref("mail.server.server1.type", "imap");
+user_pref("mail.server.server1.hostname", "imap.example.com");
+user_pref("mail.server.server1.name", "Work");
+user_pref("mail.server.server2.type", "none");
+user_pref("mail.server.server2.hostname", "Local Folders");
+"#;
+        let accounts = parse_prefs_js(prefs).unwrap().accounts;
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].name, "john.doe@example.com");
+        assert_eq!(accounts[0].username, "john.doe@example.com");
+        assert_eq!(accounts[0].server, "imap.example.com");
+    }
+
+    #[test]
+    fn test_parse_prefs_js_escaped_values() {
+        let prefs = r#"
+user_pref("mail.server.server1.type", "imap");
+user_pref("mail.server.server1.hostname", "imap.example.com");
+user_pref("mail.server.server1.port", 143);
+user_pref("mail.server.server1.name", "Doe, John \"JD\" (C:\\Mail)");
+user_pref("mail.server.server1.userName", "multi
+line");
+"#;
+        let accounts = parse_prefs_js(prefs).unwrap().accounts;
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].name, r#"Doe, John "JD" (C:\Mail)"#);
+        assert_eq!(accounts[0].port, 143);
+        assert_eq!(accounts[0].username, "multi\nline");
+    }
+
+    #[test]
+    fn test_parse_prefs_js_stable_order() {
+        let prefs = r#"
+user_pref("mail.server.server10.type", "imap");
+user_pref("mail.server.server10.hostname", "imap.ten.com");
+user_pref("mail.server.server10.name", "Ten");
+user_pref("mail.server.server2.type", "imap");
+user_pref("mail.server.server2.hostname", "imap.two.com");
+user_pref("mail.server.server2.name", "Two");
+"#;
+        for _ in 0..5 {
+            let names: Vec<_> = parse_prefs_js(prefs)
+                .unwrap()
+                .accounts
+                .into_iter()
+                .map(|a| a.name)
+                .collect();
+            assert_eq!(names, vec!["Two", "Ten"]);
+        }
+    }
+
+    #[test]
+    fn test_parse_prefs_js_colliding_directory_names() {
+        let prefs = r#"
+user_pref("mail.server.server1.type", "imap");
+user_pref("mail.server.server1.hostname", "imap.one.com");
+user_pref("mail.server.server1.name", "Work Mail");
+user_pref("mail.server.server2.type", "imap");
+user_pref("mail.server.server2.hostname", "imap.two.com");
+user_pref("mail.server.server2.name", "Work.Mail");
+user_pref("mail.server.server3.type", "imap");
+user_pref("mail.server.server3.hostname", "imap.three.com");
+user_pref("mail.server.server3.name", "work_mail");
+user_pref("mail.server.server4.type", "imap");
+user_pref("mail.server.server4.hostname", "imap.four.com");
+user_pref("mail.server.server4.name", "Work-Mail");
+"#;
+        let accounts = parse_prefs_js(prefs).unwrap().accounts;
+        let dirs: Vec<_> = accounts.iter().map(|a| a.export_directory.as_str()).collect();
+        assert_eq!(
+            dirs,
+            vec![
+                "./exports/Work_Mail",
+                "./exports/Work_Mail_2",
+                "./exports/work_mail_3",
+                "./exports/Work-Mail",
+            ]
+        );
+        // Names themselves are untouched
+        assert_eq!(accounts[1].name, "Work.Mail");
+    }
+
+    #[test]
+    fn test_colliding_directory_names_reach_config() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let prefs = r#"
+user_pref("mail.server.server1.type", "imap");
+user_pref("mail.server.server1.hostname", "imap.one.com");
+user_pref("mail.server.server1.userName", "me@one.com");
+user_pref("mail.server.server1.name", "Work");
+user_pref("mail.server.server2.type", "imap");
+user_pref("mail.server.server2.hostname", "imap.two.com");
+user_pref("mail.server.server2.userName", "me@two.com");
+user_pref("mail.server.server2.name", "work");
+user_pref("mail.server.server3.type", "imap");
+user_pref("mail.server.server3.hostname", "imap.three.com");
+user_pref("mail.server.server3.userName", "me@three.com");
+user_pref("mail.server.server3.name", "Home");
+"#;
+        let accounts = parse_prefs_js(prefs).unwrap().accounts;
+
+        let accounts_path = temp.path().join("accounts.yaml");
+        fs::write(&accounts_path, generate_accounts_yaml(&accounts)).unwrap();
+        let settings_path = temp.path().join("settings.yaml");
+        fs::write(&settings_path, format!("export_base_dir: {}\n", temp.path().display())).unwrap();
+
+        assert_eq!(write_folder_names(&accounts, &settings_path).unwrap(), 1);
+        // A second import keeps the folder already recorded
+        assert_eq!(write_folder_names(&accounts, &settings_path).unwrap(), 0);
+
+        let config = crate::config::Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        let dirs: Vec<_> = config
+            .accounts
+            .iter()
+            .map(|a| Path::new(&a.export_directory).strip_prefix(temp.path()).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(dirs, vec![PathBuf::from("Work"), PathBuf::from("work_2"), PathBuf::from("Home")]);
+    }
+
+    #[test]
+    fn test_imported_password_reaches_config() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let prefs = r#"
+user_pref("mail.account.account1.identities", "id1");
+user_pref("mail.account.account1.server", "server1");
+user_pref("mail.identity.id1.useremail", "jane.roe-import@example.com");
+user_pref("mail.server.server1.type", "imap");
+user_pref("mail.server.server1.hostname", "imap.import.example.com");
+user_pref("mail.server.server1.name", "Work");
+"#;
+        let accounts = parse_prefs_js(prefs).unwrap().accounts;
+
+        let template = generate_env_template(&accounts, &[]);
+        assert!(template.contains("\nJANE_ROE_IMPORT_EXAMPLE_COM_PASSWORD=your_password\n"), "{}", template);
+
+        let accounts_path = temp.path().join("accounts.yaml");
+        fs::write(&accounts_path, generate_accounts_yaml(&accounts)).unwrap();
+        let env_path = temp.path().join(".env");
+        let passwords = vec![ThunderbirdPassword {
+            imap_server: "IMAP.import.example.com".to_string(),
+            username: "jane.roe-import@example.com".to_string(),
+            password: "s3cret".to_string(),
+        }];
+        assert_eq!(write_passwords_to_env(&accounts, &passwords, &env_path).unwrap(), 1);
+
+        let settings_path = temp.path().join("settings.yaml");
+        fs::write(&settings_path, format!("export_base_dir: {}\n", temp.path().display())).unwrap();
+        dotenv::from_path(&env_path).unwrap();
+        let config = crate::config::Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        assert_eq!(config.accounts[0].password.as_deref(), Some("s3cret"));
+    }
+
+    #[test]
+    fn test_generate_accounts_yaml_roundtrip() {
+        let mut accounts = parse_prefs_js(
+            r#"user_pref("mail.server.server1.type", "imap");
+user_pref("mail.server.server1.hostname", "imap.example.com");
+user_pref("mail.server.server1.name", "Work: \"main\" C:\\box");
+"#,
+        )
+        .unwrap()
+        .accounts;
+        accounts[0].ignored_folders.push("[Gmail]/*".to_string());
+
+        let yaml = generate_accounts_yaml(&accounts);
+        let parsed: AccountsYamlCheck = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed.accounts[0].name, r#"Work: "main" C:\box"#);
+        assert_eq!(parsed.accounts[0].server, "imap.example.com");
+        assert_eq!(parsed.accounts[0].ignored_folders, accounts[0].ignored_folders);
+    }
+
+    #[derive(serde::Deserialize)]
+    struct AccountsYamlCheck {
+        accounts: Vec<RawAccount>,
+    }
+
+    #[test]
+    fn test_parse_prefs_reports_non_imap_servers() {
+        let prefs = r#"
+user_pref("mail.server.server1.type", "pop3");
+user_pref("mail.server.server1.hostname", "pop.example.com");
+user_pref("mail.server.server1.name", "Old POP");
+user_pref("mail.server.server2.type", "none");
+user_pref("mail.server.server2.hostname", "Local Folders");
+user_pref("mail.server.server3.type", "ews");
+user_pref("mail.server.server3.hostname", "outlook.office365.com");
+user_pref("mail.server.server3.name", "Work");
+"#;
+        let import = parse_prefs_js(prefs).unwrap();
+        assert!(import.accounts.is_empty());
+        assert_eq!(import.skipped.len(), 2);
+        assert_eq!(import.skipped[0].server_type, "pop3");
+        assert_eq!(import.skipped[1].name, "Work");
+    }
 }
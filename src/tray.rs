@@ -22,11 +22,16 @@ use crate::tray_actions::{
 mod menu_ids {
     pub const IMPORT_THUNDERBIRD: &str = "import_thunderbird";
     pub const CHOOSE_EXPORT_DIR: &str = "choose_export_dir";
+    pub const LAST_EXPORT_STATS: &str = "last_export_stats";
+    pub const OPEN_LAST_LOG: &str = "open_last_log";
     pub const OPEN_CONFIG: &str = "open_config";
     pub const OPEN_DOCUMENTATION: &str = "open_documentation";
     pub const QUIT: &str = "quit";
     pub const EXPORT_PREFIX: &str = "export_";
     pub const SORT_PREFIX: &str = "sort_";
+    pub const OPEN_DIR_PREFIX: &str = "open_dir_";
+    pub const TEST_CONNECTION_PREFIX: &str = "test_connection_";
+    pub const SHOW_CONFIG_PREFIX: &str = "show_config_";
 }
 
 /// Run the system tray application.
@@ -47,20 +52,17 @@ pub fn run_tray() -> Result<()> {
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Poll;
 
-        match event {
-            Event::NewEvents(StartCause::Init) => {
-                // Create tray icon on init
-                match create_tray_icon() {
-                    Ok(icon) => {
-                        tray_icon = Some(icon);
-                        println!("Tray icon created successfully");
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to create tray icon: {}", e);
-                    }
+        if let Event::NewEvents(StartCause::Init) = event {
+            // Create tray icon on init
+            match create_tray_icon() {
+                Ok(icon) => {
+                    tray_icon = Some(icon);
+                    println!("Tray icon created successfully");
+                }
+                Err(e) => {
+                    eprintln!("Failed to create tray icon: {}", e);
                 }
             }
-            _ => {}
         }
 
         // Handle menu events
@@ -70,6 +72,18 @@ pub fn run_tray() -> Result<()> {
 
         // Handle action results (notifications)
         if let Ok(result) = result_receiver.try_recv() {
+            // Progress only updates the tooltip; anything else ends the run
+            let tooltip = match &result {
+                ActionResult::Progress(msg) => format!("Email to Markdown — {}", msg),
+                _ => "Email to Markdown".to_string(),
+            };
+            if let Some(ref icon) = tray_icon {
+                let _ = icon.set_tooltip(Some(tooltip));
+            }
+            if let ActionResult::Progress(_) = result {
+                return;
+            }
+
             if let crate::tray_actions::ActionResult::Imported(_) = &result {
                 // Rebuild menu so Export/Sort submenus reflect new accounts
                 if let Some(ref icon) = tray_icon {
@@ -139,6 +153,61 @@ fn create_menu() -> Result<Menu> {
     }
     menu.append(&sort_submenu)?;
 
+    // Open export folder submenu
+    let open_dir_submenu = Submenu::new("Ouvrir dossier d'export", has_accounts);
+    for account in &accounts {
+        let id = format!("{}{}", menu_ids::OPEN_DIR_PREFIX, account);
+        let _ = open_dir_submenu.append(&MenuItem::with_id(
+            id,
+            account,
+            true,
+            no_accel.clone(),
+        ));
+    }
+    menu.append(&open_dir_submenu)?;
+
+    // Test connection submenu: log in and list folders, no download
+    let test_connection_submenu = Submenu::new("Tester la connexion", has_accounts);
+    for account in &accounts {
+        let id = format!("{}{}", menu_ids::TEST_CONNECTION_PREFIX, account);
+        let _ = test_connection_submenu.append(&MenuItem::with_id(
+            id,
+            account,
+            true,
+            no_accel.clone(),
+        ));
+    }
+    menu.append(&test_connection_submenu)?;
+
+    // Show config submenu: resolved settings and where they come from
+    let show_config_submenu = Submenu::new("Afficher la configuration", has_accounts);
+    for account in &accounts {
+        let id = format!("{}{}", menu_ids::SHOW_CONFIG_PREFIX, account);
+        let _ = show_config_submenu.append(&MenuItem::with_id(
+            id,
+            account,
+            true,
+            no_accel.clone(),
+        ));
+    }
+    menu.append(&show_config_submenu)?;
+
+    // Last export statistics
+    menu.append(&MenuItem::with_id(
+        menu_ids::LAST_EXPORT_STATS,
+        "Dernier export…",
+        true,
+        no_accel.clone(),
+    ))?;
+
+    // Last run log
+    menu.append(&MenuItem::with_id(
+        menu_ids::OPEN_LAST_LOG,
+        "Ouvrir le dernier journal",
+        true,
+        no_accel.clone(),
+    ))?;
+
     // Separator
     menu.append(&PredefinedMenuItem::separator())?;
 
@@ -197,6 +266,17 @@ fn handle_menu_event(id: &str, result_sender: mpsc::Sender<ActionResult>) {
         menu_ids::CHOOSE_EXPORT_DIR => {
             tray_actions::action_choose_export_dir(result_sender);
         }
+        menu_ids::LAST_EXPORT_STATS => {
+            tray_actions::action_show_last_export(result_sender);
+        }
+        menu_ids::OPEN_LAST_LOG => {
+            if let Err(e) = tray_actions::action_open_last_log() {
+                let _ = result_sender.send(ActionResult::Error(format!(
+                    "Failed to open log: {}",
+                    e
+                )));
+            }
+        }
         menu_ids::OPEN_CONFIG => {
             if let Err(e) = tray_actions::action_open_config() {
                 let _ = result_sender.send(ActionResult::Error(format!(
@@ -224,6 +304,23 @@ fn handle_menu_event(id: &str, result_sender: mpsc::Sender<ActionResult>) {
             let account_name = id.strip_prefix(menu_ids::SORT_PREFIX).unwrap();
             tray_actions::action_sort(account_name.to_string(), result_sender);
         }
+        id if id.starts_with(menu_ids::TEST_CONNECTION_PREFIX) => {
+            let account_name = id.strip_prefix(menu_ids::TEST_CONNECTION_PREFIX).unwrap();
+            tray_actions::action_test_connection(account_name.to_string(), result_sender);
+        }
+        id if id.starts_with(menu_ids::SHOW_CONFIG_PREFIX) => {
+            let account_name = id.strip_prefix(menu_ids::SHOW_CONFIG_PREFIX).unwrap();
+            tray_actions::action_show_config(account_name, result_sender);
+        }
+        id if id.starts_with(menu_ids::OPEN_DIR_PREFIX) => {
+            let account_name = id.strip_prefix(menu_ids::OPEN_DIR_PREFIX).unwrap();
+            if let Err(e) = tray_actions::action_open_export_dir(account_name) {
+                let _ = result_sender.send(ActionResult::Error(format!(
+                    "Failed to open export folder: {}",
+                    e
+                )));
+            }
+        }
         _ => {}
     }
 }
@@ -308,6 +405,7 @@ fn show_notification(result: &ActionResult) {
             m.clone(),
             rfd::MessageLevel::Error,
         ),
+        ActionResult::Progress(_) => return,
     };
 
     thread::spawn(move || {
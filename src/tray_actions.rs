@@ -3,17 +3,21 @@
 //! This module provides the functions that are called when users
 //! interact with the system tray menu.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
 use std::thread;
 
 use anyhow::{Context, Result};
-use rfd;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
 
 use crate::config::{self, Config, SortConfig};
-use crate::email_export::ImapExporter;
+use crate::run_log::{LogStatus, RunLog};
+use crate::runner;
 use crate::sort_emails::{Category, EmailSorter};
 use crate::thunderbird;
+use crate::utils::Verbosity;
 
 /// Result of an action, sent back to the main thread for notification.
 #[derive(Debug, Clone)]
@@ -22,15 +26,82 @@ pub enum ActionResult {
     Success(String, String),
     /// Import completed — the main thread should rebuild the tray menu.
     Imported(String),
+    /// Intermediate status of a running export (shown in the tooltip, no dialog).
+    Progress(String),
     Error(String),
 }
 
+/// Set while an export thread is running, so only one export runs at a time.
+static EXPORT_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Clears `EXPORT_IN_PROGRESS` when the export thread ends, even on panic.
+struct ExportGuard;
+
+impl Drop for ExportGuard {
+    fn drop(&mut self) {
+        EXPORT_IN_PROGRESS.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Statistics of the most recent export, persisted to `last_export.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastExportStats {
+    pub account: String,
+    /// Local time the export finished (RFC 3339).
+    pub finished_at: String,
+    pub exported: usize,
+    pub skipped: usize,
+    pub errors: usize,
+}
+
+impl LastExportStats {
+    /// Load the stats file, `None` if no export has run yet.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path).context("Failed to read last export stats")?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for LastExportStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Last export ({}, {}): {} exported, {} skipped, {} errors",
+            self.account, self.finished_at, self.exported, self.skipped, self.errors
+        )
+    }
+}
+
 /// Export emails for a specific account.
 ///
-/// Runs in a separate thread to avoid blocking the UI.
+/// Runs in a separate thread to avoid blocking the UI and streams
+/// `ActionResult::Progress` updates while it runs. Refused if another
+/// export is already in flight.
 pub fn action_export(account_name: String, result_sender: Sender<ActionResult>) {
+    if EXPORT_IN_PROGRESS
+        .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        .is_err()
+    {
+        let _ = result_sender.send(ActionResult::Error(
+            "Un export est déjà en cours".to_string(),
+        ));
+        return;
+    }
+
     thread::spawn(move || {
-        let result = run_export(&account_name);
+        let _guard = ExportGuard;
+        let result = run_export(&account_name, result_sender.clone());
         let action_result = match result {
             Ok(message) => ActionResult::Success("Export terminé".to_string(), message),
             Err(e) => ActionResult::Error(format!("Export error: {}", e)),
@@ -39,43 +110,113 @@ pub fn action_export(account_name: String, result_sender: Sender<ActionResult>)
     });
 }
 
-fn run_export(account_name: &str) -> Result<String> {
+fn run_export(account_name: &str, progress_sender: Sender<ActionResult>) -> Result<String> {
     dotenv::from_path(config::env_file_path()).ok();
 
-    let config = Config::load(&config::accounts_yaml_path()).context("Failed to load configuration")?;
+    let progress_account = account_name.to_string();
+    let stats = runner::run_export_with(
+        account_name,
+        &config::accounts_yaml_path(),
+        &config::settings_path(),
+        |exporter| {
+            let exporter = exporter.with_progress_callback(move |msg| {
+                let _ = progress_sender.send(ActionResult::Progress(format!("{}: {}", progress_account, msg)));
+            });
+            match RunLog::create("export") {
+                Ok(run_log) => {
+                    run_log.write(LogStatus::Info, &format!("Export of account {}", account_name));
+                    exporter.with_run_log(run_log)
+                }
+                Err(e) => {
+                    eprintln!("Could not create export log: {}", e);
+                    exporter
+                }
+            }
+        },
+    )?;
 
-    let account = config
-        .get_account(account_name)
-        .context(format!("Account '{}' not found", account_name))?
-        .clone();
+    let (total_exported, total_skipped, total_errors) = (stats.exported, stats.skipped, stats.errors);
 
-    if account.password.is_none() {
-        return Err(anyhow::anyhow!(
-            "No password found for {}. Check {}",
-            account_name,
-            config::env_file_path().display()
-        ));
+    let last_export = LastExportStats {
+        account: account_name.to_string(),
+        finished_at: Local::now().to_rfc3339(),
+        exported: total_exported,
+        skipped: total_skipped,
+        errors: total_errors,
+    };
+    if let Err(e) = last_export.save(&config::last_export_path()) {
+        eprintln!("Could not save last export stats: {}", e);
     }
 
-    let mut exporter = ImapExporter::new(account.clone(), false);
-    exporter.connect().context("Failed to connect to IMAP server")?;
-
-    let results = exporter
-        .export_account()
-        .context("Export failed")?;
-
-    exporter.disconnect().ok();
-
-    let total_exported: usize = results.values().map(|s| s.exported).sum();
-    let total_skipped: usize = results.values().map(|s| s.skipped).sum();
-    let total_errors: usize = results.values().map(|s| s.errors).sum();
-
     Ok(format!(
         "{}: {} exported, {} skipped, {} errors",
         account_name, total_exported, total_skipped, total_errors
     ))
 }
 
+/// Show the statistics of the most recent export.
+pub fn action_show_last_export(result_sender: Sender<ActionResult>) {
+    let action_result = match LastExportStats::load(&config::last_export_path()) {
+        Ok(Some(stats)) => ActionResult::Success("Dernier export".to_string(), stats.to_string()),
+        Ok(None) => ActionResult::Success(
+            "Dernier export".to_string(),
+            "Aucun export effectué pour le moment".to_string(),
+        ),
+        Err(e) => ActionResult::Error(format!("Last export stats error: {}", e)),
+    };
+    let _ = result_sender.send(action_result);
+}
+
+/// Open the most recent export/sort log file.
+pub fn action_open_last_log() -> Result<()> {
+    let log_path = RunLog::latest_in(&config::logs_dir())
+        .context("Aucun journal disponible pour le moment")?;
+
+    open::that(&log_path).context("Failed to open log file")?;
+    Ok(())
+}
+
+/// Check the connection of an account and show its folders.
+pub fn action_test_connection(account_name: String, result_sender: Sender<ActionResult>) {
+    thread::spawn(move || {
+        let result = run_test_connection(&account_name);
+        let action_result = match result {
+            Ok(message) => ActionResult::Success("Connexion réussie".to_string(), message),
+            Err(e) => ActionResult::Error(format!("Connection error: {}", e)),
+        };
+        let _ = result_sender.send(action_result);
+    });
+}
+
+fn run_test_connection(account_name: &str) -> Result<String> {
+    dotenv::from_path(config::env_file_path()).ok();
+
+    let folders = runner::run_test_connection(
+        account_name,
+        &config::accounts_yaml_path(),
+        &config::settings_path(),
+    )?;
+
+    let mut message = format!("{}: {} dossiers", account_name, folders.len());
+    for folder in &folders {
+        message.push_str(&format!("\n- {}", folder));
+    }
+    Ok(message)
+}
+
+/// Show the resolved settings of an account and where each one comes from.
+pub fn action_show_config(account_name: &str, result_sender: Sender<ActionResult>) {
+    let action_result = match Config::describe(
+        &config::accounts_yaml_path(),
+        &config::settings_path(),
+        account_name,
+    ) {
+        Ok(description) => ActionResult::Success("Configuration du compte".to_string(), description),
+        Err(e) => ActionResult::Error(format!("Config error: {}", e)),
+    };
+    let _ = result_sender.send(action_result);
+}
+
 /// Sort emails for a specific account.
 ///
 /// Runs in a separate thread to avoid blocking the UI.
@@ -102,7 +243,14 @@ fn run_sort(account_name: &str) -> Result<String> {
     let sort_directory = PathBuf::from(&account.export_directory);
     let sort_config = SortConfig::default();
 
-    let mut sorter = EmailSorter::new(sort_directory.clone(), sort_config);
+    let mut sorter = EmailSorter::new(sort_directory.clone(), sort_config).with_verbosity(Verbosity::Quiet);
+    match RunLog::create("sort") {
+        Ok(run_log) => {
+            run_log.write(LogStatus::Info, &format!("Sort of account {}", account_name));
+            sorter = sorter.with_run_log(run_log);
+        }
+        Err(e) => eprintln!("Could not create sort log: {}", e),
+    }
     sorter.sort_emails()?;
 
     let report = sorter.generate_report();
@@ -113,10 +261,11 @@ fn run_sort(account_name: &str) -> Result<String> {
     let delete_count = categories.get(&Category::Delete).map(|v| v.len()).unwrap_or(0);
     let summarize_count = categories.get(&Category::Summarize).map(|v| v.len()).unwrap_or(0);
     let keep_count = categories.get(&Category::Keep).map(|v| v.len()).unwrap_or(0);
+    let review_count = categories.get(&Category::Review).map(|v| v.len()).unwrap_or(0);
 
     Ok(format!(
-        "{}: {} delete, {} summarize, {} keep",
-        account_name, delete_count, summarize_count, keep_count
+        "{}: {} delete, {} summarize, {} keep, {} review",
+        account_name, delete_count, summarize_count, keep_count, review_count
     ))
 }
 
@@ -165,11 +314,17 @@ fn run_import_thunderbird(extract_passwords: bool) -> Result<String> {
         .cloned()
         .context("No usable Thunderbird profiles found (no prefs.js)")?;
 
-    let accounts = thunderbird::extract_accounts(&profile)
+    let import = thunderbird::extract_import(&profile)
         .context("Failed to extract accounts from Thunderbird")?;
+    let accounts = import.accounts;
+    let skipped_note = if import.skipped.is_empty() {
+        String::new()
+    } else {
+        format!("\n{} compte(s) ignoré(s) (pas IMAP)", import.skipped.len())
+    };
 
     if accounts.is_empty() {
-        return Ok("No IMAP accounts found in Thunderbird".to_string());
+        return Ok(format!("No IMAP accounts found in Thunderbird{}", skipped_note));
     }
 
     let yaml_content = thunderbird::generate_accounts_yaml(&accounts);
@@ -180,8 +335,9 @@ fn run_import_thunderbird(extract_passwords: bool) -> Result<String> {
     }
 
     std::fs::write(&output_path, &yaml_content)?;
+    thunderbird::write_folder_names(&accounts, &config::settings_path())?;
 
-    let mut message = format!("Imported {} account(s)", accounts.len());
+    let mut message = format!("Imported {} account(s){}", accounts.len(), skipped_note);
 
     if extract_passwords {
         match thunderbird::extract_passwords(&profile, None) {
@@ -294,6 +450,26 @@ defaults:
     Ok(())
 }
 
+/// Open an account's export directory in the platform file manager.
+pub fn action_open_export_dir(account_name: &str) -> Result<()> {
+    let config = Config::load(&config::accounts_yaml_path()).context("Failed to load configuration")?;
+
+    let account = config
+        .get_account(account_name)
+        .context(format!("Account '{}' not found", account_name))?;
+
+    let export_directory = PathBuf::from(&account.export_directory);
+    if !export_directory.is_dir() {
+        return Err(anyhow::anyhow!(
+            "Le répertoire d'export n'existe pas encore : {}",
+            export_directory.display()
+        ));
+    }
+
+    open::that(&export_directory).context("Failed to open export directory")?;
+    Ok(())
+}
+
 /// Get the list of configured accounts.
 pub fn get_account_names() -> Result<Vec<String>> {
     dotenv::from_path(config::env_file_path()).ok();
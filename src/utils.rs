@@ -1,8 +1,35 @@
-use regex::Regex;
-use std::path::Path;
+use crate::config::QuoteDepth;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+/// How much the library prints on its own. Errors are always printed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Errors only (tray, scripts).
+    Quiet,
+    /// Progress and per-file notes.
+    #[default]
+    Normal,
+    /// Also every processed file.
+    Verbose,
+}
+
+impl Verbosity {
+    /// Whether messages meant for `level` are printed.
+    pub fn shows(self, level: Verbosity) -> bool {
+        self >= level
+    }
+}
 
 /// Limit the depth of quoted messages to reduce redundancy.
-pub fn limit_quote_depth(text: &str, max_depth: usize) -> String {
+pub fn limit_quote_depth(text: &str, max_depth: QuoteDepth) -> String {
+    let max_depth = match max_depth {
+        QuoteDepth::Limit(depth) => depth,
+        QuoteDepth::Unlimited => return text.to_string(),
+    };
+
     text.lines()
         .filter(|line| {
             let quote_level = line.chars().take_while(|&c| c == '>').count();
@@ -65,51 +92,140 @@ pub fn get_short_name(email_str: Option<&str>) -> String {
 }
 
 /// Extract email addresses from a text field.
+///
+/// Handles `Name <addr>`, bare `<addr>` and RFC 5322 group syntax
+/// (`Team: a@x.com, b@y.com;`). URLs are ignored, and duplicates are
+/// removed keeping the first occurrence.
 pub fn extract_emails(text: Option<&str>) -> Vec<String> {
     let text = match text {
         Some(s) => s,
         None => return Vec::new(),
     };
 
-    let re = Regex::new(r"[\w\.-]+@[\w\.-]+\.\w+").unwrap();
-    re.find_iter(text)
+    // `https://user@host/...` must not yield an address
+    let url_re = Regex::new(r"(?i)https?://\S+").unwrap();
+    let text = url_re.replace_all(text, " ");
+
+    let re = Regex::new(r"<\s*([\w.+-]+@[\w.-]+\.\w+)\s*>|([\w.+-]+@[\w.-]+\.\w+)").unwrap();
+    let mut seen = HashSet::new();
+    re.captures_iter(&text)
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)))
         .map(|m| m.as_str().to_lowercase())
+        .filter(|email| seen.insert(email.clone()))
         .collect()
 }
 
+/// Mask an email address for sharing: `john@example.com` → `j***@e***.com`.
+///
+/// The mask only depends on the (lowercased) address, so a given address
+/// always gets the same mask.
+pub fn redact_email(addr: &str) -> String {
+    let addr = addr.trim().to_lowercase();
+    let Some((local, domain)) = addr.rsplit_once('@') else {
+        return "***".to_string();
+    };
+
+    let first = |s: &str| s.chars().next().map(String::from).unwrap_or_default();
+    let domain_mask = match domain.rsplit_once('.') {
+        Some((name, tld)) => format!("{}***.{}", first(name), tld),
+        None => format!("{}***", first(domain)),
+    };
+    format!("{}***@{}", first(local), domain_mask)
+}
+
+/// Replace every email address in `text` with its [`redact_email`] mask.
+pub fn redact_emails(text: &str) -> String {
+    let re = Regex::new(r"[\w.+-]+@[\w.-]+\.\w+").unwrap();
+    re.replace_all(text, |caps: &regex::Captures| redact_email(&caps[0]))
+        .to_string()
+}
+
 /// Normalize line breaks to max 2 consecutive newlines.
 pub fn normalize_line_breaks(text: &str) -> String {
     let re = Regex::new(r"\n{3,}").unwrap();
     re.replace_all(text, "\n\n").to_string()
 }
 
+/// Naively turn HTML into plain text: drop tags, scripts and styles, break
+/// lines at block elements and decode the common entities.
+///
+/// Last resort for HTML-only emails, so no raw markup lands in the notes.
+pub fn strip_html_tags(html: &str) -> String {
+    let hidden = Regex::new(r"(?is)<(script|style|head)\b.*?</(script|style|head)\s*>").unwrap();
+    let line_break = Regex::new(r"(?i)<br\s*/?>|</(p|div|li|tr|h[1-6]|blockquote)\s*>").unwrap();
+    let tag = Regex::new(r"(?s)<[^>]*>").unwrap();
+    let spaces = Regex::new(r"[ \t]+").unwrap();
+
+    let text = hidden.replace_all(html, "");
+    let text = line_break.replace_all(&text, "\n");
+    let text = tag.replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| spaces.replace_all(line.trim(), " ").to_string())
+        .collect();
+    normalize_line_breaks(&lines.join("\n")).trim().to_string()
+}
+
+/// Strip trailing spaces and tabs from every line (markdown reads them as hard breaks).
+pub fn normalize_trailing_whitespace(text: &str) -> String {
+    text.split('\n')
+        .map(|line| match line.strip_suffix('\r') {
+            Some(content) => format!("{}\r", content.trim_end_matches([' ', '\t'])),
+            None => line.trim_end_matches([' ', '\t']).to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 /// Decode MIME encoded filenames (format: =?utf-8?q?filename?=).
 pub fn decode_mime_filename(encoded_filename: &str) -> String {
-    if encoded_filename.starts_with("=?") && encoded_filename.contains("?=") {
-        let re = Regex::new(r"=\?(.*?)\?(.*?)\?(.*?)\?=").unwrap();
-        if let Some(caps) = re.captures(encoded_filename) {
-            let charset = caps.get(1).map_or("", |m| m.as_str());
-            let encoding = caps.get(2).map_or("", |m| m.as_str());
-            let encoded_text = caps.get(3).map_or("", |m| m.as_str());
-
-            match encoding.to_lowercase().as_str() {
-                "q" => {
-                    // Quoted-printable encoding
-                    if let Ok(decoded) = quoted_printable_decode(encoded_text, charset) {
-                        return decoded;
-                    }
-                }
-                "b" => {
-                    // Base64 encoding
-                    if let Ok(decoded) = base64_decode(encoded_text, charset) {
-                        return decoded;
-                    }
-                }
-                _ => {}
-            }
+    decode_mime_header(encoded_filename)
+}
+
+/// Decode the RFC 2047 encoded words of a header value (subject, display name...).
+///
+/// Every encoded word is decoded; whitespace between two adjacent encoded
+/// words is dropped, other text is kept as is. Words that fail to decode
+/// are left untouched.
+pub fn decode_mime_header(value: &str) -> String {
+    let re = Regex::new(r"=\?([^?]*)\?([^?]*)\?(.*?)\?=").unwrap();
+    let mut result = String::new();
+    let mut last_end = 0;
+    let mut previous_decoded = false;
+
+    for caps in re.captures_iter(value) {
+        let whole = caps.get(0).unwrap();
+        let gap = &value[last_end..whole.start()];
+        let charset = caps.get(1).map_or("", |m| m.as_str());
+        let encoding = caps.get(2).map_or("", |m| m.as_str());
+        let encoded_text = caps.get(3).map_or("", |m| m.as_str());
+
+        let decoded = match encoding.to_lowercase().as_str() {
+            // Quoted-printable encoding
+            "q" => quoted_printable_decode(encoded_text, charset).ok(),
+            // Base64 encoding
+            "b" => base64_decode(encoded_text, charset).ok(),
+            _ => None,
+        };
+
+        if !(previous_decoded && decoded.is_some() && gap.trim().is_empty()) {
+            result.push_str(gap);
         }
+        previous_decoded = decoded.is_some();
+        result.push_str(decoded.as_deref().unwrap_or(whole.as_str()));
+        last_end = whole.end();
     }
-    encoded_filename.to_string()
+
+    result.push_str(&value[last_end..]);
+    result
 }
 
 fn quoted_printable_decode(text: &str, charset: &str) -> Result<String, ()> {
@@ -134,19 +250,24 @@ fn quoted_printable_decode(text: &str, charset: &str) -> Result<String, ()> {
     decode_bytes(&result, charset)
 }
 
-fn base64_decode(text: &str, charset: &str) -> Result<String, ()> {
-    use std::collections::HashMap;
-
-    let base64_table: HashMap<char, u8> = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
-        .chars()
-        .enumerate()
-        .map(|(i, c)| (c, i as u8))
-        .collect();
+/// Value of a base64 digit, standard (`+/`) or URL-safe (`-_`) alphabet.
+fn base64_value(c: char) -> Option<u8> {
+    match c {
+        'A'..='Z' => Some(c as u8 - b'A'),
+        'a'..='z' => Some(c as u8 - b'a' + 26),
+        '0'..='9' => Some(c as u8 - b'0' + 52),
+        '+' | '-' => Some(62),
+        '/' | '_' => Some(63),
+        _ => None,
+    }
+}
 
+/// Lenient base64 decoding: line breaks, spaces and other stray characters are skipped.
+fn base64_decode(text: &str, charset: &str) -> Result<String, ()> {
     let chars: Vec<u8> = text
         .chars()
         .filter(|&c| c != '=')
-        .filter_map(|c| base64_table.get(&c).copied())
+        .filter_map(base64_value)
         .collect();
 
     let mut bytes = Vec::new();
@@ -194,6 +315,10 @@ pub fn decode_imap_utf7(encoded_str: &str) -> String {
     }
 
     let mut result = String::new();
+    // UTF-16 units of consecutive encoded runs, decoded together so a surrogate
+    // pair split across `&...-&...-` still yields its character
+    let mut pending: Vec<u16> = Vec::new();
+    let mut pending_raw = String::new();
     let mut chars = encoded_str.chars().peekable();
 
     while let Some(c) = chars.next() {
@@ -201,6 +326,7 @@ pub fn decode_imap_utf7(encoded_str: &str) -> String {
             // Check for &- which is literal &
             if chars.peek() == Some(&'-') {
                 chars.next();
+                flush_utf16(&mut pending, &mut pending_raw, &mut result);
                 result.push('&');
                 continue;
             }
@@ -216,30 +342,105 @@ pub fn decode_imap_utf7(encoded_str: &str) -> String {
             }
 
             if encoded.is_empty() {
+                flush_utf16(&mut pending, &mut pending_raw, &mut result);
                 result.push('&');
                 continue;
             }
 
             // Decode modified base64 to UTF-16BE
+            let raw = format!("&{}-", encoded);
             match decode_modified_base64(&encoded) {
-                Some(decoded) => result.push_str(&decoded),
+                Some(units) => {
+                    pending.extend(units);
+                    pending_raw.push_str(&raw);
+                }
                 None => {
                     // Fallback: keep original
-                    result.push('&');
-                    result.push_str(&encoded);
-                    result.push('-');
+                    flush_utf16(&mut pending, &mut pending_raw, &mut result);
+                    result.push_str(&raw);
                 }
             }
         } else {
+            flush_utf16(&mut pending, &mut pending_raw, &mut result);
             result.push(c);
         }
     }
+    flush_utf16(&mut pending, &mut pending_raw, &mut result);
+
+    result
+}
+
+/// Encode a folder name to IMAP modified UTF-7 (inverse of `decode_imap_utf7`).
+pub fn encode_imap_utf7(text: &str) -> String {
+    let mut result = String::new();
+    let mut run: Vec<u16> = Vec::new();
+
+    for c in text.chars() {
+        if (' '..='~').contains(&c) {
+            if !run.is_empty() {
+                result.push_str(&encode_modified_base64(&run));
+                run.clear();
+            }
+            if c == '&' {
+                result.push_str("&-");
+            } else {
+                result.push(c);
+            }
+        } else {
+            let mut units = [0u16; 2];
+            run.extend_from_slice(c.encode_utf16(&mut units));
+        }
+    }
+    if !run.is_empty() {
+        result.push_str(&encode_modified_base64(&run));
+    }
 
     result
 }
 
-/// Decode modified base64 (IMAP UTF-7) to string.
-fn decode_modified_base64(encoded: &str) -> Option<String> {
+/// Encode UTF-16 code units as an `&...-` modified base64 run.
+fn encode_modified_base64(units: &[u16]) -> String {
+    const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+,";
+
+    let bytes: Vec<u8> = units.iter().flat_map(|u| u.to_be_bytes()).collect();
+    let mut encoded = String::from("&");
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 6 {
+            bits -= 6;
+            encoded.push(TABLE[((buffer >> bits) & 0x3f) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        encoded.push(TABLE[((buffer << (6 - bits)) & 0x3f) as usize] as char);
+    }
+
+    encoded.push('-');
+    encoded
+}
+
+/// Append the decoded UTF-16 units to `result`, or their original encoded
+/// text if they are not valid UTF-16 (e.g. an unpaired surrogate).
+fn flush_utf16(pending: &mut Vec<u16>, pending_raw: &mut String, result: &mut String) {
+    if pending.is_empty() {
+        return;
+    }
+
+    match String::from_utf16(pending) {
+        Ok(decoded) => result.push_str(&decoded),
+        Err(_) => result.push_str(pending_raw),
+    }
+    pending.clear();
+    pending_raw.clear();
+}
+
+/// Decode modified base64 (IMAP UTF-7) to UTF-16 code units.
+fn decode_modified_base64(encoded: &str) -> Option<Vec<u16>> {
     // IMAP modified base64 uses , instead of /
     let standard = encoded.replace(',', "/");
 
@@ -258,18 +459,17 @@ fn decode_modified_base64(encoded: &str) -> Option<String> {
         return None;
     }
 
-    let utf16: Vec<u16> = bytes
-        .chunks(2)
-        .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
-        .collect();
-
-    String::from_utf16(&utf16).ok()
+    Some(
+        bytes
+            .chunks(2)
+            .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+            .collect(),
+    )
 }
 
-/// Simple base64 decoder.
+/// Simple base64 decoder: whitespace (MIME line wrapping) is skipped, any
+/// other character outside the alphabet fails the decoding.
 fn base64_decode_simple(input: &str) -> Option<Vec<u8>> {
-    const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-
     let mut output = Vec::new();
     let mut buffer: u32 = 0;
     let mut bits = 0;
@@ -278,8 +478,11 @@ fn base64_decode_simple(input: &str) -> Option<Vec<u8>> {
         if c == '=' {
             break;
         }
+        if c.is_ascii_whitespace() {
+            continue;
+        }
 
-        let value = TABLE.iter().position(|&x| x == c as u8)? as u32;
+        let value = base64_value(c)? as u32;
         buffer = (buffer << 6) | value;
         bits += 6;
 
@@ -346,15 +549,120 @@ pub fn is_signature_image(
 
     if common_image_extensions.iter().any(|ext| filename_lower.ends_with(ext))
         && payload_size < 100 * 1024
+        && generic_names.iter().any(|name| filename_lower.starts_with(name))
     {
-        if generic_names.iter().any(|name| filename_lower.starts_with(name)) {
-            return true;
-        }
+        return true;
     }
 
     false
 }
 
+/// Match text against a simple wildcard pattern.
+///
+/// `*` matches any sequence of characters (including `/`), `?` matches exactly
+/// one character. Every other character, including `[` and `]`, is literal.
+pub fn matches_wildcard(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            // Let the last '*' absorb one more character and retry
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Replace accented Latin letters with their unaccented form (`Café` → `Cafe`).
+///
+/// Covers the letters used in French and other Western European languages;
+/// other characters are kept as-is.
+pub fn fold_diacritics(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.chars() {
+        let replacement = match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => "a",
+            'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' => "A",
+            'æ' => "ae",
+            'Æ' => "AE",
+            'ç' => "c",
+            'Ç' => "C",
+            'è' | 'é' | 'ê' | 'ë' => "e",
+            'È' | 'É' | 'Ê' | 'Ë' => "E",
+            'ì' | 'í' | 'î' | 'ï' => "i",
+            'Ì' | 'Í' | 'Î' | 'Ï' => "I",
+            'ñ' => "n",
+            'Ñ' => "N",
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => "o",
+            'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' => "O",
+            'œ' => "oe",
+            'Œ' => "OE",
+            'ù' | 'ú' | 'û' | 'ü' => "u",
+            'Ù' | 'Ú' | 'Û' | 'Ü' => "U",
+            'ý' | 'ÿ' => "y",
+            'Ý' | 'Ÿ' => "Y",
+            'ß' => "ss",
+            _ => {
+                folded.push(c);
+                continue;
+            }
+        };
+        folded.push_str(replacement);
+    }
+    folded
+}
+
+/// Compile a keyword entry written as `/regex/` (matched case-insensitively).
+///
+/// Returns `None` for plain entries, which are matched as substrings.
+pub fn keyword_regex(entry: &str) -> Option<Result<Regex, regex::Error>> {
+    let pattern = entry.strip_prefix('/')?.strip_suffix('/')?;
+    if pattern.is_empty() {
+        return None;
+    }
+    Some(RegexBuilder::new(pattern).case_insensitive(true).build())
+}
+
+/// Full MD5 hash as lowercase hex.
+pub fn hash_md5(text: &str) -> String {
+    format!("{:x}", md5::compute(text.as_bytes()))
+}
+
+/// Hash of a message body that ignores quoted replies and whitespace.
+///
+/// Two copies of the same email that differ only in line wrapping, blank
+/// lines or quoted text get the same hash. Returns `None` for an empty body.
+pub fn body_hash(body: &str) -> Option<String> {
+    let normalized = body
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('>'))
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if normalized.is_empty() {
+        None
+    } else {
+        Some(hash_md5(&normalized))
+    }
+}
+
 /// Generate MD5 hash prefix for uniqueness.
 pub fn hash_md5_prefix(text: &str, length: usize) -> String {
     let digest = md5::compute(text.as_bytes());
@@ -364,18 +672,198 @@ pub fn hash_md5_prefix(text: &str, length: usize) -> String {
         .collect()
 }
 
+/// Maximum length in bytes of a sanitized filename (extension included).
+const MAX_FILENAME_BYTES: usize = 200;
+
+/// Device names Windows refuses as a file stem, whatever the extension.
+const RESERVED_WINDOWS_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
 /// Sanitize filename for filesystem.
+///
+/// Replaces illegal characters, prefixes reserved Windows device names
+/// (`CON.md` → `_CON.md`) and truncates to `MAX_FILENAME_BYTES`, keeping the extension.
 pub fn sanitize_filename(filename: &str) -> String {
     let re = Regex::new(r#"[<>:"/\\|?*]"#).unwrap();
-    re.replace_all(filename, "_").to_string()
+    let mut sanitized = re.replace_all(filename, "_").to_string();
+
+    let device = sanitized.split('.').next().unwrap_or_default();
+    if RESERVED_WINDOWS_NAMES
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(device.trim_end()))
+    {
+        sanitized.insert(0, '_');
+    }
+
+    if sanitized.len() <= MAX_FILENAME_BYTES {
+        return sanitized;
+    }
+
+    // Keep a short extension, cut the stem on a char boundary
+    let (stem, extension) = match sanitized.rfind('.') {
+        Some(pos) if pos > 0 && sanitized.len() - pos <= 16 => sanitized.split_at(pos),
+        _ => (sanitized.as_str(), ""),
+    };
+
+    format!(
+        "{}{}",
+        truncate_on_char_boundary(stem, MAX_FILENAME_BYTES - extension.len()),
+        extension
+    )
+}
+
+/// Subject without its reply/forward prefixes, for threading and dedup.
+///
+/// Strips any run of `Re:`, `Fwd:`, `Fw:` and the French `Rép :`/`TR:`, in
+/// any case, with optional space before the colon and reply counters
+/// (`Re[2]:`). `"Re: RE : Fwd: Plan"` gives `"Plan"`.
+pub fn normalize_subject(subject: &str) -> String {
+    let prefix = Regex::new(r"^(?i)(re|fwd?|tr|r[ée]p)\s*(\[\d+\]|\(\d+\))?\s*:\s*").unwrap();
+    let mut rest = subject.trim();
+    while let Some(m) = prefix.find(rest) {
+        rest = &rest[m.end()..];
+    }
+    rest.trim_end().to_string()
+}
+
+/// Turn free text into a filename slug: lowercase, words joined by hyphens,
+/// other characters dropped, at most `max_bytes` bytes.
+pub fn slugify(text: &str, max_bytes: usize) -> String {
+    let mut slug = String::new();
+    let mut pending_hyphen = false;
+
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            if pending_hyphen && !slug.is_empty() {
+                slug.push('-');
+            }
+            pending_hyphen = false;
+            slug.extend(c.to_lowercase());
+        } else if c.is_whitespace() || c == '-' || c == '_' {
+            pending_hyphen = true;
+        }
+    }
+
+    truncate_on_char_boundary(&slug, max_bytes)
+        .trim_end_matches('-')
+        .to_string()
+}
+
+/// Placeholders accepted in a `filename_template`.
+pub const FILENAME_TEMPLATE_PLACEHOLDERS: [&str; 5] = ["date", "sender", "recipient", "hash", "subject"];
+
+/// Check that a filename template only uses known placeholders and contains
+/// `{hash}` or `{subject}`, so two emails of the same day and correspondents differ.
+pub fn validate_filename_template(template: &str) -> Result<(), String> {
+    let re = Regex::new(r"\{([^{}]*)\}").unwrap();
+    for cap in re.captures_iter(template) {
+        if !FILENAME_TEMPLATE_PLACEHOLDERS.contains(&&cap[1]) {
+            return Err(format!(
+                "unknown placeholder {{{}}} (expected one of {{{}}})",
+                &cap[1],
+                FILENAME_TEMPLATE_PLACEHOLDERS.join("} {")
+            ));
+        }
+    }
+
+    if !template.contains("{hash}") && !template.contains("{subject}") {
+        return Err("template must contain {hash} or {subject}".to_string());
+    }
+
+    Ok(())
+}
+
+/// Fill a filename template; `values` pairs placeholder names with their value.
+pub fn render_filename_template(template: &str, values: &[(&str, &str)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |acc, (name, value)| {
+            acc.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+/// Truncate `s` to at most `max_bytes` bytes without splitting a UTF-8 character.
+pub fn truncate_on_char_boundary(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+
+    let mut cut = max_bytes;
+    while !s.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    &s[..cut]
 }
 
-/// Get relative path between two paths.
+/// Lazily walk the exported markdown files under `directory`, skipping
+/// attachments and `.summary.md` files written by the sorter.
+pub fn markdown_files(directory: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(directory)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.path().extension().is_some_and(|ext| ext == "md")
+                && !e.path().to_string_lossy().contains("attachments")
+                && !e.file_name().to_string_lossy().ends_with(".summary.md")
+        })
+        .map(|e| e.into_path())
+}
+
+/// Remove the empty directories under `root`, deepest first, so a directory
+/// left with only empty subdirectories goes too. `root` itself is kept.
+///
+/// Returns the number of directories removed.
+pub fn cleanup_empty_dirs(root: &Path) -> std::io::Result<usize> {
+    let mut removed = 0;
+    if !root.is_dir() {
+        return Ok(removed);
+    }
+    for entry in WalkDir::new(root).min_depth(1).contents_first(true) {
+        let entry = entry.map_err(std::io::Error::other)?;
+        if entry.file_type().is_dir() && std::fs::read_dir(entry.path())?.next().is_none() {
+            std::fs::remove_dir(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Path of `to` relative to the directory `from`, with `/` separators and
+/// `..` segments where needed (`a/b` to `a/c/d` gives `../c/d`).
+///
+/// Paths without a common root (another Windows drive or UNC share, or one
+/// relative and one absolute) cannot be related: `to` is returned as is.
+/// Nothing is read from disk, so symlinks are not resolved.
 pub fn get_relative_path(from: &Path, to: &Path) -> String {
-    if let Ok(rel) = to.strip_prefix(from) {
-        rel.to_string_lossy().to_string()
+    let is_root = |c: &Component| matches!(c, Component::Prefix(_) | Component::RootDir);
+    fn parts(path: &Path) -> Vec<Component<'_>> {
+        path.components().filter(|c| *c != Component::CurDir).collect()
+    }
+    let from_parts = parts(from);
+    let to_parts = parts(to);
+
+    let from_root: Vec<_> = from_parts.iter().take_while(|c| is_root(c)).collect();
+    let to_root: Vec<_> = to_parts.iter().take_while(|c| is_root(c)).collect();
+    if from_root != to_root {
+        return to.to_string_lossy().to_string();
+    }
+
+    let common = from_parts
+        .iter()
+        .zip(&to_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let segments: Vec<String> = std::iter::repeat_n("..".to_string(), from_parts.len() - common)
+        .chain(to_parts[common..].iter().map(|c| c.as_os_str().to_string_lossy().to_string()))
+        .collect();
+
+    if segments.is_empty() {
+        ".".to_string()
     } else {
-        to.to_string_lossy().to_string()
+        segments.join("/")
     }
 }
 
@@ -386,15 +874,21 @@ mod tests {
     #[test]
     fn test_limit_quote_depth() {
         let text = "Hello\n> First quote\n>> Second quote\n>>> Third quote\n> Back to first";
-        let result = limit_quote_depth(text, 1);
+        let result = limit_quote_depth(text, QuoteDepth::Limit(1));
         let expected = "Hello\n> First quote\n> Back to first";
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_limit_quote_depth_unlimited() {
+        let text = "Hello\n> First quote\n>> Second quote\n>>> Third quote";
+        assert_eq!(limit_quote_depth(text, QuoteDepth::Unlimited), text);
+    }
+
     #[test]
     fn test_limit_quote_depth_no_quotes() {
         let text = "Hello\nWorld";
-        let result = limit_quote_depth(text, 1);
+        let result = limit_quote_depth(text, QuoteDepth::Limit(1));
         assert_eq!(result, text);
     }
 
@@ -426,6 +920,126 @@ mod tests {
         assert_eq!(result, "Hello\n\nWorld");
     }
 
+    #[test]
+    fn test_cleanup_empty_dirs() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::create_dir_all(root.join("attachments/INBOX/a/b")).unwrap();
+        std::fs::create_dir_all(root.join("attachments/Sent")).unwrap();
+        std::fs::create_dir_all(root.join("INBOX/kept")).unwrap();
+        std::fs::write(root.join("INBOX/kept/mail.md"), "x").unwrap();
+
+        assert_eq!(cleanup_empty_dirs(root).unwrap(), 5);
+        assert!(!root.join("attachments").exists());
+        assert!(root.join("INBOX/kept/mail.md").exists());
+        assert!(root.exists());
+
+        // An empty root is kept
+        std::fs::create_dir(root.join("empty")).unwrap();
+        assert_eq!(cleanup_empty_dirs(&root.join("empty")).unwrap(), 0);
+        assert!(root.join("empty").exists());
+        assert_eq!(cleanup_empty_dirs(&root.join("missing")).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_get_relative_path() {
+        // Nested
+        assert_eq!(get_relative_path(Path::new("/vault"), Path::new("/vault/attachments/a.pdf")), "attachments/a.pdf");
+        // Sibling directories
+        assert_eq!(
+            get_relative_path(Path::new("/vault/INBOX/2024"), Path::new("/vault/attachments/INBOX/a.pdf")),
+            "../../attachments/INBOX/a.pdf"
+        );
+        assert_eq!(get_relative_path(Path::new("vault/./notes"), Path::new("vault/media")), "../media");
+        assert_eq!(get_relative_path(Path::new("/vault"), Path::new("/vault")), ".");
+        // No common root
+        assert_eq!(get_relative_path(Path::new("/vault"), Path::new("media/a.png")), "media/a.png");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_get_relative_path_windows_roots() {
+        assert_eq!(get_relative_path(Path::new(r"D:\vault\notes"), Path::new(r"D:\vault\att\a.pdf")), "../att/a.pdf");
+        assert_eq!(get_relative_path(Path::new(r"D:\vault"), Path::new(r"C:\att\a.pdf")), r"C:\att\a.pdf");
+        assert_eq!(
+            get_relative_path(Path::new(r"\\server\share\vault"), Path::new(r"\\server\other\a.pdf")),
+            r"\\server\other\a.pdf"
+        );
+    }
+
+    #[test]
+    fn test_strip_html_tags() {
+        assert_eq!(strip_html_tags("<p>Hello <b>world</b></p>"), "Hello world");
+        assert_eq!(
+            strip_html_tags("<html><head><style>p { color: red; }</style></head><body><p>A &amp; B</p>\n<div>line<br>two</div></body></html>"),
+            "A & B\n\nline\ntwo"
+        );
+    }
+
+    #[test]
+    fn test_normalize_trailing_whitespace() {
+        assert_eq!(normalize_trailing_whitespace("hello   \nworld"), "hello\nworld");
+        assert_eq!(normalize_trailing_whitespace("a\t \r\n  indented\n"), "a\r\n  indented\n");
+    }
+
+    #[test]
+    fn test_decode_mime_filename() {
+        assert_eq!(decode_mime_filename("=?utf-8?q?caf=C3=A9.txt?="), "café.txt");
+        assert_eq!(decode_mime_filename("plain name.pdf"), "plain name.pdf");
+
+        // Two encoded words: the folding whitespace between them goes away
+        assert_eq!(
+            decode_mime_filename("=?utf-8?B?UmFwcG9ydF8=?= =?utf-8?B?w6l0w6kucGRm?="),
+            "Rapport_été.pdf"
+        );
+        // Text around encoded words is kept
+        assert_eq!(decode_mime_filename("v2 =?utf-8?q?r=C3=A9sum=C3=A9?= final.pdf"), "v2 résumé final.pdf");
+    }
+
+    #[test]
+    fn test_verbosity_shows() {
+        assert!(Verbosity::Normal.shows(Verbosity::Normal));
+        assert!(Verbosity::Verbose.shows(Verbosity::Normal));
+        assert!(!Verbosity::Quiet.shows(Verbosity::Normal));
+        assert!(!Verbosity::Normal.shows(Verbosity::Verbose));
+    }
+
+    #[test]
+    fn test_normalize_subject() {
+        assert_eq!(normalize_subject("Re: Re: Fwd: Plan"), "Plan");
+        assert_eq!(normalize_subject("RE : Fw: plan de charge "), "plan de charge");
+        assert_eq!(normalize_subject("Re[2]: Re(3): Plan"), "Plan");
+        assert_eq!(normalize_subject("Rép : TR: Réunion"), "Réunion");
+        assert_eq!(normalize_subject("RÉP: tr : Réunion"), "Réunion");
+        // Only leading prefixes; words that merely start like one stay
+        assert_eq!(normalize_subject("Plan: Re: budget"), "Plan: Re: budget");
+        assert_eq!(normalize_subject("Trip: Rome"), "Trip: Rome");
+        assert_eq!(normalize_subject("Re:"), "");
+    }
+
+    #[test]
+    fn test_decode_mime_header() {
+        assert_eq!(decode_mime_header("=?UTF-8?Q?Re=3A_caf=C3=A9?="), "Re: café");
+        assert_eq!(decode_mime_header("Fwd: =?iso-8859-1?q?r=E9union?= demain"), "Fwd: réunion demain");
+        assert_eq!(decode_mime_header("Plain subject"), "Plain subject");
+        // Unknown encoding: kept as is
+        assert_eq!(decode_mime_header("=?utf-8?x?abc?="), "=?utf-8?x?abc?=");
+    }
+
+    #[test]
+    fn test_base64_decoders_skip_line_breaks() {
+        let wrapped = "Q29tcHRlIHJlbmR1IGRlIGxhIHLDqXVuaW9uIGR1IDEyIG1hcnMsIHZlcnNpb24g\r\nZMOpZmluaXRpdmUucGRm";
+        let expected = "Compte rendu de la réunion du 12 mars, version définitive.pdf";
+        assert_eq!(base64_decode(wrapped, "utf-8"), Ok(expected.to_string()));
+        assert_eq!(base64_decode_simple(wrapped), Some(expected.as_bytes().to_vec()));
+        assert_eq!(base64_decode_simple("w6l0 w6ku\ncGRm"), Some("été.pdf".as_bytes().to_vec()));
+
+        // URL-safe alphabet
+        assert_eq!(base64_decode_simple("-_-_"), base64_decode_simple("+/+/"));
+        assert_eq!(base64_decode_simple("-_-_"), Some(vec![0xfb, 0xff, 0xbf]));
+        assert_eq!(base64_decode_simple("w6l0*"), None);
+    }
+
     #[test]
     fn test_is_signature_image() {
         assert!(is_signature_image(Some("signature.png"), "image/png", 1024, Some("inline")));
@@ -440,6 +1054,18 @@ mod tests {
         assert_eq!(hash.len(), 6);
     }
 
+    #[test]
+    fn test_matches_wildcard() {
+        assert!(matches_wildcard("Archive/*", "Archive/2023"));
+        assert!(!matches_wildcard("Archive/*", "Archived"));
+        assert!(matches_wildcard("[Gmail]/*", "[Gmail]/Spam"));
+        assert!(!matches_wildcard("[Gmail]/*", "G/Spam"));
+        assert!(matches_wildcard("Draft?", "Drafts"));
+        assert!(!matches_wildcard("Draft?", "Draft"));
+        assert!(matches_wildcard("Trash", "Trash"));
+        assert!(!matches_wildcard("Trash", "Trash/Old"));
+    }
+
     // [2] Tests ameliores pour UTF-7 IMAP
     #[test]
     fn test_decode_imap_utf7_no_encoding() {
@@ -461,10 +1087,58 @@ mod tests {
         assert_eq!(result, "é");
     }
 
+    #[test]
+    fn test_decode_imap_utf7_non_bmp() {
+        // U+1F4C1 (📁) is the surrogate pair D83D DCC1
+        assert_eq!(decode_imap_utf7("&2D3cwQ- Projets"), "📁 Projets");
+        // Same pair split across two encoded runs
+        assert_eq!(decode_imap_utf7("&2D0-&3ME-"), "📁");
+        // A lone high surrogate keeps its original encoding
+        assert_eq!(decode_imap_utf7("Old &2D0- box"), "Old &2D0- box");
+    }
+
+    #[test]
+    fn test_encode_imap_utf7_round_trip() {
+        assert_eq!(encode_imap_utf7("INBOX"), "INBOX");
+        assert_eq!(encode_imap_utf7("Envoyés"), "Envoy&AOk-s");
+        assert_eq!(encode_imap_utf7("Tom & Jerry"), "Tom &- Jerry");
+
+        for name in ["INBOX", "Envoyés", "Tom & Jerry", "📁 Projets/Été", "日本語"] {
+            assert_eq!(decode_imap_utf7(&encode_imap_utf7(name)), name);
+        }
+    }
+
     #[test]
     fn test_decode_imap_utf7_complex_folder() {
         // Test folder name with accented characters
         let result = decode_imap_utf7("INBOX.Envoy&AOk-s");
         assert_eq!(result, "INBOX.Envoyés");
     }
+
+    #[test]
+    fn test_fold_diacritics() {
+        assert_eq!(fold_diacritics("Café"), "Cafe");
+        assert_eq!(fold_diacritics("résumé"), "resume");
+        assert_eq!(fold_diacritics("Œuvre à Noël, ça"), "OEuvre a Noel, ca");
+        assert_eq!(fold_diacritics("Straße"), "Strasse");
+        assert_eq!(fold_diacritics("plain ascii 123 → ok"), "plain ascii 123 → ok");
+    }
+
+    #[test]
+    fn test_redact_email() {
+        assert_eq!(redact_email("john@example.com"), "j***@e***.com");
+        assert_eq!(redact_email(" John@Example.COM "), "j***@e***.com");
+        assert_eq!(redact_email("a.b+tag@mail.example.co.uk"), "a***@m***.uk");
+        assert_eq!(redact_email("root@localhost"), "r***@l***");
+        assert_eq!(redact_email("not an address"), "***");
+    }
+
+    #[test]
+    fn test_redact_emails() {
+        assert_eq!(
+            redact_emails("John Doe <john@example.com>, jane@test.org wrote to John@Example.com"),
+            "John Doe <j***@e***.com>, j***@t***.org wrote to j***@e***.com"
+        );
+        assert_eq!(redact_emails("no addresses here"), "no addresses here");
+    }
 }
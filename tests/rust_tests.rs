@@ -1,7 +1,6 @@
-use email_to_markdown::config::{SortConfig, Config, Account, Settings, AccountBehavior};
+use email_to_markdown::config::{AccountAuth, SortConfig, Config, ConfigError, FilenameRecipient, MissingDatePolicy, QuoteDepth, Settings};
 use email_to_markdown::network::{NetworkConfig, ProgressIndicator};  // [3][4]
 use email_to_markdown::utils::*;
-use std::path::PathBuf;
 use std::time::Duration;
 use tempfile::TempDir;
 
@@ -11,7 +10,7 @@ mod utils_tests {
     #[test]
     fn test_limit_quote_depth_basic() {
         let text = "Hello\n> First quote\n>> Second quote\n>>> Third quote\n> Back to first";
-        let result = limit_quote_depth(text, 1);
+        let result = limit_quote_depth(text, QuoteDepth::Limit(1));
         let expected = "Hello\n> First quote\n> Back to first";
         assert_eq!(result, expected);
     }
@@ -19,7 +18,7 @@ mod utils_tests {
     #[test]
     fn test_limit_quote_depth_no_quotes() {
         let text = "Hello\nWorld";
-        let result = limit_quote_depth(text, 1);
+        let result = limit_quote_depth(text, QuoteDepth::Limit(1));
         assert_eq!(result, text);
     }
 
@@ -60,6 +59,22 @@ mod utils_tests {
         assert_eq!(result, vec!["a@b.com", "c@d.com"]);
     }
 
+    #[test]
+    fn test_extract_emails_group_syntax() {
+        let result = extract_emails(Some(
+            "Team: Alice <alice@x.com>, bob@y.com, <Carol@Z.org>;, alice@x.com",
+        ));
+        assert_eq!(result, vec!["alice@x.com", "bob@y.com", "carol@z.org"]);
+    }
+
+    #[test]
+    fn test_extract_emails_ignores_urls() {
+        let result = extract_emails(Some(
+            "See https://user@cdn.example.com/track?id=1 or write to help@example.com",
+        ));
+        assert_eq!(result, vec!["help@example.com"]);
+    }
+
     #[test]
     fn test_extract_emails_none() {
         let result = extract_emails(None);
@@ -135,6 +150,61 @@ mod utils_tests {
         assert!(!result.contains(':'));
     }
 
+    #[test]
+    fn test_sanitize_filename_reserved_windows_names() {
+        assert_eq!(sanitize_filename("CON.md"), "_CON.md");
+        assert_eq!(sanitize_filename("nul"), "_nul");
+        assert_eq!(sanitize_filename("com1.txt.md"), "_com1.txt.md");
+        assert_eq!(sanitize_filename("CONSOLE.md"), "CONSOLE.md");
+    }
+
+    #[test]
+    fn test_truncate_on_char_boundary() {
+        let subject = "Réunion 🎉 demain";
+        // "Réunion " is 9 bytes; the emoji takes 4 more
+        assert_eq!(truncate_on_char_boundary(subject, 10), "Réunion ");
+        assert_eq!(truncate_on_char_boundary(subject, 13), "Réunion 🎉");
+        assert_eq!(truncate_on_char_boundary(subject, 100), subject);
+        assert_eq!(truncate_on_char_boundary(subject, 0), "");
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Re: Weekly  Report — Q3!", 40), "re-weekly-report-q3");
+        assert_eq!(slugify("Réunion d'équipe", 40), "réunion-déquipe");
+        assert_eq!(slugify("one two three", 8), "one-two");
+        assert_eq!(slugify("?!", 40), "");
+    }
+
+    #[test]
+    fn test_validate_filename_template() {
+        assert!(validate_filename_template("{date}_{sender}_{hash}").is_ok());
+        assert!(validate_filename_template("{date}-{subject}").is_ok());
+
+        let err = validate_filename_template("{date}_{sender}").unwrap_err();
+        assert!(err.contains("{hash} or {subject}"));
+        let err = validate_filename_template("{date}_{hash}_{folder}").unwrap_err();
+        assert!(err.contains("{folder}"));
+    }
+
+    #[test]
+    fn test_render_filename_template() {
+        let rendered = render_filename_template(
+            "{date}_{sender}_{hash}",
+            &[("date", "2024-01-15"), ("sender", "ALI"), ("hash", "a1b2c3")],
+        );
+        assert_eq!(rendered, "2024-01-15_ALI_a1b2c3");
+    }
+
+    #[test]
+    fn test_sanitize_filename_truncates_long_names() {
+        let long_name = format!("{}.md", "é".repeat(300));
+        let result = sanitize_filename(&long_name);
+        assert!(result.len() <= 200);
+        assert!(result.ends_with(".md"));
+        assert!(result.starts_with('é'));
+    }
+
     #[test]
     fn test_decode_imap_utf7_basic() {
         let result = decode_imap_utf7("INBOX");
@@ -168,6 +238,7 @@ mod config_tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_is_whitelisted_exact_match() {
         let mut config = SortConfig::default();
         config.whitelist = vec!["important@client.com".into()];
@@ -177,6 +248,7 @@ mod config_tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_is_whitelisted_domain() {
         let mut config = SortConfig::default();
         config.whitelist = vec!["@company.com".into()];
@@ -187,6 +259,7 @@ mod config_tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_is_whitelisted_prefix() {
         let mut config = SortConfig::default();
         config.whitelist = vec!["boss@".into()];
@@ -214,6 +287,43 @@ mod config_tests {
         assert_eq!(loaded.recent_threshold_days, config.recent_threshold_days);
         assert_eq!(loaded.delete_keywords.len(), config.delete_keywords.len());
     }
+
+    #[test]
+    fn test_sort_config_validate_age_thresholds() {
+        assert!(SortConfig::default().validate().is_ok());
+
+        let config = SortConfig {
+            recent_threshold_days: 400,
+            old_threshold_days: 365,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        // Rejected at load time too
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("sort_config.json");
+        config.save(&config_path).unwrap();
+        assert!(SortConfig::load(&config_path).is_err());
+    }
+
+    #[test]
+    fn test_sort_config_validate_keyword_regex() {
+        let config = SortConfig {
+            delete_keywords: vec!["/(unclosed/".into()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_sort_config_validate_size_thresholds() {
+        let config = SortConfig {
+            small_email_threshold: 20000,
+            large_email_threshold: 10000,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
 }
 
 mod settings_tests {
@@ -228,19 +338,20 @@ mod settings_tests {
     }
 
     #[test]
+    #[allow(clippy::field_reassign_with_default)]
     fn test_settings_save_load_roundtrip() {
         let temp = TempDir::new().unwrap();
         let path = temp.path().join("settings.yaml");
 
         let mut s = Settings::default();
         s.export_base_dir = Some("/tmp/emails".to_string());
-        s.defaults.quote_depth = Some(2);
+        s.defaults.quote_depth = Some(QuoteDepth::Limit(2));
         s.defaults.skip_existing = Some(false);
         s.save(&path).unwrap();
 
         let loaded = Settings::load(&path).unwrap();
         assert_eq!(loaded.export_base_dir, Some("/tmp/emails".to_string()));
-        assert_eq!(loaded.defaults.quote_depth, Some(2));
+        assert_eq!(loaded.defaults.quote_depth, Some(QuoteDepth::Limit(2)));
         assert_eq!(loaded.defaults.skip_existing, Some(false));
     }
 
@@ -280,7 +391,7 @@ mod settings_tests {
         std::fs::write(&settings_path, settings_yaml).unwrap();
 
         let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
-        assert_eq!(config.accounts[0].quote_depth, 3);
+        assert_eq!(config.accounts[0].quote_depth, QuoteDepth::Limit(3));
         assert!(config.accounts[0].collect_contacts);
     }
 
@@ -298,7 +409,161 @@ mod settings_tests {
 
         let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
         assert!(config.accounts[0].export_directory.ends_with("custom-folder"));
-        assert_eq!(config.accounts[0].quote_depth, 5);
+        assert_eq!(config.accounts[0].quote_depth, QuoteDepth::Limit(5));
+    }
+
+    #[test]
+    fn test_config_merge_quote_depth_unlimited() {
+        let temp = TempDir::new().unwrap();
+
+        let accounts_yaml = "accounts:\n  - name: A\n    server: imap.example.com\n    port: 993\n    username: a@example.com\n  - name: B\n    server: imap.example.com\n    port: 993\n    username: b@example.com\n";
+        let accounts_path = temp.path().join("accounts.yaml");
+        std::fs::write(&accounts_path, accounts_yaml).unwrap();
+
+        let settings_yaml = "export_base_dir: /tmp/emails\ndefaults:\n  quote_depth: all\naccounts:\n  B:\n    quote_depth: 2\n";
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(&settings_path, settings_yaml).unwrap();
+
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        assert_eq!(config.accounts[0].quote_depth, QuoteDepth::Unlimited);
+        assert_eq!(config.accounts[1].quote_depth, QuoteDepth::Limit(2));
+
+        std::fs::write(&settings_path, "export_base_dir: /tmp/emails\ndefaults:\n  quote_depth: some\n").unwrap();
+        let settings = Settings::load(&settings_path);
+        assert!(settings.is_err());
+    }
+
+    #[test]
+    fn test_config_merge_filename_recipient() {
+        let temp = TempDir::new().unwrap();
+
+        let accounts_yaml = "accounts:\n  - name: A\n    server: imap.example.com\n    port: 993\n    username: a@example.com\n  - name: B\n    server: imap.example.com\n    port: 993\n    username: b@example.com\n  - name: C\n    server: imap.example.com\n    port: 993\n    username: c@example.com\n";
+        let accounts_path = temp.path().join("accounts.yaml");
+        std::fs::write(&accounts_path, accounts_yaml).unwrap();
+
+        let settings_yaml = "export_base_dir: /tmp/emails\ndefaults:\n  filename_recipient: first_other\naccounts:\n  B:\n    filename_recipient:\n      label: ME\n";
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(&settings_path, settings_yaml).unwrap();
+
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        assert_eq!(config.accounts[0].filename_recipient, FilenameRecipient::FirstOther);
+        assert_eq!(config.accounts[1].filename_recipient, FilenameRecipient::Label("ME".to_string()));
+        assert_eq!(config.accounts[2].filename_recipient, FilenameRecipient::FirstOther);
+
+        // The label form survives a save
+        let settings = Settings::load(&settings_path).unwrap();
+        settings.save(&settings_path).unwrap();
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        assert_eq!(config.accounts[1].filename_recipient, FilenameRecipient::Label("ME".to_string()));
+
+        std::fs::write(&settings_path, "export_base_dir: /tmp/emails\ndefaults:\n  filename_recipient: last\n").unwrap();
+        assert!(Settings::load(&settings_path).is_err());
+    }
+
+    #[test]
+    fn test_config_oauth2_token_from_env() {
+        let temp = TempDir::new().unwrap();
+
+        let accounts_yaml = "accounts:\n  - name: OAuthTest\n    server: imap.gmail.com\n    port: 993\n    username: a@gmail.com\n";
+        let accounts_path = temp.path().join("accounts.yaml");
+        std::fs::write(&accounts_path, accounts_yaml).unwrap();
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(&settings_path, "export_base_dir: /tmp/emails\n").unwrap();
+
+        std::env::set_var("OAUTHTEST_OAUTH2_TOKEN", "ya29.token");
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        std::env::remove_var("OAUTHTEST_OAUTH2_TOKEN");
+
+        let account = &config.accounts[0];
+        assert_eq!(account.auth, AccountAuth::OAuth2 { access_token: "ya29.token".to_string() });
+        assert!(account.password.is_none());
+        assert!(account.has_credentials());
+    }
+
+    #[test]
+    fn test_config_password_env_template() {
+        let temp = TempDir::new().unwrap();
+
+        let accounts_yaml = "accounts:\n  - name: my-mail.com\n    server: imap.example.com\n    port: 993\n    username: a@example.com\n";
+        let accounts_path = temp.path().join("accounts.yaml");
+        std::fs::write(&accounts_path, accounts_yaml).unwrap();
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(
+            &settings_path,
+            "export_base_dir: /tmp/emails\npassword_env_template: EMAIL_PASSWORD_{name}\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            email_to_markdown::config::password_env_vars("my-mail.com", Some("EMAIL_PASSWORD_{name}")),
+            vec!["EMAIL_PASSWORD_MY_MAIL_COM", "MY_MAIL_COM_APPLICATION_PASSWORD", "MY_MAIL_COM_PASSWORD"]
+        );
+
+        // The template wins over the built-in names
+        std::env::set_var("EMAIL_PASSWORD_MY_MAIL_COM", "from-template");
+        std::env::set_var("MY_MAIL_COM_PASSWORD", "built-in");
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        assert_eq!(config.accounts[0].password.as_deref(), Some("from-template"));
+
+        std::env::remove_var("EMAIL_PASSWORD_MY_MAIL_COM");
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        std::env::remove_var("MY_MAIL_COM_PASSWORD");
+        assert_eq!(config.accounts[0].password.as_deref(), Some("built-in"));
+    }
+
+    #[test]
+    fn test_config_describe_sources() {
+        let temp = TempDir::new().unwrap();
+
+        let accounts_yaml = "accounts:\n  - name: Describe\n    server: imap.example.com\n    port: 993\n    username: d@example.com\n";
+        let accounts_path = temp.path().join("accounts.yaml");
+        std::fs::write(&accounts_path, accounts_yaml).unwrap();
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(
+            &settings_path,
+            "defaults:\n  collect_contacts: true\n  redact: true\naccounts:\n  Describe:\n    redact: false\n",
+        )
+        .unwrap();
+
+        let description = Config::describe(&accounts_path, &settings_path, "describe").unwrap();
+        assert!(description.starts_with("Account: Describe\n"));
+        assert!(description.contains("  server: imap.example.com  (accounts.yaml)\n"));
+        assert!(description.contains("  collect_contacts: true  (settings.yaml defaults)\n"));
+        assert!(description.contains("  redact: false  (settings.yaml accounts.Describe)\n"));
+        assert!(description.contains("  skip_existing: true  (built-in default)\n"));
+        assert!(description.contains("  credentials: none (set DESCRIBE_PASSWORD or DESCRIBE_OAUTH2_TOKEN)  (environment)"));
+        // Loading fails on the missing export_base_dir; describing reports it
+        assert!(description.contains("(not configured: set export_base_dir in settings.yaml)"));
+        assert!(description.contains("\nProblem: Configuration validation error: Export directory not configured"));
+
+        std::fs::write(&settings_path, "export_base_dir: /tmp/emails\naccounts:\n  Describe:\n    folder_name: perso\n").unwrap();
+        let description = Config::describe(&accounts_path, &settings_path, "Describe").unwrap();
+        assert!(description.contains(
+            "  export_directory: /tmp/emails/perso  (export_base_dir '/tmp/emails' + folder_name from settings.yaml accounts.Describe)\n"
+        ));
+        assert!(!description.contains("Problem:"));
+
+        assert!(matches!(
+            Config::describe(&accounts_path, &settings_path, "Missing"),
+            Err(ConfigError::AccountNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_config_merge_missing_date_policy() {
+        let temp = TempDir::new().unwrap();
+
+        let accounts_yaml = "accounts:\n  - name: A\n    server: imap.example.com\n    port: 993\n    username: a@example.com\n  - name: B\n    server: imap.example.com\n    port: 993\n    username: b@example.com\n";
+        let accounts_path = temp.path().join("accounts.yaml");
+        std::fs::write(&accounts_path, accounts_yaml).unwrap();
+
+        let settings_yaml = "export_base_dir: /tmp/emails\naccounts:\n  B:\n    missing_date_policy: use_received_header\n";
+        let settings_path = temp.path().join("settings.yaml");
+        std::fs::write(&settings_path, settings_yaml).unwrap();
+
+        let config = Config::load_with_settings(&accounts_path, &settings_path).unwrap();
+        assert_eq!(config.accounts[0].missing_date_policy, MissingDatePolicy::UseToday);
+        assert_eq!(config.accounts[1].missing_date_policy, MissingDatePolicy::UseReceivedHeader);
     }
 
     #[test]
@@ -355,8 +620,8 @@ mod email_export_tests {
         collector.add(&EmailType::Direct, "test@example.com".to_string());
         collector.add(&EmailType::Group, "group@example.com".to_string());
 
-        assert!(collector.direct.contains("test@example.com"));
-        assert!(collector.group.contains("group@example.com"));
+        assert!(collector.direct.contains_key("test@example.com"));
+        assert!(collector.group.contains_key("group@example.com"));
     }
 
     #[test]
@@ -370,6 +635,7 @@ mod email_export_tests {
 
 mod fix_yaml_tests {
     use email_to_markdown::fix_yaml::*;
+    use email_to_markdown::utils::Verbosity;
 
     #[test]
     fn test_fix_complex_yaml_tags_python_object() {
@@ -403,6 +669,70 @@ mod fix_yaml_tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_scan_and_fix_directory_counts() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let nested = temp.path().join("INBOX/2024");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(temp.path().join("attachments")).unwrap();
+
+        let clean = "---\nfrom: a@example.com\nsubject: Hi\n---\n\nBody";
+        let broken = "---\nfrom: a@example.com\nsubject: !!python/object:email.header.Header\n  _chunks: [- 'Hello']\n---\n\nBody";
+        std::fs::write(temp.path().join("one.md"), clean).unwrap();
+        std::fs::write(nested.join("two.md"), broken).unwrap();
+        std::fs::write(nested.join("notes.txt"), broken).unwrap();
+        std::fs::write(temp.path().join("attachments/three.md"), broken).unwrap();
+
+        let stats = scan_and_fix_directory(temp.path(), true, false, DuplicateKeyPolicy::default(), Verbosity::Quiet).unwrap();
+        assert_eq!(stats.total_scanned, 2);
+        assert_eq!(stats.files_fixed, 1);
+        assert_eq!(stats.errors, 0);
+
+        let mut calls = Vec::new();
+        let stats = scan_and_fix_directory_with_progress(temp.path(), true, false, DuplicateKeyPolicy::default(), Verbosity::Quiet, |current, total| {
+            calls.push((current, total))
+        })
+        .unwrap();
+        assert_eq!(stats.total_scanned, 2);
+        assert_eq!(calls, vec![(1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn test_fix_duplicate_key_policy() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let duplicated = "---\nfrom: old@example.com\nsubject: Hi\nfrom: new@example.com\n---\n\n**Bold** *x* & Tom &amp; Jerry\n";
+        for (policy, kept) in [
+            (DuplicateKeyPolicy::KeepLast, "new@example.com"),
+            (DuplicateKeyPolicy::KeepFirst, "old@example.com"),
+        ] {
+            let file = temp.path().join("mail.md");
+            std::fs::write(&file, duplicated).unwrap();
+
+            let stats = scan_and_fix_directory(temp.path(), false, false, policy, Verbosity::Quiet).unwrap();
+            assert_eq!(stats.files_fixed, 1);
+            let fixed = std::fs::read_to_string(&file).unwrap();
+            assert!(fixed.contains(&format!("from: {}\n", kept)), "{:?}: {}", policy, fixed);
+            assert_eq!(fixed.matches("from:").count(), 1);
+            assert!(fixed.ends_with("---\n\n**Bold** *x* & Tom &amp; Jerry\n"), "{:?}: {}", policy, fixed);
+        }
+    }
+
+    #[test]
+    fn test_fix_with_backup_and_restore() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let file = temp.path().join("mail.md");
+        let broken = "---\nfrom: a@example.com\nsubject: !!python/object:email.header.Header\n  _chunks: [- 'Hello']\n---\n\nBody";
+        std::fs::write(&file, broken).unwrap();
+
+        assert!(fix_email_file(&file, false, true, DuplicateKeyPolicy::default(), Verbosity::Normal).unwrap());
+        assert_ne!(std::fs::read_to_string(&file).unwrap(), broken);
+        assert_eq!(std::fs::read_to_string(backup_path(&file)).unwrap(), broken);
+
+        assert_eq!(restore_backups(temp.path(), Verbosity::Normal).unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), broken);
+        assert!(!backup_path(&file).exists());
+    }
+
     #[test]
     fn test_extract_frontmatter_no_opening() {
         let content = "from: test@example.com\n---\n\nBody content";
@@ -421,6 +751,7 @@ mod sort_emails_tests {
         assert_eq!(Category::Delete.to_string(), "delete");
         assert_eq!(Category::Summarize.to_string(), "summarize");
         assert_eq!(Category::Keep.to_string(), "keep");
+        assert_eq!(Category::Review.to_string(), "review");
     }
 
     #[test]
@@ -430,6 +761,55 @@ mod sort_emails_tests {
         assert_eq!(EmailSortType::Group.to_string(), "group");
     }
 
+    #[test]
+    fn test_sort_emails_counts() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let nested = temp.path().join("INBOX");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        for (i, dir) in [temp.path(), nested.as_path(), nested.as_path()].iter().enumerate() {
+            let content = format!(
+                "---\nfrom: sender{}@example.com\nto: me@example.com\nsubject: Message {}\ndate: 2024-01-15T10:00:00+00:00\n---\n\nHello there, this is message {}.",
+                i, i, i
+            );
+            std::fs::write(dir.join(format!("email_{}.md", i)), content).unwrap();
+        }
+        std::fs::write(nested.join("empty.md"), "").unwrap();
+
+        let mut sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default());
+        sorter.sort_emails().unwrap();
+
+        let stats = sorter.stats();
+        assert_eq!(stats.total_emails, 3);
+        assert_eq!(stats.by_category.values().sum::<usize>(), 3);
+        assert_eq!(stats.by_sender.len(), 3);
+    }
+
+    #[test]
+    fn test_sort_report_independent_of_thread_count() {
+        let temp = tempfile::TempDir::new().unwrap();
+        for i in 0..40 {
+            let content = format!(
+                "---\nfrom: sender{}@example.com\nto: me@example.com\nsubject: {} {}\ndate: 2024-0{}-15T10:00:00+00:00\n---\n\nMessage body number {}.",
+                i % 7,
+                if i % 3 == 0 { "Newsletter" } else { "Contract" },
+                i,
+                1 + i % 9,
+                i
+            );
+            std::fs::write(temp.path().join(format!("email_{:02}.md", i)), content).unwrap();
+        }
+
+        let report_with = |threads: usize| {
+            let mut sorter = EmailSorter::new(temp.path().to_path_buf(), SortConfig::default())
+                .with_threads(threads);
+            sorter.sort_emails().unwrap();
+            serde_json::to_value(sorter.generate_report()).unwrap()
+        };
+
+        assert_eq!(report_with(1), report_with(4));
+    }
+
     #[test]
     fn test_email_sorter_new() {
         let config = SortConfig::default();
@@ -512,6 +892,7 @@ mod network_tests {
     }
 
     #[test]
+    #[allow(unused_variables, clippy::assertions_on_constants)]
     fn test_progress_indicator_create() {
         let progress = ProgressIndicator::new("Test", 100);
         // Just verify it creates without panic
@@ -522,8 +903,7 @@ mod network_tests {
     fn test_progress_indicator_update() {
         let mut progress = ProgressIndicator::new("Test", 10);
         progress.update(5);
-        progress.inc();
         // Verify it updates without panic
-        assert!(true);
+        progress.inc();
     }
 }